@@ -0,0 +1,83 @@
+//! What a frame costs on the biggest map: drawing the overworld, and the player's collision
+//! rect that movement and the camera ask for several times per update. Each one also runs
+//! the way it was before sprites were cached, parsing the ANSI art again every time, to
+//! keep the difference in view.
+//!
+//! `cargo bench --bench frame`
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use ratatui::{Terminal, backend::TestBackend, layout::Rect};
+use std::hint::black_box;
+use std::sync::OnceLock;
+use under_term::assets::{self, Sprite};
+use under_term::game::map::Map;
+use under_term::game::state::GameState;
+use under_term::harness::TestHarness;
+use under_term::ui;
+
+fn overworld() -> TestHarness {
+    let mut harness = TestHarness::new(Map::load("map_1_2").unwrap());
+    harness.frame_size = Rect::new(0, 0, 225, 56);
+    harness.game_state.player.x = 150.0;
+    harness.game_state.player.y = 10.0;
+    harness.update();
+    harness
+}
+
+// throws away the parsed map and placed sprites, so the next draw parses them again
+fn forget_parsed_sprites(game_state: &mut GameState) {
+    for map in game_state.loaded_maps.values_mut() {
+        map.parsed = OnceLock::new();
+        for placed_sprite in &mut map.placed_sprites {
+            placed_sprite.parsed = OnceLock::new();
+        }
+    }
+}
+
+// what every look at the player's sprite used to cost
+fn parse_player_sprite(game_state: &GameState) -> Sprite {
+    let path = game_state.player.sprite_path();
+    Sprite::parse(&assets::store().load_str(path).unwrap()).unwrap()
+}
+
+fn frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw_overworld");
+    let mut harness = overworld();
+    let mut terminal = Terminal::new(TestBackend::new(225, 56)).unwrap();
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            terminal
+                .draw(|frame| ui::draw(frame, &mut harness.game_state))
+                .unwrap();
+        })
+    });
+    group.bench_function("parsing_every_frame", |b| {
+        b.iter(|| {
+            forget_parsed_sprites(&mut harness.game_state);
+            black_box(parse_player_sprite(&harness.game_state));
+            terminal
+                .draw(|frame| ui::draw(frame, &mut harness.game_state))
+                .unwrap();
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("player_collision_rect");
+    let harness = overworld();
+    group.bench_function("cached", |b| {
+        b.iter(|| black_box(harness.game_state.player.get_collision_rect()))
+    });
+    group.bench_function("parsing_every_call", |b| {
+        b.iter(|| {
+            black_box(parse_player_sprite(&harness.game_state));
+            black_box(harness.game_state.player.get_collision_rect())
+        })
+    });
+    group.finish();
+
+    let mut harness = overworld();
+    c.bench_function("update", |b| b.iter(|| harness.update()));
+}
+
+criterion_group!(benches, frame);
+criterion_main!(benches);
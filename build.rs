@@ -0,0 +1,83 @@
+// Walks assets/ and writes $OUT_DIR/asset_manifest.rs, which src/assets.rs includes.
+// Every file gets embedded with include_bytes!, keyed by its path relative to assets/,
+// and every directory gets a listing of what's directly inside it.
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn logical_path(assets_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(assets_dir)
+        .unwrap()
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn main() -> io::Result<()> {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let assets_dir = manifest_dir.join("assets");
+    println!("cargo:rerun-if-changed=assets");
+
+    let mut files = Vec::new();
+    collect_files(&assets_dir, &mut files)?;
+
+    let mut entries: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for file in files {
+        entries.insert(logical_path(&assets_dir, &file), file);
+    }
+
+    // directory -> names of the files and directories right under it
+    let mut directories: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for path in entries.keys() {
+        let mut parent = String::new();
+        for part in path.split('/') {
+            let children = directories.entry(parent.clone()).or_default();
+            if !children.iter().any(|child| child == part) {
+                children.push(part.to_string());
+            }
+            if !parent.is_empty() {
+                parent.push('/');
+            }
+            parent.push_str(part);
+        }
+    }
+
+    for children in directories.values_mut() {
+        children.sort();
+    }
+
+    let mut out = String::new();
+    out.push_str("pub static EMBEDDED_ASSETS: &[(&str, &[u8])] = &[\n");
+    for (path, file) in &entries {
+        out.push_str(&format!(
+            "    ({:?}, include_bytes!({:?})),\n",
+            path,
+            file.display().to_string()
+        ));
+    }
+    out.push_str("];\n\n");
+
+    out.push_str("pub static EMBEDDED_DIRECTORIES: &[(&str, &[&str])] = &[\n");
+    for (directory, children) in &directories {
+        out.push_str(&format!("    ({:?}, &{:?}),\n", directory, children));
+    }
+    out.push_str("];\n");
+
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("asset_manifest.rs");
+    fs::write(out_path, out)
+}
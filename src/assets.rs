@@ -1,122 +1,391 @@
-#[macro_export]
-macro_rules! load_sprite_asset_str {
-    ($path:expr) => {
-        match $path {
-            "assets/sprites/animation/0.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/animation/0.ans"
-            )),
-            "assets/sprites/animation/1.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/animation/1.ans"
-            )),
-            "assets/sprites/animation/2.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/animation/2.ans"
-            )),
-            "assets/sprites/animation/3.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/animation/3.ans"
-            )),
-            "assets/sprites/animation/4.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/animation/4.ans"
-            )),
-            "assets/sprites/animation/5.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/animation/5.ans"
-            )),
-            "assets/sprites/faces/face_3.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_3.ans"
-            )),
-            "assets/sprites/faces/face_determined_smile.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_determined_smile.ans"
-            )),
-            "assets/sprites/faces/face_determined.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_determined.ans"
-            )),
-            "assets/sprites/faces/face_hehehe.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_hehehe.ans"
-            )),
-            "assets/sprites/faces/face_huh.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_huh.ans"
-            )),
-            "assets/sprites/faces/face_meh.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_meh.ans"
-            )),
-            "assets/sprites/faces/face_neutral.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_neutral.ans"
-            )),
-            "assets/sprites/faces/face_sight.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_sight.ans"
-            )),
-            "assets/sprites/faces/face_smile.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/faces/face_smile.ans"
-            )),
-            "assets/sprites/enemy/not_a_placeholder/battle_3.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/enemy/not_a_placeholder/battle_3.ans"
-            )),
-            "assets/sprites/enemy/not_a_placeholder/battle_hehehe.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/enemy/not_a_placeholder/battle_hehehe.ans"
-            )),
-            "assets/sprites/enemy/not_a_placeholder/battle_meh.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/enemy/not_a_placeholder/battle_meh.ans"
-            )),
-            "assets/sprites/enemy/not_a_placeholder/battle_more_neutral.ans" => {
-                include_str!(concat!(
-                    env!("CARGO_MANIFEST_DIR"),
-                    "/assets/sprites/enemy/not_a_placeholder/battle_more_neutral.ans"
-                ))
+use ansi_to_tui::IntoText;
+use ratatui::text::Text;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Points the game at an asset directory on disk, same as `--assets <dir>`.
+pub const ASSET_DIR_ENV_VAR: &str = "UNDER_TERM_ASSETS";
+
+#[derive(Debug)]
+pub enum AssetError {
+    NotFound(String),
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: String, message: String },
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::NotFound(path) => write!(f, "asset {} not found", path),
+            AssetError::Io { path, source } => {
+                write!(f, "failed to read asset {}: {}", path.display(), source)
+            }
+            AssetError::Parse { path, message } => {
+                write!(f, "failed to parse asset {}: {}", path, message)
             }
-            "assets/sprites/enemy/not_a_placeholder/battle_neutral.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/enemy/not_a_placeholder/battle_neutral.ans"
-            )),
-            "assets/sprites/enemy/not_a_placeholder/battle_smile.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/enemy/not_a_placeholder/battle_smile.ans"
-            )),
-            "assets/sprites/ME/idle/insanly_dead.ans" => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/sprites/ME/idle/insanly_dead.ans"
-            )),
-            _ => "",
         }
-    };
+    }
 }
-#[macro_export]
-macro_rules! load_map_asset_str {
-    ($map_name:expr, $file_name:expr) => {
-        match ($map_name, $file_name) {
-            ("map_0_0", "data.json") => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/map/map_0_0/data.json"
-            )),
-            ("map_0_0", "sprite.ans") => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/map/map_0_0/sprite.ans"
-            )),
-            ("map_1_2", "data.json") => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/map/map_1_2/data.json"
-            )),
-            ("map_1_2", "sprite.ans") => include_str!(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/assets/map/map_1_2/sprite.ans"
-            )),
-            _ => "",
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssetError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// ANSI art parsed once, with the size it takes on screen.
+#[derive(Debug, Clone, Default)]
+pub struct Sprite {
+    pub text: Text<'static>,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Sprite {
+    /// Shows `error` where art that couldn't be loaded would have gone.
+    pub fn error(error: &dyn fmt::Display) -> Self {
+        let text = Text::raw(error.to_string());
+        Sprite {
+            width: text.width() as u16,
+            height: text.height() as u16,
+            text,
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self, ansi_to_tui::Error> {
+        let text = content.as_bytes().into_text()?;
+        let width = text
+            .lines
+            .iter()
+            .map(|line| line.width() as u16)
+            .max()
+            .unwrap_or(0);
+        let height = text.lines.len() as u16;
+        Ok(Sprite {
+            text,
+            width,
+            height,
+        })
+    }
+}
+
+/// Resolves logical asset paths like `sprites/faces/face_3.ans` (a leading `assets/` is
+/// fine too). Files in the asset directory win, anything else comes from the copy
+/// embedded in the binary.
+pub struct AssetStore {
+    root: Option<PathBuf>,
+    sprite_cache: Mutex<HashMap<String, Arc<Sprite>>>,
+}
+
+impl AssetStore {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        AssetStore {
+            root,
+            sprite_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(std::env::var_os(ASSET_DIR_ENV_VAR).map(PathBuf::from))
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Where the editors write maps and dialogues: the asset directory if there is one,
+    /// the source tree otherwise.
+    pub fn writable_root(&self) -> PathBuf {
+        self.root
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/assets")))
+    }
+
+    pub fn load_bytes(&self, path: &str) -> Result<Cow<'static, [u8]>, AssetError> {
+        let path = normalize(path);
+        match self.read_from_disk(path)? {
+            Some(content) => Ok(Cow::Owned(content)),
+            None => embedded(path)
+                .map(Cow::Borrowed)
+                .ok_or_else(|| AssetError::NotFound(path.to_string())),
+        }
+    }
+
+    pub fn load_str(&self, path: &str) -> Result<Cow<'static, str>, AssetError> {
+        let path = normalize(path);
+        let content = match self.load_bytes(path)? {
+            Cow::Borrowed(bytes) => {
+                Cow::Borrowed(std::str::from_utf8(bytes).map_err(|e| AssetError::Parse {
+                    path: path.to_string(),
+                    message: e.to_string(),
+                })?)
+            }
+            Cow::Owned(bytes) => {
+                Cow::Owned(String::from_utf8(bytes).map_err(|e| AssetError::Parse {
+                    path: path.to_string(),
+                    message: e.to_string(),
+                })?)
+            }
+        };
+        if cfg!(windows) && content.contains("\r\n") {
+            return Ok(Cow::Owned(content.replace("\r\n", "\n")));
         }
+        Ok(content)
+    }
+
+    /// Parses an ANSI art file, only the first call for a path does the work.
+    pub fn load_sprite(&self, path: &str) -> Result<Arc<Sprite>, AssetError> {
+        let key = normalize(path).to_string();
+        if let Some(sprite) = self.sprite_cache.lock().unwrap().get(&key) {
+            return Ok(Arc::clone(sprite));
+        }
+        let content = self.load_str(&key)?;
+        let sprite = Arc::new(Sprite::parse(&content).map_err(|e| AssetError::Parse {
+            path: key.clone(),
+            message: e.to_string(),
+        })?);
+        self.sprite_cache
+            .lock()
+            .unwrap()
+            .insert(key, Arc::clone(&sprite));
+        Ok(sprite)
+    }
+
+    pub fn load_text(&self, path: &str) -> Result<Text<'static>, AssetError> {
+        Ok(self.load_sprite(path)?.text.clone())
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        let path = normalize(path);
+        embedded(path).is_some()
+            || self
+                .root
+                .as_ref()
+                .is_some_and(|root| root.join(path).is_file())
+    }
+
+    /// Names of the files and directories directly inside `dir`, from both the asset
+    /// directory and the embedded bundle. `""` lists the top level.
+    pub fn list(&self, dir: &str) -> Vec<String> {
+        let dir = normalize(dir).trim_end_matches('/');
+        let mut names: Vec<String> = manifest::EMBEDDED_DIRECTORIES
+            .binary_search_by(|(path, _)| (*path).cmp(dir))
+            .map(|index| {
+                manifest::EMBEDDED_DIRECTORIES[index]
+                    .1
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if let Some(root) = &self.root
+            && let Ok(entries) = std::fs::read_dir(root.join(dir))
+        {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// Every `.ans` file under `dir`, nested directories included, as `assets/...` paths.
+    pub fn sprite_paths(&self, dir: &str) -> Vec<String> {
+        let dir = normalize(dir).trim_end_matches('/');
+        let mut paths = Vec::new();
+        for name in self.list(dir) {
+            let child = format!("{}/{}", dir, name);
+            if name.ends_with(".ans") {
+                paths.push(format!("assets/{}", child));
+            } else {
+                paths.extend(self.sprite_paths(&child));
+            }
+        }
+        paths
+    }
+
+    fn read_from_disk(&self, path: &str) -> Result<Option<Vec<u8>>, AssetError> {
+        let Some(root) = &self.root else {
+            return Ok(None);
+        };
+        let full_path = root.join(path);
+        if !full_path.is_file() {
+            return Ok(None);
+        }
+        std::fs::read(&full_path)
+            .map(Some)
+            .map_err(|source| AssetError::Io {
+                path: full_path,
+                source,
+            })
+    }
+}
+
+static STORE: OnceLock<AssetStore> = OnceLock::new();
+
+/// Sets the asset directory for the rest of the run. `None` falls back to the env var.
+/// Does nothing if the store was already used.
+pub fn init(root: Option<PathBuf>) {
+    let store = match root {
+        Some(root) => AssetStore::new(Some(root)),
+        None => AssetStore::from_env(),
     };
+    let _ = STORE.set(store);
+}
+
+pub fn store() -> &'static AssetStore {
+    STORE.get_or_init(AssetStore::from_env)
+}
+
+fn normalize(path: &str) -> &str {
+    path.strip_prefix("assets/").unwrap_or(path)
+}
+
+mod manifest {
+    include!(concat!(env!("OUT_DIR"), "/asset_manifest.rs"));
+}
+
+fn embedded(path: &str) -> Option<&'static [u8]> {
+    manifest::EMBEDDED_ASSETS
+        .binary_search_by(|(embedded_path, _)| (*embedded_path).cmp(path))
+        .ok()
+        .map(|index| manifest::EMBEDDED_ASSETS[index].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // a fresh asset directory per test, so parallel tests don't see each other's files
+    fn scratch_root() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "under_term_assets_{}_{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let full_path = root.join(path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        std::fs::write(full_path, content).unwrap();
+    }
+
+    #[test]
+    fn a_file_on_disk_wins_over_the_embedded_copy() {
+        let root = scratch_root();
+        let embedded = AssetStore::new(None)
+            .load_str("sprites/peefest.ans")
+            .unwrap();
+        write(&root, "sprites/peefest.ans", "from disk");
+
+        let store = AssetStore::new(Some(root.clone()));
+        assert_eq!(store.load_str("sprites/peefest.ans").unwrap(), "from disk");
+        assert_ne!(embedded, "from disk");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn an_asset_that_is_nowhere_is_not_found() {
+        let root = scratch_root();
+        let store = AssetStore::new(Some(root.clone()));
+        match store.load_str("sprites/nope.ans") {
+            Err(AssetError::NotFound(path)) => assert_eq!(path, "sprites/nope.ans"),
+            other => panic!("expected NotFound, got {:?}", other.map(|_| ())),
+        }
+        assert!(!store.exists("sprites/nope.ans"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn the_assets_prefix_is_optional() {
+        let store = AssetStore::new(None);
+        assert_eq!(
+            store.load_str("assets/sprites/peefest.ans").unwrap(),
+            store.load_str("sprites/peefest.ans").unwrap()
+        );
+        assert!(store.exists("assets/sprites/peefest.ans"));
+    }
+
+    #[test]
+    fn listing_merges_disk_and_embedded_entries() {
+        let root = scratch_root();
+        write(&root, "sprites/extra.ans", "x");
+        write(&root, "sprites/peefest.ans", "x");
+
+        let listed = AssetStore::new(Some(root.clone())).list("sprites");
+        assert!(listed.contains(&"extra.ans".to_string()));
+        assert!(listed.contains(&"faces".to_string()));
+        assert_eq!(
+            listed.iter().filter(|name| *name == "peefest.ans").count(),
+            1
+        );
+        assert!(listed.is_sorted());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn sprite_paths_walk_nested_directories() {
+        let root = scratch_root();
+        write(&root, "sprites/test_only/top.ans", "x");
+        write(&root, "sprites/test_only/nested/deeper/bottom.ans", "x");
+        write(&root, "sprites/test_only/notes.txt", "x");
+
+        let mut paths =
+            AssetStore::new(Some(root.clone())).sprite_paths("assets/sprites/test_only");
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                "assets/sprites/test_only/nested/deeper/bottom.ans",
+                "assets/sprites/test_only/top.ans",
+            ]
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn ansi_art_is_parsed_once() {
+        let root = scratch_root();
+        write(&root, "sprites/box.ans", "abc\nde");
+        let store = AssetStore::new(Some(root.clone()));
+
+        let first = store.load_text("sprites/box.ans").unwrap();
+        assert_eq!((first.width(), first.height()), (3, 2));
+
+        write(&root, "sprites/box.ans", "changed on disk");
+        assert_eq!(store.load_text("assets/sprites/box.ans").unwrap(), first);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn a_sprite_is_parsed_once_and_then_shared() {
+        let root = scratch_root();
+        write(&root, "sprites/box.ans", "abc\nde");
+        let store = AssetStore::new(Some(root.clone()));
+
+        let first = store.load_sprite("sprites/box.ans").unwrap();
+        assert_eq!((first.width, first.height), (3, 2));
+
+        write(&root, "sprites/box.ans", "changed on disk");
+        let second = store.load_sprite("assets/sprites/box.ans").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }
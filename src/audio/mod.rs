@@ -1,45 +1,238 @@
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Source};
-use std::io::Cursor;
+mod null_backend;
+mod rodio_backend;
 
+pub use null_backend::{NullAudio, REQUESTED_LIMIT};
+pub use rodio_backend::RodioAudio;
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// blips closer together than this are dropped
+const BLIP_INTERVAL: Duration = Duration::from_millis(45);
+
+/// Something the game asked to hear. The silent backend keeps a list of them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sound {
+    // assets/sound/<name>.mp3
+    Effect(String),
+    // a voice blip, by sound name
+    Voice(String),
+    // the track the music switched to, None when it stopped
+    Music(Option<String>),
+}
+
+fn default_voice_sound() -> String {
+    "TEXT".to_string()
+}
+
+/// How someone sounds while their text types out: a short `assets/sound/<sound>.mp3`
+/// blip, played a bit higher or lower each time by up to `pitch_variance`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Voice {
+    #[serde(default = "default_voice_sound")]
+    pub sound: String,
+    #[serde(default)]
+    pub pitch_variance: f32,
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        Voice {
+            sound: default_voice_sound(),
+            pitch_variance: 0.0,
+        }
+    }
+}
+
+/// Where sound goes: the default output device, or nowhere when there isn't one
+/// (or the game was started with `--mute`).
 pub struct Audio {
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
-    _text_sound_sink: Option<rodio::Sink>,
+    backend: Backend,
+    // when the last voice blip went out, by whatever clock the caller goes by
+    last_blip: Option<Instant>,
+}
+
+enum Backend {
+    Rodio(Box<RodioAudio>),
+    Null(NullAudio),
 }
 
 impl Audio {
-    pub fn new() -> Result<Self, rodio::StreamError> {
-        let (stream, stream_handle) = OutputStream::try_default()?;
-        Ok(Audio {
-            _stream: stream,
-            stream_handle,
-            _text_sound_sink: None,
-        })
+    /// The default output device, falling back to silence when it can't be opened.
+    pub fn open() -> Self {
+        match RodioAudio::new() {
+            Ok(audio) => Self::with_backend(Backend::Rodio(Box::new(audio))),
+            Err(_) => Audio::null(),
+        }
+    }
+
+    pub fn null() -> Self {
+        Self::with_backend(Backend::Null(NullAudio::default()))
     }
 
-    pub fn play_open_settings_sound(&self) {
-        let bytes = include_bytes!("../../assets/sound/open_settings.mp3");
-        let decoder = Decoder::new(Cursor::new(&bytes[..])).unwrap();
-        self.stream_handle
-            .play_raw(decoder.convert_samples())
-            .unwrap();
+    fn with_backend(backend: Backend) -> Self {
+        Audio {
+            backend,
+            last_blip: None,
+        }
     }
 
-    pub fn play_text_sound(&mut self) {
-        let bytes = include_bytes!("../../assets/sound/TEXT.mp3");
-        let decoder = Decoder::new(Cursor::new(&bytes[..])).unwrap();
-        let sink = rodio::Sink::try_new(&self.stream_handle).unwrap();
-        sink.set_volume(4.0);
-        sink.append(decoder.convert_samples::<f32>());
-        self._text_sound_sink = Some(sink);
+    pub fn is_muted(&self) -> bool {
+        matches!(self.backend, Backend::Null(_))
     }
 
-    pub fn play_enemy_encounter_sound(&self) {
-        let bytes = include_bytes!("../../assets/sound/enemy_encounter.mp3");
-        let decoder = Decoder::new(Cursor::new(&bytes[..])).unwrap();
-        self.stream_handle
-            .play_raw(decoder.convert_samples())
-            .unwrap();
+    /// The most recent things asked for, oldest first. Only the silent backend keeps track.
+    pub fn requested(&self) -> Vec<Sound> {
+        match &self.backend {
+            Backend::Rodio(_) => Vec::new(),
+            Backend::Null(audio) => audio.requested.iter().cloned().collect(),
+        }
+    }
+
+    /// Plays `assets/sound/<name>.mp3` once.
+    pub fn play_sound(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match &mut self.backend {
+            Backend::Rodio(audio) => audio.play_sound(name),
+            Backend::Null(audio) => audio.play_sound(name),
+        }
+    }
+
+    /// A blip in `voice` for the character typed at `now`. Spaces and punctuation stay
+    /// quiet, and so does anything typed right after the last blip.
+    pub fn play_voice(
+        &mut self,
+        voice: &Voice,
+        typed: char,
+        now: Instant,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !typed.is_alphanumeric()
+            || self
+                .last_blip
+                .is_some_and(|last_blip| now.saturating_duration_since(last_blip) < BLIP_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_blip = Some(now);
+        match &mut self.backend {
+            Backend::Rodio(audio) => audio.play_voice(voice),
+            Backend::Null(audio) => audio.play_voice(voice),
+        }
+    }
+
+    /// Crossfades to `assets/music/<track>.mp3` over `fade` and loops it. Asking for the
+    /// track that's already playing does nothing, `None` fades the music out.
+    pub fn play_music(
+        &mut self,
+        track: Option<&str>,
+        fade: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match &mut self.backend {
+            Backend::Rodio(audio) => audio.play_music(track, fade),
+            Backend::Null(audio) => audio.play_music(track),
+        }
+    }
+
+    /// Moves the crossfade along, called once a frame.
+    pub fn update(&mut self, delta_time: Duration) {
+        if let Backend::Rodio(audio) = &mut self.backend {
+            audio.update(delta_time);
+        }
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        match &self.backend {
+            Backend::Rodio(audio) => audio.music_volume(),
+            Backend::Null(audio) => audio.music_volume,
+        }
+    }
+
+    pub fn set_music_volume(&mut self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        match &mut self.backend {
+            Backend::Rodio(audio) => audio.set_music_volume(volume),
+            Backend::Null(audio) => audio.music_volume = volume,
+        }
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        match &self.backend {
+            Backend::Rodio(audio) => audio.sfx_volume(),
+            Backend::Null(audio) => audio.sfx_volume,
+        }
+    }
+
+    /// Sound effects and voice blips, the music has its own volume.
+    pub fn set_sfx_volume(&mut self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        match &mut self.backend {
+            Backend::Rodio(audio) => audio.set_sfx_volume(volume),
+            Backend::Null(audio) => audio.sfx_volume = volume,
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_narration_asks_for_voice_blips() {
+        let voice = Voice {
+            sound: "A".to_string(),
+            pitch_variance: 0.0,
+        };
+        let mut audio = Audio::null();
+        let start = Instant::now();
+        for (i, typed) in "* Hi!".chars().enumerate() {
+            let now = start + BLIP_INTERVAL * i as u32;
+            audio.play_voice(&voice, typed, now).unwrap();
+        }
+        // only the letters blip, not the space or punctuation
+        assert_eq!(audio.requested(), vec![Sound::Voice("A".to_string()); 2]);
+    }
+
+    #[test]
+    fn blips_typed_right_after_the_last_one_stay_quiet() {
+        let voice = Voice::default();
+        let mut audio = Audio::null();
+        let start = Instant::now();
+        for ms in [0, 20, 44, 45, 60, 100] {
+            let now = start + Duration::from_millis(ms);
+            audio.play_voice(&voice, 'a', now).unwrap();
+        }
+        // 0, 45 and 100 are far enough apart, the rest are dropped
+        assert_eq!(audio.requested().len(), 3);
+    }
+
+    #[test]
+    fn silent_audio_keeps_track_of_music_changes() {
+        let mut audio = Audio::null();
+        audio.play_music(Some("field"), Duration::ZERO).unwrap();
+        audio.play_music(Some("field"), Duration::ZERO).unwrap();
+        audio.play_music(None, Duration::ZERO).unwrap();
+        audio.play_sound("enemy_encounter").unwrap();
+        assert_eq!(
+            audio.requested(),
+            &[
+                Sound::Music(Some("field".to_string())),
+                Sound::Music(None),
+                Sound::Effect("enemy_encounter".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn silent_audio_only_remembers_the_latest_requests() {
+        let mut audio = Audio::null();
+        for i in 0..REQUESTED_LIMIT + 10 {
+            audio.play_sound(&i.to_string()).unwrap();
+        }
+        let requested = audio.requested();
+        assert_eq!(requested.len(), REQUESTED_LIMIT);
+        assert_eq!(requested[0], Sound::Effect("10".to_string()));
+        assert_eq!(
+            requested.last(),
+            Some(&Sound::Effect((REQUESTED_LIMIT + 9).to_string()))
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use super::{Sound, Voice};
+use crate::game::config::{DEFAULT_MUSIC_VOLUME, DEFAULT_SFX_VOLUME};
+use std::collections::VecDeque;
+
+// how many requests are remembered, a long silent run drops the oldest ones
+pub const REQUESTED_LIMIT: usize = 256;
+
+/// Plays nothing and writes down the last `REQUESTED_LIMIT` things it was asked to play.
+#[derive(Debug)]
+pub struct NullAudio {
+    pub requested: VecDeque<Sound>,
+    music: Option<String>,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+}
+
+impl Default for NullAudio {
+    fn default() -> Self {
+        NullAudio {
+            requested: VecDeque::new(),
+            music: None,
+            music_volume: DEFAULT_MUSIC_VOLUME,
+            sfx_volume: DEFAULT_SFX_VOLUME,
+        }
+    }
+}
+
+impl NullAudio {
+    pub fn play_sound(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.remember(Sound::Effect(name.to_string()));
+        Ok(())
+    }
+
+    pub fn play_voice(&mut self, voice: &Voice) -> Result<(), Box<dyn std::error::Error>> {
+        self.remember(Sound::Voice(voice.sound.clone()));
+        Ok(())
+    }
+
+    pub fn play_music(&mut self, track: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.music.as_deref() != track {
+            self.music = track.map(str::to_string);
+            self.remember(Sound::Music(self.music.clone()));
+        }
+        Ok(())
+    }
+
+    fn remember(&mut self, sound: Sound) {
+        if self.requested.len() == REQUESTED_LIMIT {
+            self.requested.pop_front();
+        }
+        self.requested.push_back(sound);
+    }
+}
@@ -0,0 +1,275 @@
+use super::Voice;
+use crate::game::config::{DEFAULT_MUSIC_VOLUME, DEFAULT_SFX_VOLUME};
+use rand::Rng;
+use rodio::buffer::SamplesBuffer;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Source};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::Duration;
+
+// the blip files are recorded much quieter than the other sounds, this brings them up to match
+const VOICE_VOLUME_BOOST: f32 = 4.0;
+
+// a sound decoded once and kept around as raw samples
+struct DecodedSound {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+impl DecodedSound {
+    fn load(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = crate::assets::store().load_bytes(&format!("sound/{}.mp3", name))?;
+        let decoder = Decoder::new(Cursor::new(bytes.into_owned()))?;
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        Ok(DecodedSound {
+            channels,
+            sample_rate,
+            samples: decoder.convert_samples().collect(),
+        })
+    }
+
+    fn source(&self) -> SamplesBuffer<f32> {
+        SamplesBuffer::new(self.channels, self.sample_rate, self.samples.clone())
+    }
+}
+
+// a looping track, the sink is missing when the file couldn't be played
+struct MusicTrack {
+    name: String,
+    sink: Option<rodio::Sink>,
+}
+
+impl MusicTrack {
+    fn set_volume(&self, volume: f32) {
+        if let Some(sink) = &self.sink {
+            sink.set_volume(volume);
+        }
+    }
+
+    fn stop(&self) {
+        if let Some(sink) = &self.sink {
+            sink.stop();
+        }
+    }
+}
+
+struct Crossfade {
+    elapsed: Duration,
+    duration: Duration,
+}
+
+impl Crossfade {
+    // 0 when the fade just started, 1 once the new track is at full volume
+    fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+}
+
+// the incoming and the outgoing track's volumes, `progress` of the way through a fade
+fn crossfade_volumes(music_volume: f32, progress: f32) -> (f32, f32) {
+    let progress = progress.clamp(0.0, 1.0);
+    (music_volume * progress, music_volume * (1.0 - progress))
+}
+
+pub struct RodioAudio {
+    _stream: OutputStream,
+    stream_handle: OutputStreamHandle,
+    // every blip goes through this one sink, a new blip cuts the last one off
+    voice_sink: Option<rodio::Sink>,
+    // None when the sound couldn't be loaded, so it isn't retried or reported every character
+    blips: HashMap<String, Option<DecodedSound>>,
+    music: Option<MusicTrack>,
+    // the track being faded out while `music` fades in
+    fading_music: Option<MusicTrack>,
+    crossfade: Option<Crossfade>,
+    music_volume: f32,
+    sfx_volume: f32,
+}
+
+impl RodioAudio {
+    pub fn new() -> Result<Self, rodio::StreamError> {
+        let (stream, stream_handle) = OutputStream::try_default()?;
+        Ok(RodioAudio {
+            _stream: stream,
+            stream_handle,
+            voice_sink: None,
+            blips: HashMap::new(),
+            music: None,
+            fading_music: None,
+            crossfade: None,
+            music_volume: DEFAULT_MUSIC_VOLUME,
+            sfx_volume: DEFAULT_SFX_VOLUME,
+        })
+    }
+
+    // a sound that can't be decoded is reported the first time and stays quiet after that
+    pub fn play_voice(&mut self, voice: &Voice) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.blips.contains_key(&voice.sound) {
+            match DecodedSound::load(&voice.sound) {
+                Ok(decoded) => self.blips.insert(voice.sound.clone(), Some(decoded)),
+                Err(e) => {
+                    self.blips.insert(voice.sound.clone(), None);
+                    return Err(format!("voice {}: {}", voice.sound, e).into());
+                }
+            };
+        }
+        let Some(blip) = self.blips[&voice.sound].as_ref().map(DecodedSound::source) else {
+            return Ok(());
+        };
+
+        if self.voice_sink.is_none() {
+            let sink = rodio::Sink::try_new(&self.stream_handle)?;
+            self.voice_sink = Some(sink);
+        }
+        let Some(sink) = &self.voice_sink else {
+            return Ok(());
+        };
+        let variance = voice.pitch_variance.abs();
+        let pitch = if variance > 0.0 {
+            1.0 + rand::thread_rng().gen_range(-variance..=variance)
+        } else {
+            1.0
+        };
+        sink.set_volume(VOICE_VOLUME_BOOST * self.sfx_volume);
+        sink.clear();
+        sink.append(blip.speed(pitch));
+        sink.play();
+        Ok(())
+    }
+
+    pub fn play_sound(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = crate::assets::store().load_bytes(&format!("sound/{}.mp3", name))?;
+        let decoder = Decoder::new(Cursor::new(bytes.into_owned()))?;
+        self.stream_handle
+            .play_raw(decoder.convert_samples().amplify(self.sfx_volume))?;
+        Ok(())
+    }
+
+    pub fn play_music(
+        &mut self,
+        track: Option<&str>,
+        fade: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.music.as_ref().map(|music| music.name.as_str()) == track {
+            return Ok(());
+        }
+        if let Some(old) = self.fading_music.take() {
+            old.stop();
+        }
+        self.fading_music = self.music.take();
+        self.crossfade = Some(Crossfade {
+            elapsed: Duration::ZERO,
+            duration: fade,
+        });
+
+        let result = match track {
+            Some(track) => {
+                let sink = self.start_track(track);
+                let (sink, result) = match sink {
+                    Ok(sink) => (Some(sink), Ok(())),
+                    Err(e) => (None, Err(e)),
+                };
+                // kept even without a sink so a missing file isn't retried every frame
+                self.music = Some(MusicTrack {
+                    name: track.to_string(),
+                    sink,
+                });
+                result
+            }
+            None => Ok(()),
+        };
+        self.apply_music_volume();
+        result
+    }
+
+    fn start_track(&self, track: &str) -> Result<rodio::Sink, Box<dyn std::error::Error>> {
+        let bytes = crate::assets::store().load_bytes(&format!("music/{}.mp3", track))?;
+        let decoder = Decoder::new_looped(Cursor::new(bytes.into_owned()))?;
+        let sink = rodio::Sink::try_new(&self.stream_handle)?;
+        sink.set_volume(0.0);
+        sink.append(decoder);
+        Ok(sink)
+    }
+
+    pub fn update(&mut self, delta_time: Duration) {
+        let Some(crossfade) = &mut self.crossfade else {
+            return;
+        };
+        crossfade.elapsed += delta_time;
+        let done = crossfade.progress() >= 1.0;
+        self.apply_music_volume();
+        if done {
+            if let Some(old) = self.fading_music.take() {
+                old.stop();
+            }
+            self.crossfade = None;
+        }
+    }
+
+    fn apply_music_volume(&self) {
+        let progress = self.crossfade.as_ref().map_or(1.0, Crossfade::progress);
+        let (incoming, outgoing) = crossfade_volumes(self.music_volume, progress);
+        if let Some(music) = &self.music {
+            music.set_volume(incoming);
+        }
+        if let Some(old) = &self.fading_music {
+            old.set_volume(outgoing);
+        }
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.music_volume
+    }
+
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.music_volume = volume;
+        self.apply_music_volume();
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.sfx_volume
+    }
+
+    pub fn set_sfx_volume(&mut self, volume: f32) {
+        self.sfx_volume = volume;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_crossfade_ramps_one_track_up_as_the_other_goes_down() {
+        let mut crossfade = Crossfade {
+            elapsed: Duration::ZERO,
+            duration: Duration::from_millis(800),
+        };
+        let mut ramp = Vec::new();
+        for _ in 0..6 {
+            ramp.push(crossfade_volumes(0.5, crossfade.progress()));
+            crossfade.elapsed += Duration::from_millis(200);
+        }
+        assert_eq!(
+            ramp,
+            [
+                (0.0, 0.5),
+                (0.125, 0.375),
+                (0.25, 0.25),
+                (0.375, 0.125),
+                (0.5, 0.0),
+                (0.5, 0.0),
+            ]
+        );
+
+        let cut = Crossfade {
+            elapsed: Duration::ZERO,
+            duration: Duration::ZERO,
+        };
+        assert_eq!(crossfade_volumes(0.5, cut.progress()), (0.5, 0.0));
+    }
+}
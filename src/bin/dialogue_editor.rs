@@ -13,32 +13,10 @@ use ratatui::{
 };
 use std::fs;
 use std::path::Path;
-use under_term::game::dialogue::{Dialogue, DialogueManager};
-use serde_json;
+use under_term::game::dialogue::{Dialogue, DialogueFile, DialogueManager, ENDING_DIALOGUE_ID};
 use std::io;
-use ansi_to_tui::IntoText;
-use under_term::load_sprite_asset_str;
-
-const FACE_SPRITES: &[&str] = &[
-    "assets/sprites/faces/face_3.ans",
-    "assets/sprites/faces/face_determined_smile.ans",
-    "assets/sprites/faces/face_determined.ans",
-    "assets/sprites/faces/face_hehehe.ans",
-    "assets/sprites/faces/face_huh.ans",
-    "assets/sprites/faces/face_meh.ans",
-    "assets/sprites/faces/face_neutral.ans",
-    "assets/sprites/faces/face_sight.ans",
-    "assets/sprites/faces/face_smile.ans",
-];
-
-const ENEMY_SPRITES: &[&str] = &[
-    "assets/sprites/enemy/not_a_placeholder/battle_3.ans",
-    "assets/sprites/enemy/not_a_placeholder/battle_hehehe.ans",
-    "assets/sprites/enemy/not_a_placeholder/battle_meh.ans",
-    "assets/sprites/enemy/not_a_placeholder/battle_more_neutral.ans",
-    "assets/sprites/enemy/not_a_placeholder/battle_neutral.ans",
-    "assets/sprites/enemy/not_a_placeholder/battle_smile.ans",
-];
+use ratatui::text::Text;
+use under_term::assets;
 
 enum EditorState {
     SelectFace,
@@ -58,12 +36,14 @@ struct Editor {
     should_quit: bool,
     face_list_state: ListState,
     enemy_list_state: ListState,
+    // why the last save didn't go through, shown on the confirm screen
+    save_error: Option<String>,
 }
 
 impl Editor {
     fn new() -> Self {
-        let faces = FACE_SPRITES.iter().map(|s| s.to_string()).collect();
-        let enemies = ENEMY_SPRITES.iter().map(|s| s.to_string()).collect();
+        let faces = assets::store().sprite_paths("sprites/faces");
+        let enemies = assets::store().sprite_paths("sprites/enemy");
 
         let mut face_list_state = ListState::default();
         face_list_state.select(Some(0));
@@ -78,13 +58,19 @@ impl Editor {
             selected_enemy_index: 0,
             text: String::new(),
             new_dialogue: Dialogue {
+                speaker: None,
+                voice: None,
                 face_ansi_path: String::new(),
                 text: String::new(),
                 enemy_ansi_path: String::new(),
+                next: None,
+                choices: Vec::new(),
+            events: Vec::new(),
             },
             should_quit: false,
             face_list_state,
             enemy_list_state,
+            save_error: None,
         }
     }
 
@@ -108,7 +94,7 @@ impl Editor {
                     let items: Vec<ListItem> = self
                         .faces
                         .iter()
-                        .map(|i| ListItem::new(Path::new(i).file_name().unwrap().to_str().unwrap()))
+                        .map(|i| ListItem::new(Path::new(i).file_name().and_then(|name| name.to_str()).unwrap_or(i)))
                         .collect();
                     let list = List::new(items)
                         .block(Block::default().title("Select Face").borders(Borders::ALL))
@@ -116,13 +102,9 @@ impl Editor {
                     f.render_stateful_widget(list, chunks[0], &mut self.face_list_state);
 
                     if let Some(path) = self.faces.get(self.selected_face_index) {
-                        let content = load_sprite_asset_str!(path.as_str());
-                        let fixed_content = if cfg!(windows) {
-                            content.replace("\r\n", "\n")
-                        } else {
-                            content.to_string()
-                        };
-                        let text = fixed_content.as_bytes().into_text().unwrap();
+                        let text = assets::store()
+                            .load_text(path)
+                            .unwrap_or_else(|e| Text::raw(e.to_string()));
                         let paragraph = Paragraph::new(text)
                             .block(Block::default().title("Preview").borders(Borders::ALL));
                         f.render_widget(paragraph, chunks[1]);
@@ -137,7 +119,7 @@ impl Editor {
                     let items: Vec<ListItem> = self
                         .enemies
                         .iter()
-                        .map(|i| ListItem::new(Path::new(i).file_name().unwrap().to_str().unwrap()))
+                        .map(|i| ListItem::new(Path::new(i).file_name().and_then(|name| name.to_str()).unwrap_or(i)))
                         .collect();
                     let list = List::new(items)
                         .block(Block::default().title("Select Enemy").borders(Borders::ALL))
@@ -145,20 +127,19 @@ impl Editor {
                     f.render_stateful_widget(list, chunks[0], &mut self.enemy_list_state);
 
                     if let Some(path) = self.enemies.get(self.selected_enemy_index) {
-                        let content = load_sprite_asset_str!(path.as_str());
-                        let fixed_content = if cfg!(windows) {
-                            content.replace("\r\n", "\n")
-                        } else {
-                            content.to_string()
-                        };
-                        let text = fixed_content.as_bytes().into_text().unwrap();
+                        let text = assets::store()
+                            .load_text(path)
+                            .unwrap_or_else(|e| Text::raw(e.to_string()));
                         let paragraph = Paragraph::new(text)
                             .block(Block::default().title("Preview").borders(Borders::ALL));
                         f.render_widget(paragraph, chunks[1]);
                     }
                 }
                 EditorState::ConfirmSave => {
-                    let text = "Press Enter to save and create another dialogue, or X to save and exit.";
+                    let mut text = String::from("Press Enter to save and create another dialogue, or X to save and exit.");
+                    if let Some(error) = &self.save_error {
+                        text.push_str(&format!("\n\nCould not save: {}", error));
+                    }
                     let paragraph = Paragraph::new(text)
                         .block(Block::default().title("Confirm").borders(Borders::ALL));
                     f.render_widget(paragraph, f.area());
@@ -180,13 +161,16 @@ impl Editor {
                             }
                         }
                         KeyCode::Down => {
-                            if self.selected_face_index < self.faces.len() - 1 {
+                            if self.selected_face_index + 1 < self.faces.len() {
                                 self.selected_face_index += 1;
                                 self.face_list_state.select(Some(self.selected_face_index));
                             }
                         }
                         KeyCode::Enter => {
-                            self.new_dialogue.face_ansi_path = self.faces[self.selected_face_index].clone();
+                            // no faces to pick from leaves the line without one
+                            if let Some(face) = self.faces.get(self.selected_face_index) {
+                                self.new_dialogue.face_ansi_path = face.clone();
+                            }
                             self.state = EditorState::EnterText;
                         }
                         _ => {}
@@ -214,13 +198,15 @@ impl Editor {
                             }
                         }
                         KeyCode::Down => {
-                            if self.selected_enemy_index < self.enemies.len() - 1 {
+                            if self.selected_enemy_index + 1 < self.enemies.len() {
                                 self.selected_enemy_index += 1;
                                 self.enemy_list_state.select(Some(self.selected_enemy_index));
                             }
                         }
                         KeyCode::Enter => {
-                            self.new_dialogue.enemy_ansi_path = self.enemies[self.selected_enemy_index].clone();
+                            if let Some(enemy) = self.enemies.get(self.selected_enemy_index) {
+                                self.new_dialogue.enemy_ansi_path = enemy.clone();
+                            }
                             self.state = EditorState::ConfirmSave;
                         }
                         _ => {}
@@ -228,14 +214,14 @@ impl Editor {
                 }
                 EditorState::ConfirmSave => {
                     match key.code {
-                        KeyCode::Enter => {
-                            self.save_dialogue();
-                            self.reset();
-                        }
-                        KeyCode::Char('x') => {
-                            self.save_dialogue();
-                            self.should_quit = true;
-                        }
+                        KeyCode::Enter => match self.save_dialogue() {
+                            Ok(()) => self.reset(),
+                            Err(e) => self.save_error = Some(e.to_string()),
+                        },
+                        KeyCode::Char('x') => match self.save_dialogue() {
+                            Ok(()) => self.should_quit = true,
+                            Err(e) => self.save_error = Some(e.to_string()),
+                        },
                         _ => {}
                     }
                 }
@@ -244,11 +230,19 @@ impl Editor {
         Ok(())
     }
 
-    fn save_dialogue(&self) {
-        let mut manager = DialogueManager::new();
-        manager.dialogues.push(self.new_dialogue.clone());
-        let serialized = serde_json::to_string_pretty(&manager.dialogues).unwrap();
-        fs::write("dialogues.json", serialized).unwrap();
+    // new lines go at the end of the ending, written back as a list or a graph like it was
+    fn save_dialogue(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = DialogueManager::path(ENDING_DIALOGUE_ID);
+        let content = assets::store().load_str(&path)?;
+        let mut file = DialogueFile::parse(&content).map_err(|e| format!("{} is broken: {}", path, e))?;
+        file.push_line(self.new_dialogue.clone());
+        let serialized = serde_json::to_string_pretty(&file)?;
+        let path = assets::store().writable_root().join(path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serialized)?;
+        Ok(())
     }
 
     fn reset(&mut self) {
@@ -256,10 +250,16 @@ impl Editor {
         self.selected_face_index = 0;
         self.selected_enemy_index = 0;
         self.text = String::new();
+        self.save_error = None;
         self.new_dialogue = Dialogue {
+            speaker: None,
+            voice: None,
             face_ansi_path: String::new(),
             text: String::new(),
             enemy_ansi_path: String::new(),
+            next: None,
+            choices: Vec::new(),
+            events: Vec::new(),
         };
         self.face_list_state.select(Some(0));
         self.enemy_list_state.select(Some(0));
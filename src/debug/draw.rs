@@ -2,10 +2,10 @@ use crate::game::config::ANIMATION_FRAME_DURATION;
 use crate::game::state::{GameState, TeleportCreationState};
 
 use ratatui::{
+    Frame,
     layout::Rect,
     style::{Color, Style},
     widgets::{Block, Borders, Clear, Paragraph},
-    Frame,
 };
 
 // kind of complicated hehe  ദ്ദി/ᐠ｡‸｡ᐟ\
@@ -20,24 +20,23 @@ pub fn draw_debug_info(frame: &mut Frame, game_state: &GameState) {
     let size = frame.area();
     let current_map_key = (game_state.current_map_row, game_state.current_map_col);
 
-    if let Some(current_map) = game_state.loaded_maps.get(&current_map_key) {
-        if let crate::game::map::MapKind::Walls = current_map.kind {
-            for &(wx, wy) in &current_map.walls {
-                let wall_x_on_screen = wx.saturating_sub(game_state.camera_x as u32);
-                let wall_y_on_screen = wy.saturating_sub(game_state.camera_y as u32);
-
-                if wall_x_on_screen < size.width as u32 && wall_y_on_screen < size.height as u32 {
-                    let draw_rect =
-                        Rect::new(wall_x_on_screen as u16, wall_y_on_screen as u16, 1, 1);
-                    let clamped_rect = draw_rect.intersection(size);
-                    if !clamped_rect.is_empty() {
-                        let wall_paragraph = Paragraph::new("󰟾").style(
-                            Style::default()
-                                .fg(Color::Rgb(255, 0, 0))
-                                .bg(Color::Rgb(0, 0, 0)),
-                        );
-                        frame.render_widget(wall_paragraph, clamped_rect);
-                    }
+    if let Some(current_map) = game_state.loaded_maps.get(&current_map_key)
+        && let crate::game::map::MapKind::Walls = current_map.kind
+    {
+        for &(wx, wy) in &current_map.walls {
+            let wall_x_on_screen = wx.saturating_sub(game_state.camera_x as u32);
+            let wall_y_on_screen = wy.saturating_sub(game_state.camera_y as u32);
+
+            if wall_x_on_screen < size.width as u32 && wall_y_on_screen < size.height as u32 {
+                let draw_rect = Rect::new(wall_x_on_screen as u16, wall_y_on_screen as u16, 1, 1);
+                let clamped_rect = draw_rect.intersection(size);
+                if !clamped_rect.is_empty() {
+                    let wall_paragraph = Paragraph::new("󰟾").style(
+                        Style::default()
+                            .fg(Color::Rgb(255, 0, 0))
+                            .bg(Color::Rgb(0, 0, 0)),
+                    );
+                    frame.render_widget(wall_paragraph, clamped_rect);
                 }
             }
         }
@@ -64,7 +63,7 @@ pub fn draw_debug_info(frame: &mut Frame, game_state: &GameState) {
     }
 
     // player collision box
-    let (_, player_sprite_width, player_sprite_height) = game_state.player.get_sprite_content();
+    let (player_sprite_width, player_sprite_height) = game_state.player.sprite_size();
     let collision_box_start_x = (game_state.player.x as u16)
         .saturating_add(player_sprite_width / 2)
         .saturating_sub(COLLISION_BOX_WIDTH / 2);
@@ -201,35 +200,33 @@ pub fn draw_debug_info(frame: &mut Frame, game_state: &GameState) {
     if (game_state.is_drawing_select_box
         || game_state.teleport_creation_state == TeleportCreationState::DrawingBox)
         && game_state.select_box_start_coords.is_some()
+        && let Some((start_x, start_y)) = game_state.select_box_start_coords
     {
-        if let Some((start_x, start_y)) = game_state.select_box_start_coords {
-            let (end_x, end_y) = (game_state.player.x as u16, game_state.player.y as u16);
+        let (end_x, end_y) = (game_state.player.x as u16, game_state.player.y as u16);
 
-            let rect_x = start_x.min(end_x);
-            let rect_y = start_y.min(end_y);
-            let rect_width = start_x.max(end_x).saturating_sub(rect_x).saturating_add(1);
-            let rect_height = start_y.max(end_y).saturating_sub(rect_y).saturating_add(1);
+        let rect_x = start_x.min(end_x);
+        let rect_y = start_y.min(end_y);
+        let rect_width = start_x.max(end_x).saturating_sub(rect_x).saturating_add(1);
+        let rect_height = start_y.max(end_y).saturating_sub(rect_y).saturating_add(1);
 
-            let draw_x = rect_x.saturating_sub(game_state.camera_x);
-            let draw_y = rect_y.saturating_sub(game_state.camera_y);
+        let draw_x = rect_x.saturating_sub(game_state.camera_x);
+        let draw_y = rect_y.saturating_sub(game_state.camera_y);
 
-            let draw_rect = Rect::new(draw_x, draw_y, rect_width, rect_height);
-            let clamped_rect = draw_rect.intersection(size);
+        let draw_rect = Rect::new(draw_x, draw_y, rect_width, rect_height);
+        let clamped_rect = draw_rect.intersection(size);
 
-            if !clamped_rect.is_empty() {
-                let color =
-                    if game_state.teleport_creation_state == TeleportCreationState::DrawingBox {
-                        Color::Rgb(255, 0, 255)
-                    } else {
-                        Color::Rgb(0, 255, 0)
-                    };
-                let pending_box_paragraph = Paragraph::new("").block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .border_style(Style::default().fg(color)),
-                );
-                frame.render_widget(pending_box_paragraph, clamped_rect);
-            }
+        if !clamped_rect.is_empty() {
+            let color = if game_state.teleport_creation_state == TeleportCreationState::DrawingBox {
+                Color::Rgb(255, 0, 255)
+            } else {
+                Color::Rgb(0, 255, 0)
+            };
+            let pending_box_paragraph = Paragraph::new("").block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(color)),
+            );
+            frame.render_widget(pending_box_paragraph, clamped_rect);
         }
     }
 
@@ -1,28 +1,31 @@
+use crate::game::keymap::Action;
 use crate::game::state::{GameState, TeleportCreationState};
-use ansi_to_tui::IntoText;
-use crossterm::event::{KeyCode, KeyEvent};
-use std::time::Instant;
 
-pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
+/// The map editor's side of a key press, true when it took care of `action`.
+pub fn handle_debug_input(action: Action, game_state: &mut GameState) -> bool {
     if !game_state.debug_mode {
         return false;
     }
 
-    match key.code {
-        KeyCode::Char('r') => {
+    match action {
+        Action::DebugShowCollision => {
+            game_state.show_collision_box = true;
+            true
+        }
+        Action::DebugUndoWall => {
             game_state.undo_wall_change();
             true
         }
-        KeyCode::Char('z') => {
+        Action::DebugRedoWall => {
             game_state.redo_wall_change();
             true
         }
-        KeyCode::Char('s') => {
+        Action::DebugSetSpawn => {
             game_state
                 .set_player_spawn_to_current_position(game_state.player.x, game_state.player.y);
             true
         }
-        KeyCode::Char('k') => {
+        Action::DebugMapKind => {
             game_state.is_map_kind_selection_active = !game_state.is_map_kind_selection_active;
             if game_state.is_map_kind_selection_active {
                 game_state.message =
@@ -32,11 +35,10 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                 game_state.message = "Map Kind Selection cancelled.".to_string();
             }
             game_state.show_message = true;
-            game_state.message_animation_start_time = Instant::now();
-            game_state.animated_message_content.clear();
+            game_state.message_typewriter.clear();
             true
         }
-        KeyCode::Char('o') => {
+        Action::DebugSelectBox => {
             if !game_state.is_drawing_select_box {
                 game_state.is_drawing_select_box = true;
                 game_state.select_box_start_coords =
@@ -49,22 +51,20 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                 game_state.message = "Finish drawing select box by pressing Enter.".to_string();
             }
             game_state.show_message = true;
-            game_state.message_animation_start_time = Instant::now();
-            game_state.animated_message_content.clear();
+            game_state.message_typewriter.clear();
             true
         }
 
-        KeyCode::F(3) => {
+        Action::DebugNewMap => {
             game_state.is_creating_map = true;
             game_state.is_text_input_active = true;
             game_state.text_input_buffer.clear();
             game_state.message = "Enter new map name (e.g., map_0_1):".to_string();
             game_state.show_message = true;
-            game_state.message_animation_start_time = Instant::now();
-            game_state.animated_message_content.clear();
+            game_state.message_typewriter.clear();
             true
         }
-        KeyCode::Char('t') => {
+        Action::DebugTeleportBox => {
             if game_state.teleport_creation_state == TeleportCreationState::None {
                 game_state.teleport_creation_state = TeleportCreationState::DrawingBox;
                 game_state.select_box_start_coords =
@@ -77,11 +77,10 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                 game_state.message = "Finish drawing teleport line by pressing Enter.".to_string();
             }
             game_state.show_message = true;
-            game_state.message_animation_start_time = Instant::now();
-            game_state.animated_message_content.clear();
+            game_state.message_typewriter.clear();
             true
         }
-        KeyCode::Char('x') => {
+        Action::DebugPlaceSprite => {
             if !game_state.is_placing_sprite {
                 game_state.is_placing_sprite = true;
                 let sprite_content = include_str!("../../assets/sprites/ME/idle/default.ans");
@@ -90,23 +89,17 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                 } else {
                     sprite_content.to_string()
                 };
-                let text = fixed_content.as_bytes().into_text().unwrap();
-                let height = text.lines.len() as u32;
-                let mut width = 0;
-                for line in text.lines.iter() {
-                    let line_width = line.width() as u32;
-                    if line_width > width {
-                        width = line_width;
-                    }
-                }
+                let sprite = crate::assets::Sprite::parse(&fixed_content).unwrap();
 
                 let new_placed_sprite = crate::game::map::PlacedSprite {
                     id: 0,
                     x: game_state.player.x as u32,
                     y: game_state.player.y as u32,
-                    width,
-                    height,
+                    width: sprite.width as u32,
+                    height: sprite.height as u32,
                     ansi_content: sprite_content.to_string(),
+                    conversation: None,
+                    parsed: std::sync::OnceLock::from(std::sync::Arc::new(sprite)),
                 };
                 game_state.pending_placed_sprite = Some(new_placed_sprite);
                 game_state.message =
@@ -116,11 +109,10 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                 game_state.message = "Finish placing sprite by pressing Enter.".to_string();
             }
             game_state.show_message = true;
-            game_state.message_animation_start_time = Instant::now();
-            game_state.animated_message_content.clear();
+            game_state.message_typewriter.clear();
             true
         }
-        KeyCode::Enter => {
+        Action::Confirm => {
             if game_state.is_drawing_select_box {
                 if game_state.is_confirming_select_box {
                     game_state.is_text_input_active = true;
@@ -128,8 +120,7 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                     game_state.message =
                         "Enter message. Press Enter to add, or Esc to finish.".to_string();
                     game_state.show_message = true;
-                    game_state.message_animation_start_time = Instant::now();
-                    game_state.animated_message_content.clear();
+                    game_state.message_typewriter.clear();
                     game_state.is_confirming_select_box = false;
                 } else {
                     if let Some((start_x, start_y)) = game_state.select_box_start_coords {
@@ -163,12 +154,14 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                                     as u32,
                                 messages: Vec::new(),
                                 events: Vec::new(),
+                                requires_flag: None,
+                                unless_flag: None,
+                                conversation: None,
                             };
                             game_state.pending_select_box = Some(new_select_box);
                             game_state.message = "Select box confirmed. Press Enter again to add messages, or Esc to cancel.".to_string();
                             game_state.show_message = true;
-                            game_state.message_animation_start_time = Instant::now();
-                            game_state.animated_message_content.clear();
+                            game_state.message_typewriter.clear();
                         }
                     }
                     game_state.select_box_start_coords = None;
@@ -202,6 +195,9 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                                 .saturating_add(1) as u32,
                             messages: Vec::new(),
                             events: Vec::new(),
+                            requires_flag: None,
+                            unless_flag: None,
+                            conversation: None,
                         };
                         map_to_modify.add_select_object_box(new_teleport_box.clone());
                         if let Err(e) = map_to_modify.save_data() {
@@ -214,8 +210,7 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                         game_state.is_text_input_active = true;
                         game_state.teleport_destination_map_name_buffer.clear();
                         game_state.show_message = true;
-                        game_state.message_animation_start_time = Instant::now();
-                        game_state.animated_message_content.clear();
+                        game_state.message_typewriter.clear();
                     }
                 }
                 true
@@ -249,8 +244,7 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
                 game_state.is_placing_sprite = false;
                 game_state.block_player_movement_on_message = true;
                 game_state.show_message = true;
-                game_state.message_animation_start_time = Instant::now();
-                game_state.animated_message_content.clear();
+                game_state.message_typewriter.clear();
                 true
             } else {
                 false
@@ -258,4 +252,4 @@ pub fn handle_debug_input(key: KeyEvent, game_state: &mut GameState) -> bool {
         }
         _ => false,
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,12 @@
+use super::config::{
+    BULLET_BOX_HEIGHT, BULLET_BOX_WIDTH, SOUL_HORIZONTAL_SPEED, SOUL_INVINCIBILITY_DURATION,
+    SOUL_SPEED,
+};
+use super::keymap::Action;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +19,20 @@ pub struct Bullet {
     pub height: u16,
     pub symbol: String,
     pub bounces_remaining: u8,
+    // wave bullets wiggle around the line they were spawned on
+    #[serde(default)]
+    pub origin_y: f32,
+    #[serde(default)]
+    pub age: f32,
+}
+
+impl Bullet {
+    fn overlaps(&self, x: f32, y: f32) -> bool {
+        x >= self.x.floor()
+            && x < self.x.floor() + self.width as f32
+            && y >= self.y.floor()
+            && y < self.y.floor() + self.height as f32
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -31,3 +53,325 @@ pub struct Attack {
     pub wave_amplitude: Option<f32>,
     pub wave_frequency: Option<f32>,
 }
+
+impl Default for Attack {
+    fn default() -> Self {
+        Attack {
+            attack_type: AttackType::Bouncing,
+            duration: Duration::from_secs(6),
+            damage: 3,
+            bullet_speed: 25.0,
+            bullet_symbol: "●".to_string(),
+            spawn_rate: 3.0,
+            wave_amplitude: None,
+            wave_frequency: None,
+        }
+    }
+}
+
+/// The player's heart inside the bullet box, in box-local cells.
+#[derive(Debug, Clone)]
+pub struct Soul {
+    pub x: f32,
+    pub y: f32,
+    pub invincibility_remaining: Duration,
+}
+
+impl Soul {
+    pub fn is_invincible(&self) -> bool {
+        !self.invincibility_remaining.is_zero()
+    }
+}
+
+/// One dodge phase: a bordered box, the soul and the bullets of a single `Attack`.
+#[derive(Debug, Clone)]
+pub struct AttackPhase {
+    pub attack: Attack,
+    pub bullets: Vec<Bullet>,
+    pub soul: Soul,
+    pub box_width: u16,
+    pub box_height: u16,
+    pub elapsed: Duration,
+    spawn_accumulator: f64,
+    // where the bullets spawn, seeded by the caller when it has to come out the same
+    rng: StdRng,
+}
+
+impl AttackPhase {
+    /// Bullets spawn from the game's rng, so a replay gets the same waves back.
+    pub fn new(attack: Attack) -> Self {
+        let seed = crate::game::rng::with(|rng| rng.next_u64());
+        Self::with_rng(attack, StdRng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng(attack: Attack, rng: StdRng) -> Self {
+        AttackPhase {
+            attack,
+            bullets: Vec::new(),
+            soul: Soul {
+                x: (BULLET_BOX_WIDTH / 2) as f32,
+                y: (BULLET_BOX_HEIGHT / 2) as f32,
+                invincibility_remaining: Duration::ZERO,
+            },
+            box_width: BULLET_BOX_WIDTH,
+            box_height: BULLET_BOX_HEIGHT,
+            elapsed: Duration::ZERO,
+            spawn_accumulator: 0.0,
+            rng,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.attack.duration
+    }
+
+    /// Advances the phase by `delta_time` and returns the damage the soul took.
+    pub fn update(&mut self, key_states: &HashMap<Action, bool>, delta_time: Duration) -> i32 {
+        let dt = delta_time.as_secs_f32();
+        self.elapsed += delta_time;
+        self.soul.invincibility_remaining =
+            self.soul.invincibility_remaining.saturating_sub(delta_time);
+
+        self.move_soul(key_states, dt);
+
+        if !self.is_finished() {
+            self.spawn_accumulator += self.attack.spawn_rate * delta_time.as_secs_f64();
+            while self.spawn_accumulator >= 1.0 {
+                self.spawn_accumulator -= 1.0;
+                self.spawn_bullet();
+            }
+        }
+
+        self.move_bullets(dt);
+        self.check_hits()
+    }
+
+    fn move_soul(&mut self, key_states: &HashMap<Action, bool>, dt: f32) {
+        let up = *key_states.get(&Action::MoveUp).unwrap_or(&false);
+        let down = *key_states.get(&Action::MoveDown).unwrap_or(&false);
+        let left = *key_states.get(&Action::MoveLeft).unwrap_or(&false);
+        let right = *key_states.get(&Action::MoveRight).unwrap_or(&false);
+
+        if up && !down {
+            self.soul.y -= SOUL_SPEED * dt;
+        } else if down && !up {
+            self.soul.y += SOUL_SPEED * dt;
+        }
+        if left && !right {
+            self.soul.x -= SOUL_HORIZONTAL_SPEED * dt;
+        } else if right && !left {
+            self.soul.x += SOUL_HORIZONTAL_SPEED * dt;
+        }
+
+        // the box border takes one cell on each side
+        self.soul.x = self.soul.x.clamp(0.0, (self.box_width - 3) as f32);
+        self.soul.y = self.soul.y.clamp(0.0, (self.box_height - 3) as f32);
+    }
+
+    fn spawn_bullet(&mut self) {
+        let rng = &mut self.rng;
+        let inner_width = (self.box_width - 2) as f32;
+        let inner_height = (self.box_height - 2) as f32;
+        let speed = self.attack.bullet_speed;
+        let from_left = rng.gen_bool(0.5);
+
+        let (x, y, vx, vy, bounces_remaining) = match self.attack.attack_type {
+            AttackType::Simple => {
+                let y = rng.gen_range(0.0..inner_height);
+                if from_left {
+                    (0.0, y, speed, 0.0, 0)
+                } else {
+                    (inner_width - 1.0, y, -speed, 0.0, 0)
+                }
+            }
+            AttackType::Bouncing => {
+                let x = rng.gen_range(0.0..inner_width);
+                let vx = if from_left { speed } else { -speed };
+                // cells are about twice as tall as they are wide
+                (x, 0.0, vx, speed / 2.0, 3)
+            }
+            AttackType::Wave => {
+                let y = rng.gen_range(0.0..inner_height);
+                if from_left {
+                    (0.0, y, speed, 0.0, 0)
+                } else {
+                    (inner_width - 1.0, y, -speed, 0.0, 0)
+                }
+            }
+        };
+
+        self.bullets.push(Bullet {
+            x,
+            y,
+            vx,
+            vy,
+            width: 1,
+            height: 1,
+            symbol: self.attack.bullet_symbol.clone(),
+            bounces_remaining,
+            origin_y: y,
+            age: 0.0,
+        });
+    }
+
+    fn move_bullets(&mut self, dt: f32) {
+        let max_x = (self.box_width - 2) as f32;
+        let max_y = (self.box_height - 2) as f32;
+        let amplitude = self.attack.wave_amplitude.unwrap_or(2.0);
+        let frequency = self.attack.wave_frequency.unwrap_or(1.0);
+        let attack_type = &self.attack.attack_type;
+
+        for bullet in &mut self.bullets {
+            bullet.age += dt;
+            bullet.x += bullet.vx * dt;
+
+            if *attack_type == AttackType::Wave {
+                bullet.y = bullet.origin_y
+                    + amplitude * (bullet.age * frequency * std::f32::consts::TAU).sin();
+            } else {
+                bullet.y += bullet.vy * dt;
+            }
+
+            if bullet.bounces_remaining > 0 {
+                let mut bounced = false;
+                if bullet.x < 0.0 || bullet.x + bullet.width as f32 > max_x {
+                    bullet.vx = -bullet.vx;
+                    bullet.x = bullet.x.clamp(0.0, max_x - bullet.width as f32);
+                    bounced = true;
+                }
+                if bullet.y < 0.0 || bullet.y + bullet.height as f32 > max_y {
+                    bullet.vy = -bullet.vy;
+                    bullet.y = bullet.y.clamp(0.0, max_y - bullet.height as f32);
+                    bounced = true;
+                }
+                if bounced {
+                    bullet.bounces_remaining -= 1;
+                }
+            }
+        }
+
+        self.bullets.retain(|b| {
+            b.x + b.width as f32 >= 0.0
+                && b.x < max_x
+                && b.y + b.height as f32 >= -amplitude
+                && b.y < max_y + amplitude
+        });
+    }
+
+    fn check_hits(&mut self) -> i32 {
+        if self.soul.is_invincible() {
+            return 0;
+        }
+        let soul_x = self.soul.x.floor();
+        let soul_y = self.soul.y.floor();
+        if let Some(index) = self.bullets.iter().position(|b| b.overlaps(soul_x, soul_y)) {
+            self.bullets.remove(index);
+            self.soul.invincibility_remaining = SOUL_INVINCIBILITY_DURATION;
+            return self.attack.damage;
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // no bullets of its own, the tests place them
+    fn quiet_attack() -> Attack {
+        Attack {
+            spawn_rate: 0.0,
+            ..Attack::default()
+        }
+    }
+
+    fn bullet_at(x: f32, y: f32, vx: f32, vy: f32, bounces_remaining: u8) -> Bullet {
+        Bullet {
+            x,
+            y,
+            vx,
+            vy,
+            width: 1,
+            height: 1,
+            symbol: "●".to_string(),
+            bounces_remaining,
+            origin_y: y,
+            age: 0.0,
+        }
+    }
+
+    #[test]
+    fn bullets_spawn_at_the_attack_rate() {
+        let attack = Attack {
+            spawn_rate: 5.0,
+            ..Attack::default()
+        };
+        let mut phase = AttackPhase::with_rng(attack, StdRng::seed_from_u64(7));
+        let keys = HashMap::new();
+        phase.update(&keys, Duration::from_millis(100));
+        assert!(phase.bullets.is_empty());
+        phase.update(&keys, Duration::from_millis(150));
+        assert_eq!(phase.bullets.len(), 1);
+        assert_eq!(phase.bullets[0].symbol, "●");
+    }
+
+    #[test]
+    fn bullets_bounce_off_the_box_edges_until_out_of_bounces() {
+        let mut phase = AttackPhase::new(quiet_attack());
+        let max_x = (phase.box_width - 2) as f32;
+        phase
+            .bullets
+            .push(bullet_at(max_x - 1.5, 2.0, 10.0, 0.0, 1));
+        phase.move_bullets(0.1);
+        let bullet = &phase.bullets[0];
+        assert_eq!(bullet.vx, -10.0);
+        assert_eq!(bullet.bounces_remaining, 0);
+        assert!(bullet.x + bullet.width as f32 <= max_x);
+
+        // out of bounces it flies through the left edge and is gone
+        phase.bullets[0].x = 0.5;
+        phase.move_bullets(0.2);
+        assert!(phase.bullets.is_empty());
+    }
+
+    #[test]
+    fn the_wave_ends_after_its_duration_and_stops_spawning() {
+        let mut phase = AttackPhase::new(Attack {
+            duration: Duration::from_secs(1),
+            ..Attack::default()
+        });
+        let keys = HashMap::new();
+        phase.update(&keys, Duration::from_millis(900));
+        assert!(!phase.is_finished());
+        phase.update(&keys, Duration::from_millis(100));
+        assert!(phase.is_finished());
+        phase.bullets.clear();
+        phase.update(&keys, Duration::from_secs(1));
+        assert!(phase.bullets.is_empty());
+    }
+
+    #[test]
+    fn a_hit_lowers_hp_and_invincibility_blocks_the_next_one() {
+        let mut phase = AttackPhase::new(quiet_attack());
+        let keys = HashMap::new();
+        let (x, y) = (phase.soul.x, phase.soul.y);
+
+        phase.bullets.push(bullet_at(x, y, 0.0, 0.0, 0));
+        let damage = phase.update(&keys, Duration::from_millis(10));
+        assert_eq!(damage, phase.attack.damage);
+        assert!(phase.bullets.is_empty());
+
+        phase.bullets.push(bullet_at(x, y, 0.0, 0.0, 0));
+        assert_eq!(phase.update(&keys, Duration::from_millis(10)), 0);
+        assert_eq!(phase.bullets.len(), 1);
+
+        phase.bullets.clear();
+        phase.update(&keys, SOUL_INVINCIBILITY_DURATION);
+        assert!(!phase.soul.is_invincible());
+        phase.bullets.push(bullet_at(x, y, 0.0, 0.0, 0));
+        assert_eq!(
+            phase.update(&keys, Duration::from_millis(10)),
+            phase.attack.damage
+        );
+    }
+}
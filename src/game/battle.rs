@@ -1,364 +1,747 @@
-use crossterm::event::KeyCode;
-use rand::Rng;
+use super::attack::{Attack, AttackPhase};
+use super::config::{ATTACK_BAR_DURATION, PLAYER_BASE_ATK};
+use super::deltarune::Deltarune;
+use super::enemy::{Enemy, SpareCondition, load_ansi};
+use super::keymap::Action;
+use super::settings::TextSpeed;
+use super::typewriter::{Pace, Typewriter};
+use crate::audio::Voice;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
 use std::time::{Duration, Instant};
 
-fn default_instant() -> Instant {
-    Instant::now()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MenuButton {
+    Fight,
+    Act,
+    Item,
+    Mercy,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum BattleMode {
-    Dialogue,
-    ExitTransition,
-}
+impl MenuButton {
+    pub const ALL: [MenuButton; 4] = [
+        MenuButton::Fight,
+        MenuButton::Act,
+        MenuButton::Item,
+        MenuButton::Mercy,
+    ];
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Dialogue {
-    pub enemy_sprite_ansi: String,
-    pub face_link: Option<String>,
-    pub text: String,
-}
+    pub fn label(&self) -> &'static str {
+        match self {
+            MenuButton::Fight => "FIGHT",
+            MenuButton::Act => "ACT",
+            MenuButton::Item => "ITEM",
+            MenuButton::Mercy => "MERCY",
+        }
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BattleState {
-    pub mode: BattleMode,
-    pub dialogues: Vec<Dialogue>,
-    pub current_dialogue_index: usize,
-    pub narrative_text: String,
-    pub narrative_face: Option<String>,
-    pub animated_narrative_content: String,
-    #[serde(skip, default = "default_instant")]
-    pub narrative_animation_start_time: Instant,
-    #[serde(skip)]
-    pub narrative_animation_interval: Duration,
-    #[serde(skip)]
-    pub narrative_animation_finished: bool,
-    #[serde(skip)]
-    pub previous_chars_shown: usize,
-    #[serde(skip, default = "default_instant")]
-    pub exit_transition_timer: Instant,
-    pub deltarune: crate::game::deltarune::Deltarune,
-}
+    pub fn next(&self) -> Self {
+        match self {
+            MenuButton::Fight => MenuButton::Act,
+            MenuButton::Act => MenuButton::Item,
+            MenuButton::Item => MenuButton::Mercy,
+            MenuButton::Mercy => MenuButton::Fight,
+        }
+    }
 
-impl BattleState {
-    pub fn new() -> Self {
-        let dialogues = vec![
-            Dialogue {
-                                use crossterm::event::KeyCode;
-use rand::Rng;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+    pub fn previous(&self) -> Self {
+        match self {
+            MenuButton::Fight => MenuButton::Mercy,
+            MenuButton::Act => MenuButton::Fight,
+            MenuButton::Item => MenuButton::Act,
+            MenuButton::Mercy => MenuButton::Item,
+        }
+    }
+}
 
-fn default_instant() -> Instant {
-    Instant::now()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BattleOutcome {
+    Won,
+    Spared,
+    Fled,
+    Lost,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BattleMode {
-    Dialogue,
-    ExitTransition,
+    // the red flicker over the overworld before the battle screen shows up
+    Encounter,
+    Menu,
+    ActMenu,
+    ItemMenu,
+    MercyMenu,
+    PlayerAttack,
+    // "* You did a thing." text after an ACT / ITEM / MERCY choice
+    Narration,
+    EnemyDialogue,
+    EnemyAttack,
+    Outcome(BattleOutcome),
+    ExitTransition(BattleOutcome),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dialogue {
     pub enemy_sprite_ansi: String,
     pub face_ansi: Option<String>,
+    pub voice: Voice,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActOption {
+    pub name: String,
     pub text: String,
+    // how much closer to being spareable this makes the enemy, out of 100
+    pub mercy: u8,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BattleState {
     pub mode: BattleMode,
+    pub selected_button: MenuButton,
+    pub submenu_index: usize,
+    pub enemy_id: String,
+    pub enemy_name: String,
+    // shown whenever the enemy isn't in the middle of a line
+    pub enemy_sprite_ansi: String,
+    pub enemy_hp: i32,
+    pub enemy_max_hp: i32,
+    pub enemy_def: i32,
+    pub exp_reward: u32,
+    pub gold_reward: u32,
+    // copied from the player's stats when the battle starts
+    pub player_atk: i32,
+    pub act_options: Vec<ActOption>,
+    pub acts_used: Vec<String>,
+    // names of the carried items, kept up to date by GameState
+    pub items: Vec<String>,
+    // set when an item is picked, GameState uses it and calls show_item_used
+    pub item_to_use: Option<usize>,
+    // conversation GameState plays once the enemy is beaten or spared
+    #[serde(default)]
+    pub conversation: Option<String>,
+    #[serde(default)]
+    pub music: Option<String>,
+    pub spare_conditions: Vec<SpareCondition>,
+    pub mercy: u8,
+    pub attacks: Vec<Attack>,
+    pub turn: usize,
     pub dialogues: Vec<Dialogue>,
     pub current_dialogue_index: usize,
     pub narrative_text: String,
     pub narrative_face: Option<String>,
-    pub animated_narrative_content: String,
-    #[serde(skip, default = "default_instant")]
-    pub narrative_animation_start_time: Instant,
     #[serde(skip)]
-    pub narrative_animation_interval: Duration,
+    pub narrative: Typewriter,
+    // whoever is talking, the narrator unless it's the enemy's turn to speak
+    #[serde(default)]
+    pub narrative_voice: Voice,
     #[serde(skip)]
-    pub narrative_animation_finished: bool,
+    pub text_speed: TextSpeed,
     #[serde(skip)]
-    pub previous_chars_shown: usize,
-    #[serde(skip, default = "default_instant")]
-    pub exit_transition_timer: Instant,
-    pub deltarune: crate::game::deltarune::Deltarune,
+    pub attack_bar_position: f32,
+    #[serde(skip)]
+    pub attack_phase: Option<AttackPhase>,
+    #[serde(skip)]
+    pub last_damage_dealt: Option<i32>,
+    #[serde(skip)]
+    pub flicker_elapsed: Duration,
+    pub flicker_duration: Duration,
+    pub flicker_count: u8,
+    pub show_flicker_black_screen: bool,
+    // how long the screen has been fully dark on the way out
+    #[serde(skip)]
+    pub exit_transition_elapsed: Duration,
+    pub deltarune: Deltarune,
 }
 
 impl BattleState {
-    pub fn new() -> Self {
-        let dialogues = vec![
-            Dialogue {
-                enemy_sprite_ansi: if cfg!(windows) { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_neutral.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_neutral.ans").to_string() },
-                face_ansi: Some(if cfg!(windows) { include_str!("../../assets/sprites/faces/face_neutral.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/faces/face_neutral.ans").to_string() }),
-                text: "Hello there, human. You've stumbled into my domain...".to_string(),
-            },
-            Dialogue {
-                enemy_sprite_ansi: if cfg!(windows) { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_smile.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_smile.ans").to_string() },
-                face_ansi: Some(if cfg!(windows) { include_str!("../../assets/sprites/faces/face_smile.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/faces/face_smile.ans").to_string() }),
-                text: "Prepare for a dialogue-only encounter! Mwahaha!".to_string(),
-            },
-            Dialogue {
-                enemy_sprite_ansi: if cfg!(windows) { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_hehehe.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_hehehe.ans").to_string() },
-                face_ansi: Some(if cfg!(windows) { include_str!("../../assets/sprites/faces/face_hehehe.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/faces/face_hehehe.ans").to_string() }),
-                text: "But seriously, this is just a test of the dialogue system...".to_string(),
-            },
-            Dialogue {
-                enemy_sprite_ansi: if cfg!(windows) { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_more_neutral.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_more_neutral.ans").to_string() },
-                face_ansi: Some(if cfg!(windows) { include_str!("../../assets/sprites/faces/face_determined.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/faces/face_determined.ans").to_string() }),
-                text: "Press Enter to advance, or Esc/X to skip the current line.".to_string(),
-            },
-            Dialogue {
-                enemy_sprite_ansi: if cfg!(windows) { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_3.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/enemy/not_a_placeholder/battle_3.ans").to_string() },
-                face_ansi: Some(if cfg!(windows) { include_str!("../../assets/sprites/faces/face_3.ans").replace("\r\n", "\n") } else { include_str!("../../assets/sprites/faces/face_3.ans").to_string() }),
-                text: "Watch out for the pauses... like this, ... and this. And this.".to_string(),
-            },
-        ];
+    pub fn new(enemy: &Enemy) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut dialogues = Vec::new();
+        for line in &enemy.dialogue {
+            dialogues.push(Dialogue {
+                enemy_sprite_ansi: enemy.sprite_for(&line.mood)?,
+                face_ansi: line.face.as_deref().map(load_ansi).transpose()?,
+                voice: line.voice.clone().unwrap_or_else(|| enemy.voice.clone()),
+                text: line.text.clone(),
+            });
+        }
 
         let current_dialogue = dialogues.first().cloned().unwrap_or_else(|| Dialogue {
             enemy_sprite_ansi: String::new(),
             face_ansi: None,
+            voice: enemy.voice.clone(),
             text: "No dialogues defined.".to_string(),
         });
 
-        BattleState {
-            mode: BattleMode::Dialogue,
+        let narrative = Typewriter::new(&current_dialogue.text, Pace::BATTLE);
+        Ok(BattleState {
+            mode: BattleMode::Encounter,
+            selected_button: MenuButton::Fight,
+            submenu_index: 0,
+            enemy_id: enemy.id.clone(),
+            enemy_name: enemy.name.clone(),
+            enemy_sprite_ansi: enemy.sprite_for("neutral")?,
+            enemy_hp: enemy.hp,
+            enemy_max_hp: enemy.hp,
+            enemy_def: enemy.def,
+            exp_reward: enemy.exp_reward,
+            gold_reward: enemy.gold_reward,
+            player_atk: PLAYER_BASE_ATK,
+            act_options: enemy.act_options.clone(),
+            acts_used: Vec::new(),
+            items: Vec::new(),
+            item_to_use: None,
+            conversation: None,
+            music: enemy.music.clone(),
+            spare_conditions: enemy.spare_conditions.clone(),
+            mercy: 0,
+            attacks: enemy.attacks.clone(),
+            turn: 0,
             dialogues,
             current_dialogue_index: 0,
             narrative_text: current_dialogue.text,
             narrative_face: current_dialogue.face_ansi,
-            animated_narrative_content: String::new(),
-            narrative_animation_start_time: Instant::now(),
-            narrative_animation_interval: Duration::from_millis(50),
-            narrative_animation_finished: false,
-            previous_chars_shown: 0,
-            exit_transition_timer: Instant::now(),
-            deltarune: crate::game::deltarune::Deltarune::new(),
+            narrative,
+            narrative_voice: current_dialogue.voice,
+            text_speed: TextSpeed::default(),
+            attack_bar_position: 0.0,
+            attack_phase: None,
+            last_damage_dealt: None,
+            flicker_elapsed: Duration::ZERO,
+            flicker_duration: Duration::from_millis(25),
+            flicker_count: 10,
+            show_flicker_black_screen: false,
+            exit_transition_elapsed: Duration::ZERO,
+            deltarune: Deltarune::new(),
+        })
+    }
+
+    pub fn current_dialogue(&self) -> Option<&Dialogue> {
+        self.dialogues.get(self.current_dialogue_index)
+    }
+
+    /// The enemy keeps the mood of its last line until its attack is over.
+    pub fn current_sprite(&self) -> &str {
+        match (&self.mode, self.current_dialogue()) {
+            (BattleMode::EnemyDialogue | BattleMode::EnemyAttack, Some(dialogue)) => {
+                &dialogue.enemy_sprite_ansi
+            }
+            _ => &self.enemy_sprite_ansi,
+        }
+    }
+
+    pub fn is_spareable(&self) -> bool {
+        if self.spare_conditions.is_empty() {
+            return self.mercy >= 100;
         }
+        self.spare_conditions
+            .iter()
+            .any(|condition| match condition {
+                SpareCondition::Mercy { at_least } => self.mercy >= *at_least,
+                SpareCondition::LowHp { percent } => {
+                    self.enemy_hp * 100 <= self.enemy_max_hp * *percent as i32
+                }
+                SpareCondition::Acted { act } => self.acts_used.contains(act),
+                SpareCondition::Turns { at_least } => self.turn >= *at_least,
+            })
     }
 
+    /// Set once the exit transition is over, the battle can be dropped after that.
+    pub fn finished_outcome(&self) -> Option<BattleOutcome> {
+        match self.mode {
+            BattleMode::ExitTransition(outcome)
+                if self.deltarune.level >= 100
+                    && self.exit_transition_elapsed > Duration::from_millis(500) =>
+            {
+                Some(outcome)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn mercy_options(&self) -> [&'static str; 2] {
+        ["Spare", "Flee"]
+    }
+
+    /// Runs one frame of the battle and returns how much damage the player took. A voice
+    /// blip that can't be played goes in `sound_error`.
     pub fn update(
         &mut self,
-        _delta_time: std::time::Duration,
-        key_states: &HashMap<KeyCode, bool>,
+        key_states: &HashMap<Action, bool>,
+        delta_time: Duration,
         audio: &mut crate::audio::Audio,
-        game_should_exit: &mut bool,
-    ) {
+        now: Instant,
+        sound_error: &mut Option<String>,
+    ) -> i32 {
+        let (damage, typed_char) = self.step(key_states, delta_time);
+        if typed_char
+            && let Some(typed) = self.narrative.last_char()
+            && let Err(e) = audio.play_voice(&self.narrative_voice, typed, now)
+        {
+            *sound_error = Some(format!("Failed to play voice: {}", e));
+        }
+        damage
+    }
+
+    fn step(&mut self, key_states: &HashMap<Action, bool>, delta_time: Duration) -> (i32, bool) {
+        let mut typed_char = false;
+        let mut damage = 0;
         match self.mode {
-            BattleMode::Dialogue => {
-                let current_dialogue = &self.dialogues[self.current_dialogue_index];
-                self.narrative_text = current_dialogue.text.clone();
-                self.narrative_face = current_dialogue.face_ansi.clone();
-
-                //  animation :O
-                if !self.narrative_animation_finished {
-                    if self.narrative_animation_start_time.elapsed() >= self.narrative_animation_interval {
-                        let current_len = self.animated_narrative_content.chars().count();
-                        if current_len < self.narrative_text.chars().count() {
-                            let next_char_index = self.animated_narrative_content.chars().count();
-                            let next_char = self.narrative_text.chars().nth(next_char_index).unwrap();
-                            self.animated_narrative_content.push(next_char);
-                            audio.play_text_sound(); // we scream every time we show a fucking
-                                                     // character (-_-)=b
-
-                            self.narrative_animation_interval = match next_char {
-                                ' ' => Duration::from_millis(100),
-                                ',' => Duration::from_millis(250), 
-                                '.' => {
-                                    if self.narrative_text[next_char_index..].starts_with("...") {
-                                        Duration::from_millis(400) 
-                                    } else {
-                                        Duration::from_millis(300) 
-                                    }
-                                }
-                                _ => Duration::from_millis(rand::thread_rng().gen_range(10..=30)),
-                            };
-                            self.narrative_animation_start_time = Instant::now();
-                        } else {
-                            self.narrative_animation_finished = true;
-                        }
-                    }
-                }
+            BattleMode::Encounter => {
+                self.flicker_elapsed += delta_time;
+                if self.flicker_elapsed >= self.flicker_duration {
+                    self.show_flicker_black_screen = !self.show_flicker_black_screen;
+                    self.flicker_count = self.flicker_count.saturating_sub(1);
+                    self.flicker_elapsed = Duration::ZERO;
 
-                if *key_states.get(&KeyCode::Enter).unwrap_or(&false) {
-                    if !self.narrative_animation_finished {
-                        self.animated_narrative_content = self.narrative_text.clone();
-                        self.narrative_animation_finished = true;
-                    } else {
-                        self.current_dialogue_index += 1;
-                        if self.current_dialogue_index < self.dialogues.len() {
-                            let next_dialogue = &self.dialogues[self.current_dialogue_index];
-                            self.narrative_text = next_dialogue.text.clone();
-                            self.narrative_face = next_dialogue.face_ansi.clone();
-                            self.animated_narrative_content.clear();
-                            self.narrative_animation_finished = false;
-                            self.narrative_animation_start_time = Instant::now();
-                        } else {
-                            self.mode = BattleMode::ExitTransition;
-                            self.exit_transition_timer = Instant::now();
-                        }
+                    if self.flicker_count == 0 {
+                        self.show_flicker_black_screen = false;
+                        self.open_menu();
                     }
-                } else if *key_states.get(&KeyCode::Esc).unwrap_or(&false)
-                    || *key_states.get(&KeyCode::Char('x')).unwrap_or(&false)
-                {
-                    if !self.narrative_animation_finished {
-                        self.animated_narrative_content = self.narrative_text.clone();
-                        self.narrative_animation_finished = true;
+                }
+            }
+            BattleMode::Narration | BattleMode::EnemyDialogue | BattleMode::Outcome(_) => {
+                typed_char = self.narrative.update(self.text_speed.scale(delta_time)) > 0;
+            }
+            BattleMode::PlayerAttack => {
+                self.attack_bar_position +=
+                    delta_time.as_secs_f32() / ATTACK_BAR_DURATION.as_secs_f32();
+                if self.attack_bar_position >= 1.0 {
+                    self.resolve_player_attack();
+                }
+            }
+            BattleMode::EnemyAttack => {
+                if let Some(attack_phase) = &mut self.attack_phase {
+                    damage = attack_phase.update(key_states, delta_time);
+                    if attack_phase.is_finished() {
+                        self.attack_phase = None;
+                        self.turn += 1;
+                        self.open_menu();
                     }
+                } else {
+                    self.open_menu();
                 }
             }
-            BattleMode::ExitTransition => {
+            BattleMode::ExitTransition(_) => {
                 if self.deltarune.level < 100 {
                     self.deltarune.increase();
-                } else if self.exit_transition_timer.elapsed() > Duration::from_secs(3) {
-                    *game_should_exit = true;
+                    self.exit_transition_elapsed = Duration::ZERO;
+                } else {
+                    self.exit_transition_elapsed += delta_time;
                 }
             }
+            BattleMode::Menu
+            | BattleMode::ActMenu
+            | BattleMode::ItemMenu
+            | BattleMode::MercyMenu => {}
         }
+        (damage, typed_char)
     }
-}
-                face_link: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/faces/face_neutral.ans").to_string()),
-                text: "Hello there, human. You've stumbled into my domain...".to_string(),
-            },
-            Dialogue {
-                                enemy_sprite_ansi: fs::read_to_string(
-                    concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/enemy/not_a_placeholder/battle_smile.ans"),
-                )
-                .unwrap_or_default(),
-                face_link: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/faces/face_smile.ans").to_string()),
-                text: "Prepare for a dialogue-only encounter! Mwahaha!".to_string(),
-            },
-            Dialogue {
-                                enemy_sprite_ansi: fs::read_to_string(
-                    concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/enemy/not_a_placeholder/battle_hehehe.ans"),
-                )
-                .unwrap_or_default(),
-                face_link: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/faces/face_hehehe.ans").to_string()),
-                text: "But seriously, this is just a test of the dialogue system...".to_string(),
-            },
-            Dialogue {
-                                enemy_sprite_ansi: fs::read_to_string(
-                    concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/enemy/not_a_placeholder/battle_more_neutral.ans"),
-                )
-                .unwrap_or_default(),
-                face_link: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/faces/face_determined.ans").to_string()),
-                text: "Press Enter to advance, or Esc/X to skip the current line.".to_string(),
-            },
-            Dialogue {
-                                enemy_sprite_ansi: fs::read_to_string(
-                    concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/enemy/not_a_placeholder/battle_3.ans"),
-                )
-                .unwrap_or_default(),
-                face_link: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/faces/face_3.ans").to_string()),
-                text: "Watch out for the pauses... like this, ... and this. And this.".to_string(),
-            },
-        ];
 
-        let current_dialogue = dialogues.first().cloned().unwrap_or_else(|| Dialogue {
-            enemy_sprite_ansi: String::new(),
-            face_link: None,
-            text: "No dialogues defined.".to_string(),
-        });
+    fn set_narrative(&mut self, text: String, face: Option<String>) {
+        self.narrative_text = text;
+        self.narrative_face = face;
+        self.narrative = Typewriter::new(&self.narrative_text, Pace::BATTLE);
+        self.narrative_voice = Voice::default();
+    }
 
-        BattleState {
-            mode: BattleMode::Dialogue,
-            dialogues,
-            current_dialogue_index: 0,
-            narrative_text: current_dialogue.text,
-            narrative_face: current_dialogue.face_link,
-            animated_narrative_content: String::new(),
-            narrative_animation_start_time: Instant::now(),
-            narrative_animation_interval: Duration::from_millis(50),
-            narrative_animation_finished: false,
-            previous_chars_shown: 0,
-            exit_transition_timer: Instant::now(),
-            deltarune: crate::game::deltarune::Deltarune::new(),
+    fn skip_narrative_animation(&mut self) {
+        self.narrative.skip();
+    }
+
+    fn open_menu(&mut self) {
+        self.mode = BattleMode::Menu;
+        self.submenu_index = 0;
+        let text = if self.is_spareable() {
+            format!(
+                "* {} is sparing you... or is it the other way around?",
+                self.enemy_name
+            )
+        } else {
+            format!("* {} blocks the way!", self.enemy_name)
+        };
+        self.set_narrative(text, None);
+        self.skip_narrative_animation();
+    }
+
+    fn start_enemy_dialogue(&mut self) {
+        if self.dialogues.is_empty() {
+            self.start_enemy_attack();
+            return;
         }
+        self.current_dialogue_index = self.turn % self.dialogues.len();
+        let dialogue = self.dialogues[self.current_dialogue_index].clone();
+        self.mode = BattleMode::EnemyDialogue;
+        self.set_narrative(dialogue.text, dialogue.face_ansi);
+        self.narrative_voice = dialogue.voice;
     }
 
-    pub fn update(
-        &mut self,
-        _delta_time: std::time::Duration,
-        key_states: &HashMap<KeyCode, bool>,
-        audio: &mut crate::audio::Audio,
-        game_should_exit: &mut bool,
-    ) {
+    fn start_enemy_attack(&mut self) {
+        if self.attacks.is_empty() {
+            self.turn += 1;
+            self.open_menu();
+            return;
+        }
+        let attack = self.attacks[self.turn % self.attacks.len()].clone();
+        self.attack_phase = Some(AttackPhase::new(attack));
+        self.mode = BattleMode::EnemyAttack;
+    }
+
+    fn finish(&mut self, outcome: BattleOutcome) {
+        let text = match outcome {
+            BattleOutcome::Won => format!(
+                "* YOU WON!\n* You earned {} EXP and {} gold.",
+                self.exp_reward, self.gold_reward
+            ),
+            BattleOutcome::Spared => format!(
+                "* YOU WON!\n* You spared {} and earned {} gold.",
+                self.enemy_name, self.gold_reward
+            ),
+            BattleOutcome::Fled => "* You ran away...".to_string(),
+            BattleOutcome::Lost => "* ...".to_string(),
+        };
+        self.mode = BattleMode::Outcome(outcome);
+        self.set_narrative(text, None);
+    }
+
+    /// Using an item takes the player's turn, `text` is what it did.
+    pub fn show_item_used(&mut self, text: String) {
+        self.item_to_use = None;
+        self.mode = BattleMode::Narration;
+        self.set_narrative(text, None);
+    }
+
+    /// The player ran out of HP, there is nothing left to do in this battle.
+    pub fn lose(&mut self) {
+        self.attack_phase = None;
+        self.mode = BattleMode::ExitTransition(BattleOutcome::Lost);
+        self.exit_transition_elapsed = Duration::ZERO;
+    }
+
+    fn resolve_player_attack(&mut self) {
+        let accuracy = 1.0 - ((self.attack_bar_position - 0.5).abs() * 2.0).min(1.0);
+        let damage = attack_damage(accuracy, self.player_atk, self.enemy_def);
+        self.enemy_hp = (self.enemy_hp - damage).max(0);
+        self.last_damage_dealt = Some(damage);
+        self.attack_bar_position = 0.0;
+
+        if self.enemy_hp == 0 {
+            self.finish(BattleOutcome::Won);
+        } else {
+            self.start_enemy_dialogue();
+        }
+    }
+
+    /// Handles a single key press, repeats included.
+    pub fn handle_action(&mut self, action: Action) {
+        let confirm = action == Action::Confirm;
+        // Esc backs out of menus too
+        let cancel = matches!(action, Action::Cancel | Action::Quit);
+
         match self.mode {
-            BattleMode::Dialogue => {
-                let current_dialogue = &self.dialogues[self.current_dialogue_index];
-                self.narrative_text = current_dialogue.text.clone();
-                self.narrative_face = current_dialogue.face_link.clone();
-
-                //  animation :O
-                if !self.narrative_animation_finished {
-                    if self.narrative_animation_start_time.elapsed() >= self.narrative_animation_interval {
-                        let current_len = self.animated_narrative_content.chars().count();
-                        if current_len < self.narrative_text.chars().count() {
-                            let next_char_index = self.animated_narrative_content.chars().count();
-                            let next_char = self.narrative_text.chars().nth(next_char_index).unwrap();
-                            self.animated_narrative_content.push(next_char);
-                            audio.play_text_sound(); // we scream every time we show a fucking
-                                                     // character (-_-)=b
-
-                            self.narrative_animation_interval = match next_char {
-                                ' ' => Duration::from_millis(100),
-                                ',' => Duration::from_millis(250), 
-                                '.' => {
-                                    if self.narrative_text[next_char_index..].starts_with("...") {
-                                        Duration::from_millis(400) 
-                                    } else {
-                                        Duration::from_millis(300) 
-                                    }
-                                }
-                                _ => Duration::from_millis(rand::thread_rng().gen_range(10..=30)),
-                            };
-                            self.narrative_animation_start_time = Instant::now();
-                        } else {
-                            self.narrative_animation_finished = true;
+            BattleMode::Menu => match action {
+                Action::MoveLeft => self.selected_button = self.selected_button.previous(),
+                Action::MoveRight => self.selected_button = self.selected_button.next(),
+                _ if confirm => {
+                    self.submenu_index = 0;
+                    match self.selected_button {
+                        MenuButton::Fight => {
+                            self.attack_bar_position = 0.0;
+                            self.last_damage_dealt = None;
+                            self.mode = BattleMode::PlayerAttack;
                         }
+                        MenuButton::Act => self.mode = BattleMode::ActMenu,
+                        MenuButton::Item => self.mode = BattleMode::ItemMenu,
+                        MenuButton::Mercy => self.mode = BattleMode::MercyMenu,
                     }
                 }
-
-                if *key_states.get(&KeyCode::Enter).unwrap_or(&false) {
-                    if !self.narrative_animation_finished {
-                        self.animated_narrative_content = self.narrative_text.clone();
-                        self.narrative_animation_finished = true;
-                    } else {
-                        self.current_dialogue_index += 1;
-                        if self.current_dialogue_index < self.dialogues.len() {
-                            let next_dialogue = &self.dialogues[self.current_dialogue_index];
-                            self.narrative_text = next_dialogue.text.clone();
-                            self.narrative_face = next_dialogue.face_link.clone();
-                            self.animated_narrative_content.clear();
-                            self.narrative_animation_finished = false;
-                            self.narrative_animation_start_time = Instant::now();
-                        } else {
-                            self.mode = BattleMode::ExitTransition;
-                            self.exit_transition_timer = Instant::now();
+                _ => {}
+            },
+            BattleMode::ActMenu => match action {
+                Action::MoveUp => self.submenu_index = self.submenu_index.saturating_sub(1),
+                Action::MoveDown => {
+                    if self.submenu_index + 1 < self.act_options.len() {
+                        self.submenu_index += 1;
+                    }
+                }
+                _ if confirm => {
+                    if let Some(option) = self.act_options.get(self.submenu_index).cloned() {
+                        if !self.acts_used.contains(&option.name) {
+                            self.acts_used.push(option.name.clone());
                         }
+                        self.mercy = self.mercy.saturating_add(option.mercy).min(100);
+                        self.mode = BattleMode::Narration;
+                        self.set_narrative(option.text, None);
+                    }
+                }
+                _ if cancel => self.mode = BattleMode::Menu,
+                _ => {}
+            },
+            BattleMode::ItemMenu => match action {
+                Action::MoveUp => self.submenu_index = self.submenu_index.saturating_sub(1),
+                Action::MoveDown => {
+                    if self.submenu_index + 1 < self.items.len() {
+                        self.submenu_index += 1;
+                    }
+                }
+                _ if confirm => {
+                    if self.items.is_empty() {
+                        self.mode = BattleMode::Narration;
+                        self.set_narrative("* You have no items.".to_string(), None);
+                    } else {
+                        self.item_to_use = Some(self.submenu_index.min(self.items.len() - 1));
                     }
-                } else if *key_states.get(&KeyCode::Esc).unwrap_or(&false)
-                    || *key_states.get(&KeyCode::Char('x')).unwrap_or(&false)
-                {
-                    if !self.narrative_animation_finished {
-                        self.animated_narrative_content = self.narrative_text.clone();
-                        self.narrative_animation_finished = true;
+                }
+                _ if cancel => self.mode = BattleMode::Menu,
+                _ => {}
+            },
+            BattleMode::MercyMenu => match action {
+                Action::MoveUp => self.submenu_index = 0,
+                Action::MoveDown => self.submenu_index = 1,
+                _ if confirm => {
+                    if self.submenu_index == 1 {
+                        self.finish(BattleOutcome::Fled);
+                    } else if self.is_spareable() {
+                        self.finish(BattleOutcome::Spared);
+                    } else {
+                        self.mode = BattleMode::Narration;
+                        self.set_narrative(
+                            format!("* You spared {}.\n* But it wasn't ready.", self.enemy_name),
+                            None,
+                        );
                     }
                 }
+                _ if cancel => self.mode = BattleMode::Menu,
+                _ => {}
+            },
+            BattleMode::PlayerAttack => {
+                if confirm {
+                    self.resolve_player_attack();
+                }
             }
-            BattleMode::ExitTransition => {
-                if self.deltarune.level < 100 {
-                    self.deltarune.increase();
-                } else if self.exit_transition_timer.elapsed() > Duration::from_secs(3) {
-                    *game_should_exit = true;
+            BattleMode::Narration | BattleMode::EnemyDialogue | BattleMode::Outcome(_) => {
+                if !self.narrative.is_finished() {
+                    if confirm || cancel {
+                        self.skip_narrative_animation();
+                    }
+                } else if confirm {
+                    match self.mode {
+                        BattleMode::Narration => self.start_enemy_dialogue(),
+                        BattleMode::EnemyDialogue => self.start_enemy_attack(),
+                        BattleMode::Outcome(outcome) => {
+                            self.mode = BattleMode::ExitTransition(outcome);
+                            self.exit_transition_elapsed = Duration::ZERO;
+                        }
+                        _ => {}
+                    }
                 }
             }
+            BattleMode::Encounter | BattleMode::EnemyAttack | BattleMode::ExitTransition(_) => {}
         }
     }
-}
\ No newline at end of file
+}
+
+/// `accuracy` goes from 0 (bar at the edge) to 1 (dead center).
+pub fn attack_damage(accuracy: f32, atk: i32, def: i32) -> i32 {
+    if accuracy <= 0.0 {
+        return 0;
+    }
+    let raw = (atk as f32 * (0.5 + accuracy * 1.5)).round() as i32 - def;
+    raw.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::enemy::DEFAULT_ENEMY_ID;
+
+    fn battle_in_menu() -> BattleState {
+        let enemy = Enemy::load(DEFAULT_ENEMY_ID).unwrap();
+        let mut battle = BattleState::new(&enemy).unwrap();
+        battle.open_menu();
+        battle
+    }
+
+    fn no_keys() -> HashMap<Action, bool> {
+        HashMap::new()
+    }
+
+    #[test]
+    fn menu_selection_wraps_around() {
+        let mut battle = battle_in_menu();
+        battle.handle_action(Action::MoveLeft);
+        assert_eq!(battle.selected_button, MenuButton::Mercy);
+        battle.handle_action(Action::MoveRight);
+        battle.handle_action(Action::MoveRight);
+        assert_eq!(battle.selected_button, MenuButton::Act);
+    }
+
+    #[test]
+    fn fight_hits_enemy_and_hands_turn_to_enemy() {
+        let mut battle = battle_in_menu();
+        battle.handle_action(Action::Confirm);
+        assert_eq!(battle.mode, BattleMode::PlayerAttack);
+
+        battle.attack_bar_position = 0.5;
+        battle.handle_action(Action::Confirm);
+
+        let dealt = battle.last_damage_dealt.unwrap();
+        assert!(dealt > 0);
+        assert_eq!(battle.enemy_hp, battle.enemy_max_hp - dealt);
+        assert_eq!(battle.mode, BattleMode::EnemyDialogue);
+    }
+
+    #[test]
+    fn letting_the_bar_run_out_misses() {
+        let mut battle = battle_in_menu();
+        battle.handle_action(Action::Confirm);
+        battle.step(&no_keys(), ATTACK_BAR_DURATION + Duration::from_millis(1));
+        assert_eq!(battle.last_damage_dealt, Some(0));
+        assert_eq!(battle.enemy_hp, battle.enemy_max_hp);
+    }
+
+    #[test]
+    fn killing_blow_wins_the_battle() {
+        let mut battle = battle_in_menu();
+        battle.enemy_hp = 1;
+        battle.handle_action(Action::Confirm);
+        battle.attack_bar_position = 0.5;
+        battle.handle_action(Action::Confirm);
+        assert_eq!(battle.mode, BattleMode::Outcome(BattleOutcome::Won));
+    }
+
+    #[test]
+    fn acting_builds_mercy_until_spareable() {
+        let mut battle = battle_in_menu();
+        assert!(!battle.is_spareable());
+        for _ in 0..2 {
+            battle.open_menu();
+            battle.selected_button = MenuButton::Act;
+            battle.handle_action(Action::Confirm);
+            battle.handle_action(Action::MoveDown);
+            battle.handle_action(Action::Confirm);
+            assert_eq!(battle.mode, BattleMode::Narration);
+        }
+        assert!(battle.is_spareable());
+
+        battle.open_menu();
+        battle.selected_button = MenuButton::Mercy;
+        battle.handle_action(Action::Confirm);
+        battle.handle_action(Action::Confirm);
+        assert_eq!(battle.mode, BattleMode::Outcome(BattleOutcome::Spared));
+    }
+
+    #[test]
+    fn sparing_too_early_keeps_fighting() {
+        let mut battle = battle_in_menu();
+        battle.selected_button = MenuButton::Mercy;
+        battle.handle_action(Action::Confirm);
+        battle.handle_action(Action::Confirm);
+        assert_eq!(battle.mode, BattleMode::Narration);
+    }
+
+    #[test]
+    fn fleeing_ends_the_battle() {
+        let mut battle = battle_in_menu();
+        battle.selected_button = MenuButton::Mercy;
+        battle.handle_action(Action::Confirm);
+        battle.handle_action(Action::MoveDown);
+        battle.handle_action(Action::Confirm);
+        assert_eq!(battle.mode, BattleMode::Outcome(BattleOutcome::Fled));
+
+        battle.skip_narrative_animation();
+        battle.handle_action(Action::Confirm);
+        assert_eq!(battle.mode, BattleMode::ExitTransition(BattleOutcome::Fled));
+    }
+
+    #[test]
+    fn cancel_returns_to_menu() {
+        let mut battle = battle_in_menu();
+        battle.selected_button = MenuButton::Act;
+        battle.handle_action(Action::Confirm);
+        battle.handle_action(Action::Cancel);
+        assert_eq!(battle.mode, BattleMode::Menu);
+    }
+
+    #[test]
+    fn picking_an_item_waits_for_the_game_to_use_it() {
+        let mut battle = battle_in_menu();
+        battle.items = vec!["Bandage".to_string(), "Pie".to_string()];
+        battle.selected_button = MenuButton::Item;
+        battle.handle_action(Action::Confirm);
+        battle.handle_action(Action::MoveDown);
+        battle.handle_action(Action::Confirm);
+        assert_eq!(battle.item_to_use, Some(1));
+        assert_eq!(battle.mode, BattleMode::ItemMenu);
+
+        battle.show_item_used("* You ate the Pie.".to_string());
+        assert_eq!(battle.item_to_use, None);
+        assert_eq!(battle.mode, BattleMode::Narration);
+        assert_eq!(battle.narrative_text, "* You ate the Pie.");
+    }
+
+    #[test]
+    fn enemy_attack_runs_for_its_duration_then_returns_to_menu() {
+        let mut battle = battle_in_menu();
+        battle.start_enemy_attack();
+        assert_eq!(battle.mode, BattleMode::EnemyAttack);
+
+        let duration = battle.attack_phase.as_ref().unwrap().attack.duration;
+        let mut elapsed = Duration::ZERO;
+        while elapsed <= duration {
+            battle.step(&no_keys(), Duration::from_millis(100));
+            elapsed += Duration::from_millis(100);
+        }
+        assert_eq!(battle.mode, BattleMode::Menu);
+        assert_eq!(battle.turn, 1);
+    }
+
+    #[test]
+    fn enemy_dialogue_leads_into_enemy_attack() {
+        let mut battle = battle_in_menu();
+        battle.start_enemy_dialogue();
+        battle.handle_action(Action::Confirm); // skip the typewriter
+        battle.handle_action(Action::Confirm);
+        assert_eq!(battle.mode, BattleMode::EnemyAttack);
+    }
+
+    #[test]
+    fn damage_scales_with_accuracy() {
+        assert_eq!(attack_damage(0.0, 10, 2), 0);
+        assert!(attack_damage(1.0, 10, 2) > attack_damage(0.3, 10, 2));
+        assert_eq!(attack_damage(0.01, 1, 50), 1);
+    }
+
+    #[test]
+    fn battle_is_built_from_the_enemy_file() {
+        let enemy = Enemy::load(DEFAULT_ENEMY_ID).unwrap();
+        let battle = BattleState::new(&enemy).unwrap();
+        assert_eq!(battle.enemy_name, enemy.name);
+        assert_eq!(battle.enemy_max_hp, enemy.hp);
+        assert_eq!(battle.dialogues.len(), enemy.dialogue.len());
+        assert_eq!(battle.attacks.len(), enemy.attacks.len());
+        assert!(Enemy::load("nobody").is_err());
+    }
+
+    #[test]
+    fn any_spare_condition_makes_the_enemy_spareable() {
+        let mut battle = battle_in_menu();
+        battle.spare_conditions = vec![
+            SpareCondition::Acted {
+                act: "Talk".to_string(),
+            },
+            SpareCondition::LowHp { percent: 25 },
+        ];
+        assert!(!battle.is_spareable());
+
+        battle.enemy_hp = battle.enemy_max_hp / 4;
+        assert!(battle.is_spareable());
+
+        battle.enemy_hp = battle.enemy_max_hp;
+        battle.selected_button = MenuButton::Act;
+        battle.handle_action(Action::Confirm);
+        battle.handle_action(Action::MoveDown);
+        battle.handle_action(Action::Confirm);
+        assert!(battle.is_spareable());
+    }
+}
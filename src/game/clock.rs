@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+/// Where the overworld's timers get the time from. The real clock follows the wall clock, a
+/// stepped one stands still until it's advanced. The game loop advances it by one fixed step
+/// per update, so fades and cooldowns come out the same however the frames were spaced,
+/// and tests can step through them without waiting.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    origin: Instant,
+    // time since `origin`, only for a stepped clock
+    stepped_elapsed: Option<Duration>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::real()
+    }
+}
+
+impl Clock {
+    pub fn real() -> Self {
+        Clock {
+            origin: Instant::now(),
+            stepped_elapsed: None,
+        }
+    }
+
+    pub fn stepped() -> Self {
+        Clock {
+            origin: Instant::now(),
+            stepped_elapsed: Some(Duration::ZERO),
+        }
+    }
+
+    pub fn is_stepped(&self) -> bool {
+        self.stepped_elapsed.is_some()
+    }
+
+    pub fn now(&self) -> Instant {
+        match self.stepped_elapsed {
+            Some(elapsed) => self.origin + elapsed,
+            None => Instant::now(),
+        }
+    }
+
+    /// `Instant::elapsed`, by this clock.
+    pub fn since(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+
+    /// Moves a stepped clock forward, the real one can't be pushed around.
+    pub fn advance(&mut self, delta_time: Duration) {
+        if let Some(elapsed) = &mut self.stepped_elapsed {
+            *elapsed += delta_time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stepped_clock_only_moves_when_advanced() {
+        let mut clock = Clock::stepped();
+        let start = clock.now();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(clock.since(start), Duration::ZERO);
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(clock.since(start), Duration::from_millis(250));
+    }
+}
@@ -3,8 +3,36 @@ use std::time::Duration;
 pub const PLAYER_SPEED: f32 = 40.0;
 pub const PLAYER_HORIZONTAL_SPEED: f32 = 80.0;
 pub const DEBUG_MOVEMENT_FRAME_INTERVAL: u8 = 5;
-pub const FRAME_RATE: u64 = 120;
+// most frames drawn a second, there's nothing new to draw more often than SIMULATION_RATE
+pub const FRAME_RATE: u64 = 60;
+// the game logic always steps at this rate, whatever the frame rate
+pub const SIMULATION_RATE: u64 = 60;
+pub const SIMULATION_STEP: Duration = Duration::from_nanos(1_000_000_000 / SIMULATION_RATE);
 pub const ANIMATION_FRAME_DURATION: Duration = Duration::from_millis(200);
 pub const PLAYER_INTERACTION_BOX_WIDTH: u16 = 30;
 pub const PLAYER_INTERACTION_BOX_HEIGHT: u16 = 20;
 pub const TELEPORT_COOLDOWN_DURATION: Duration = Duration::from_millis(500);
+pub const TELEPORT_FADE_OUT_DURATION: Duration = Duration::from_millis(500);
+pub const TELEPORT_FADE_IN_DURATION: Duration = Duration::from_millis(750);
+// switching tracks outside of a teleport, e.g. into a battle
+pub const MUSIC_FADE_DURATION: Duration = Duration::from_millis(400);
+pub const DEFAULT_MUSIC_VOLUME: f32 = 0.6;
+pub const DEFAULT_SFX_VOLUME: f32 = 1.0;
+pub const BULLET_BOX_WIDTH: u16 = 60;
+pub const BULLET_BOX_HEIGHT: u16 = 18;
+pub const SOUL_SPEED: f32 = 15.0;
+pub const SOUL_HORIZONTAL_SPEED: f32 = 30.0;
+pub const SOUL_INVINCIBILITY_DURATION: Duration = Duration::from_millis(750);
+pub const PLAYER_MAX_HP: i32 = 20;
+pub const ATTACK_BAR_DURATION: Duration = Duration::from_millis(1500);
+pub const PLAYER_BASE_ATK: i32 = 10;
+pub const PLAYER_BASE_DEF: i32 = 0;
+// total EXP needed to reach LV 2, 3, 4...
+pub const LV_EXP_THRESHOLDS: [u32; 19] = [
+    10, 30, 70, 120, 200, 300, 500, 800, 1200, 1700, 2500, 3500, 5000, 7000, 10000, 15000, 25000,
+    50000, 99999,
+];
+pub const GAME_OVER_FADE_DURATION: Duration = Duration::from_secs(2);
+
+// how many items fit in the ITEM menu
+pub const INVENTORY_CAPACITY: usize = 8;
@@ -1,10 +1,16 @@
-use serde::{Serialize, Deserialize};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Deltarune {
     pub level: u8,
 }
 
+impl Default for Deltarune {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Deltarune {
     pub fn new() -> Self {
         Self { level: 0 }
@@ -21,4 +27,4 @@ impl Deltarune {
             self.level -= 1;
         }
     }
-}
\ No newline at end of file
+}
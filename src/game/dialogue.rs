@@ -1,58 +1,481 @@
+use super::map::Event;
+use super::typewriter::{Pace, Typewriter};
+use crate::audio::Voice;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
+/// One line of a conversation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dialogue {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    // either sprite can be left out
+    #[serde(default)]
     pub enemy_ansi_path: String,
+    #[serde(default)]
     pub face_ansi_path: String,
+    // the default text blip when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice: Option<Voice>,
     pub text: String,
+    // node shown after this one, the conversation ends without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    // when there are choices the picked one decides where to go instead of `next`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<DialogueChoice>,
+    // run once the conversation is over, if this line was shown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DialogueChoice {
+    pub text: String,
+    #[serde(default)]
+    pub next: Option<String>,
+    // SetFlag, StartBattle... run once the conversation is over
+    #[serde(default)]
+    pub events: Vec<Event>,
+}
+
+/// A conversation as written in `assets/dialogues/<id>.json`: named nodes and the one it
+/// starts on. A plain list of lines works too, it's read as nodes chained one after another.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DialogueGraph {
+    pub start: String,
+    pub nodes: BTreeMap<String, Dialogue>,
+}
+
+impl DialogueGraph {
+    /// Loads `assets/dialogues/<id>.json`.
+    pub fn load(id: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let dialogues_content = crate::assets::store().load_str(&DialogueManager::path(id))?;
+        let graph = match DialogueFile::parse(&dialogues_content)? {
+            DialogueFile::Graph(graph) => graph,
+            DialogueFile::Lines(lines) if lines.is_empty() => {
+                return Err(format!("Dialogue {} has no lines", id).into());
+            }
+            DialogueFile::Lines(lines) => DialogueGraph::from_lines(lines),
+        };
+        graph
+            .validate()
+            .map_err(|e| format!("Dialogue {}: {}", id, e))?;
+        Ok(graph)
+    }
+
+    pub fn from_lines(lines: Vec<Dialogue>) -> Self {
+        let count = lines.len();
+        let nodes = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut line)| {
+                if line.next.is_none() && line.choices.is_empty() && i + 1 < count {
+                    line.next = Some((i + 1).to_string());
+                }
+                (i.to_string(), line)
+            })
+            .collect();
+        DialogueGraph {
+            start: "0".to_string(),
+            nodes,
+        }
+    }
+
+    /// Adds `line` after every place the conversation can end now, under the first free number.
+    pub fn push_line(&mut self, line: Dialogue) {
+        let name = (0..)
+            .map(|i: usize| i.to_string())
+            .find(|name| !self.nodes.contains_key(name))
+            .unwrap();
+        if self.nodes.is_empty() {
+            self.start = name.clone();
+        }
+        for node in self.nodes.values_mut() {
+            if node.choices.is_empty() {
+                node.next.get_or_insert_with(|| name.clone());
+            }
+            for choice in &mut node.choices {
+                choice.next.get_or_insert_with(|| name.clone());
+            }
+        }
+        self.nodes.insert(name, line);
+    }
+
+    /// Every `next` has to point at a node, and every line the player can reach needs a
+    /// way to the end, so typos and endless loops show up when the file is loaded.
+    fn validate(&self) -> Result<(), String> {
+        if !self.nodes.contains_key(&self.start) {
+            return Err(format!("start node {} doesn't exist", self.start));
+        }
+        for (name, node) in &self.nodes {
+            let targets = node.next.iter().chain(
+                node.choices
+                    .iter()
+                    .filter_map(|choice| choice.next.as_ref()),
+            );
+            for target in targets {
+                if !self.nodes.contains_key(target) {
+                    return Err(format!(
+                        "node {} goes to {}, which doesn't exist",
+                        name, target
+                    ));
+                }
+            }
+        }
+
+        // grow the set of lines that can finish the conversation until it stops changing
+        let mut can_end: BTreeSet<&str> = BTreeSet::new();
+        loop {
+            let before = can_end.len();
+            for (name, node) in &self.nodes {
+                if Self::ways_on(node)
+                    .into_iter()
+                    .any(|next| next.is_none_or(|next| can_end.contains(next)))
+                {
+                    can_end.insert(name);
+                }
+            }
+            if can_end.len() == before {
+                break;
+            }
+        }
+
+        let mut reachable = BTreeSet::from([self.start.as_str()]);
+        let mut unvisited = vec![self.start.as_str()];
+        while let Some(name) = unvisited.pop() {
+            for next in Self::ways_on(&self.nodes[name]).into_iter().flatten() {
+                if reachable.insert(next) {
+                    unvisited.push(next);
+                }
+            }
+        }
+        match reachable.iter().find(|name| !can_end.contains(*name)) {
+            Some(name) => Err(format!("node {} loops forever, nothing leads out", name)),
+            None => Ok(()),
+        }
+    }
+
+    // where advancing from `node` can go, `None` being the end of the conversation
+    fn ways_on(node: &Dialogue) -> Vec<Option<&str>> {
+        if node.choices.is_empty() {
+            vec![node.next.as_deref()]
+        } else {
+            node.choices
+                .iter()
+                .map(|choice| choice.next.as_deref())
+                .collect()
+        }
+    }
+}
+
+/// A dialogue file the way it's written, so the editor can add to it and save it back as is.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum DialogueFile {
+    Lines(Vec<Dialogue>),
+    Graph(DialogueGraph),
+}
+
+impl DialogueFile {
+    pub fn parse(content: &str) -> Result<Self, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        if value.is_array() {
+            Ok(DialogueFile::Lines(serde_json::from_value(value)?))
+        } else {
+            Ok(DialogueFile::Graph(serde_json::from_value(value)?))
+        }
+    }
+
+    pub fn push_line(&mut self, line: Dialogue) {
+        match self {
+            DialogueFile::Lines(lines) => lines.push(line),
+            DialogueFile::Graph(graph) => graph.push_line(line),
+        }
+    }
+}
+
+/// The dialogue the game ends on, played after the battle.
+pub const ENDING_DIALOGUE_ID: &str = "ending";
+
+/// Every conversation in `assets/dialogues`, by id. Files are read the first time
+/// they're asked for and kept around after that.
+#[derive(Debug, Clone, Default)]
+pub struct ConversationRegistry {
+    graphs: HashMap<String, DialogueGraph>,
+}
+
+impl ConversationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn available_ids() -> Vec<String> {
+        crate::assets::store()
+            .list("dialogues")
+            .into_iter()
+            .filter_map(|name| name.strip_suffix(".json").map(str::to_string))
+            .collect()
+    }
+
+    pub fn get(&mut self, id: &str) -> Result<&DialogueGraph, Box<dyn std::error::Error>> {
+        if !self.graphs.contains_key(id) {
+            let graph = DialogueGraph::load(id)?;
+            self.graphs.insert(id.to_string(), graph);
+        }
+        Ok(&self.graphs[id])
+    }
+
+    /// Registers a conversation that doesn't come from a file, it wins over one that does.
+    pub fn insert(&mut self, id: &str, graph: DialogueGraph) {
+        self.graphs.insert(id.to_string(), graph);
+    }
+
+    /// A fresh manager sitting on the first line of the conversation.
+    pub fn start(&mut self, id: &str) -> Result<DialogueManager, Box<dyn std::error::Error>> {
+        Ok(DialogueManager::new(self.get(id)?.clone()))
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DialogueManager {
-    pub dialogues: Vec<Dialogue>,
-    pub current_dialogue_index: usize,
-    pub animated_text: String,
-    pub text_animation_finished: bool,
-    pub visible_text_len: usize,
+    pub graph: DialogueGraph,
+    pub current_node: String,
+    pub selected_choice: usize,
+    // events from the shown lines and picked choices, run when the conversation ends
+    #[serde(default)]
+    pub pending_events: Vec<Event>,
+    #[serde(skip)]
+    pub typewriter: Typewriter,
 }
 
 impl DialogueManager {
-    pub fn new() -> Self {
-        let dialogues = Self::load_dialogues().unwrap_or_default();
-        DialogueManager {
-            dialogues,
+    pub fn new(graph: DialogueGraph) -> Self {
+        let mut manager = DialogueManager {
+            current_node: graph.start.clone(),
+            graph,
             ..Default::default()
-        }
+        };
+        manager.restart_typewriter();
+        manager
     }
 
-    fn load_dialogues() -> Result<Vec<Dialogue>, Box<dyn std::error::Error>> {
-        let dialogues_content = include_str!("../../dialogues.json");
-        let dialogues: Vec<Dialogue> = serde_json::from_str(dialogues_content)?;
-        Ok(dialogues)
+    fn restart_typewriter(&mut self) {
+        let text = self
+            .current_dialogue()
+            .map(|dialogue| dialogue.text.as_str())
+            .unwrap_or_default();
+        self.typewriter = Typewriter::new(text, Pace::DIALOGUE);
+    }
+
+    pub fn current_voice(&self) -> Voice {
+        self.current_dialogue()
+            .and_then(|dialogue| dialogue.voice.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn is_text_finished(&self) -> bool {
+        self.typewriter.is_finished()
+    }
+
+    pub fn path(id: &str) -> String {
+        format!("dialogues/{}.json", id)
     }
 
     pub fn current_dialogue(&self) -> Option<&Dialogue> {
-        self.dialogues.get(self.current_dialogue_index)
+        self.graph.nodes.get(&self.current_node)
+    }
+
+    /// The choices to draw, only once the line is fully typed out.
+    pub fn visible_choices(&self) -> &[DialogueChoice] {
+        match self.current_dialogue() {
+            Some(dialogue) if self.is_text_finished() => &dialogue.choices,
+            _ => &[],
+        }
+    }
+
+    pub fn select_previous_choice(&mut self) {
+        self.selected_choice = self.selected_choice.saturating_sub(1);
+    }
+
+    pub fn select_next_choice(&mut self) {
+        if self.selected_choice + 1 < self.visible_choices().len() {
+            self.selected_choice += 1;
+        }
     }
 
+    /// Moves on from the current line, following the selected choice if it has any.
+    /// Returns true when the conversation is over.
     pub fn advance_dialogue(&mut self) -> bool {
-        if self.current_dialogue_index < self.dialogues.len() - 1 {
-            self.current_dialogue_index += 1;
-            self.animated_text.clear();
-            self.text_animation_finished = false;
-            self.visible_text_len = 0;
-            false
-        } else {
-            // No more dialogues, signal game state to change
-            // This will be handled in the main game loop update
-            true
+        let Some(dialogue) = self.current_dialogue() else {
+            return true;
+        };
+        let mut events = dialogue.events.clone();
+        let next = match dialogue.choices.get(self.selected_choice) {
+            Some(choice) => {
+                events.extend(choice.events.iter().cloned());
+                choice.next.clone()
+            }
+            None => dialogue.next.clone(),
+        };
+        self.pending_events.extend(events);
+
+        match next {
+            Some(next) => {
+                self.current_node = next;
+                self.selected_choice = 0;
+                self.restart_typewriter();
+                false
+            }
+            None => true,
         }
     }
 
     pub fn skip_animation(&mut self) {
-        if let Some(dialogue) = self.current_dialogue() {
-            self.visible_text_len = dialogue.text.chars().count();
-            self.text_animation_finished = true;
+        self.typewriter.skip();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str, next: Option<&str>) -> Dialogue {
+        Dialogue {
+            speaker: None,
+            enemy_ansi_path: String::new(),
+            face_ansi_path: String::new(),
+            voice: None,
+            text: text.to_string(),
+            next: next.map(str::to_string),
+            choices: Vec::new(),
+            events: Vec::new(),
         }
     }
+
+    fn choice(text: &str, next: Option<&str>) -> DialogueChoice {
+        DialogueChoice {
+            text: text.to_string(),
+            next: next.map(str::to_string),
+            events: Vec::new(),
+        }
+    }
+
+    fn graph(nodes: Vec<(&str, Dialogue)>) -> DialogueGraph {
+        DialogueGraph {
+            start: nodes[0].0.to_string(),
+            nodes: nodes
+                .into_iter()
+                .map(|(name, node)| (name.to_string(), node))
+                .collect(),
+        }
+    }
+
+    fn question() -> DialogueGraph {
+        let mut ask = line("Spare me?", None);
+        ask.choices = vec![choice("Yes", Some("spared")), choice("No", Some("fight"))];
+        graph(vec![
+            ("ask", ask),
+            ("spared", line("Thank you.", None)),
+            ("fight", line("So be it.", None)),
+        ])
+    }
+
+    #[test]
+    fn the_picked_choice_decides_the_next_line() {
+        let mut manager = DialogueManager::new(question());
+        manager.skip_animation();
+        assert_eq!(manager.visible_choices().len(), 2);
+        manager.select_next_choice();
+        manager.select_next_choice();
+        assert_eq!(manager.selected_choice, 1);
+
+        assert!(!manager.advance_dialogue());
+        assert_eq!(manager.current_node, "fight");
+        assert_eq!(manager.selected_choice, 0);
+        assert!(manager.advance_dialogue());
+    }
+
+    #[test]
+    fn validate_catches_missing_nodes_and_endless_loops() {
+        assert!(question().validate().is_ok());
+
+        let typo = graph(vec![
+            ("0", line("Hi.", Some("1"))),
+            ("1", line("Bye.", Some("2"))),
+        ]);
+        assert_eq!(
+            typo.validate(),
+            Err("node 1 goes to 2, which doesn't exist".to_string())
+        );
+
+        let stuck = graph(vec![
+            ("a", line("Again.", Some("b"))),
+            ("b", line("And again.", Some("a"))),
+        ]);
+        assert!(stuck.validate().unwrap_err().contains("loops forever"));
+
+        // a loop is fine as long as a choice leads out of it
+        let mut again = line("Once more?", None);
+        again.choices = vec![choice("Yes", Some("again")), choice("No", None)];
+        assert!(graph(vec![("again", again)]).validate().is_ok());
+    }
+
+    #[test]
+    fn a_plain_list_of_lines_plays_in_order() {
+        let lines: Vec<Dialogue> =
+            serde_json::from_str(r#"[{ "text": "one" }, { "text": "two" }, { "text": "three" }]"#)
+                .unwrap();
+        let graph = DialogueGraph::from_lines(lines);
+        assert!(graph.validate().is_ok());
+
+        let mut manager = DialogueManager::new(graph);
+        let mut shown = vec![manager.current_dialogue().unwrap().text.clone()];
+        while !manager.advance_dialogue() {
+            shown.push(manager.current_dialogue().unwrap().text.clone());
+        }
+        assert_eq!(shown, ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn a_pushed_line_is_saved_in_the_format_the_file_had() {
+        let mut list = DialogueFile::parse(r#"[{ "text": "one" }]"#).unwrap();
+        list.push_line(line("two", None));
+        let saved: serde_json::Value = serde_json::to_value(&list).unwrap();
+        assert_eq!(saved[1]["text"], "two");
+
+        let mut graph = DialogueFile::Graph(question());
+        graph.push_line(line("The end.", None));
+        let saved = serde_json::to_string(&graph).unwrap();
+        let DialogueFile::Graph(graph) = DialogueFile::parse(&saved).unwrap() else {
+            panic!("the graph was saved as a list");
+        };
+        assert!(graph.validate().is_ok());
+        // both ways the question could end now lead to the new line
+        assert_eq!(graph.nodes["spared"].next.as_deref(), Some("0"));
+        assert_eq!(graph.nodes["fight"].next.as_deref(), Some("0"));
+        assert_eq!(graph.nodes["0"].text, "The end.");
+    }
+
+    #[test]
+    fn the_registry_finds_conversations_by_id() {
+        let mut registry = ConversationRegistry::new();
+        assert!(ConversationRegistry::available_ids().contains(&ENDING_DIALOGUE_ID.to_string()));
+        let graph = registry.get(ENDING_DIALOGUE_ID).unwrap().clone();
+        let manager = registry.start(ENDING_DIALOGUE_ID).unwrap();
+        assert_eq!(manager.current_node, graph.start);
+        assert_eq!(
+            manager.current_dialogue().unwrap().text,
+            graph.nodes[&graph.start].text
+        );
+
+        let error = registry.start("nobody_says_this").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("dialogues/nobody_says_this.json"),
+            "{}",
+            error
+        );
+    }
 }
@@ -0,0 +1,92 @@
+use super::attack::Attack;
+use super::battle::ActOption;
+use crate::audio::Voice;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DEFAULT_ENEMY_ID: &str = "not_a_placeholder";
+
+fn default_enemy_mood() -> String {
+    "neutral".to_string()
+}
+
+/// One thing the enemy says before its attack, shown with the sprite for `mood`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnemyLine {
+    #[serde(default = "default_enemy_mood")]
+    pub mood: String,
+    pub face: Option<String>,
+    // a line said by someone else (usually with a face) can sound like them
+    #[serde(default)]
+    pub voice: Option<Voice>,
+    pub text: String,
+}
+
+/// When the enemy's name turns yellow in the MERCY menu. Any one of them is enough.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SpareCondition {
+    Mercy { at_least: u8 },
+    LowHp { percent: u8 },
+    Acted { act: String },
+    Turns { at_least: usize },
+}
+
+/// An enemy as written in `assets/enemies/<id>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Enemy {
+    pub id: String,
+    pub name: String,
+    pub hp: i32,
+    pub def: i32,
+    #[serde(default)]
+    pub exp_reward: u32,
+    #[serde(default)]
+    pub gold_reward: u32,
+    // mood -> sprite path, "neutral" is used when a mood has no sprite
+    pub sprites: HashMap<String, String>,
+    #[serde(default)]
+    pub act_options: Vec<ActOption>,
+    #[serde(default)]
+    pub attacks: Vec<Attack>,
+    #[serde(default)]
+    pub spare_conditions: Vec<SpareCondition>,
+    #[serde(default)]
+    pub dialogue: Vec<EnemyLine>,
+    #[serde(default)]
+    pub voice: Voice,
+    // battle track, the map's music keeps playing without one
+    #[serde(default)]
+    pub music: Option<String>,
+}
+
+impl Enemy {
+    pub fn load(enemy_id: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let data_content =
+            crate::assets::store().load_str(&format!("enemies/{}.json", enemy_id))?;
+        let enemy: Enemy = serde_json::from_str(&data_content)?;
+        Ok(enemy)
+    }
+
+    pub fn available_ids() -> Vec<String> {
+        crate::assets::store()
+            .list("enemies")
+            .into_iter()
+            .filter_map(|name| name.strip_suffix(".json").map(str::to_string))
+            .collect()
+    }
+
+    /// The ANSI art for `mood`, falling back to the neutral sprite.
+    pub fn sprite_for(&self, mood: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let path = self
+            .sprites
+            .get(mood)
+            .or_else(|| self.sprites.get(&default_enemy_mood()))
+            .ok_or_else(|| format!("Enemy {} has no sprite for mood {}", self.id, mood))?;
+        load_ansi(path)
+    }
+}
+
+pub(crate) fn load_ansi(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(crate::assets::store().load_str(path)?.into_owned())
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FlagValue {
+    Bool(bool),
+    Int(i64),
+}
+
+impl FlagValue {
+    /// `true` and anything but 0 count as set.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            FlagValue::Bool(value) => *value,
+            FlagValue::Int(value) => *value != 0,
+        }
+    }
+
+    pub fn as_int(&self) -> i64 {
+        match self {
+            FlagValue::Bool(value) => *value as i64,
+            FlagValue::Int(value) => *value,
+        }
+    }
+}
+
+/// Named story variables: "talked_to_sign", "times_died" and friends. Saved with the game.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Flags {
+    values: BTreeMap<String, FlagValue>,
+}
+
+impl Flags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<FlagValue> {
+        self.values.get(name).copied()
+    }
+
+    /// Unknown flags are unset.
+    pub fn is_set(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|value| value.is_truthy())
+    }
+
+    pub fn get_int(&self, name: &str) -> i64 {
+        self.get(name).map(|value| value.as_int()).unwrap_or(0)
+    }
+
+    pub fn set(&mut self, name: &str, value: FlagValue) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// Treats a missing flag as 0 and a boolean as 0 or 1.
+    pub fn increment(&mut self, name: &str, by: i64) {
+        let value = self.get_int(name).saturating_add(by);
+        self.values.insert(name.to_string(), FlagValue::Int(value));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.values.remove(name);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &FlagValue)> {
+        self.values.iter()
+    }
+
+    /// The `requires_flag` / `unless_flag` pair boxes and battle zones carry.
+    pub fn allows(&self, requires_flag: Option<&str>, unless_flag: Option<&str>) -> bool {
+        requires_flag.is_none_or(|name| self.is_set(name))
+            && !unless_flag.is_some_and(|name| self.is_set(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_read_back_what_was_set() {
+        let mut flags = Flags::new();
+        assert_eq!(flags.get("talked_to_sign"), None);
+        assert!(!flags.is_set("talked_to_sign"));
+
+        flags.set("talked_to_sign", FlagValue::Bool(true));
+        flags.increment("times_died", 2);
+        flags.increment("talked_to_sign", 1);
+        assert_eq!(flags.get("talked_to_sign"), Some(FlagValue::Int(2)));
+        assert_eq!(flags.get_int("times_died"), 2);
+        assert!(flags.allows(Some("times_died"), Some("never_set")));
+        assert!(!flags.allows(None, Some("times_died")));
+
+        flags.set("times_died", FlagValue::Int(0));
+        assert!(!flags.is_set("times_died"));
+        flags.remove("talked_to_sign");
+        assert_eq!(flags.get("talked_to_sign"), None);
+    }
+
+    #[test]
+    fn flags_are_saved_as_a_plain_object() {
+        let mut flags = Flags::new();
+        flags.set("opened_door", FlagValue::Bool(true));
+        flags.set("gold_found", FlagValue::Int(12));
+
+        let saved = serde_json::to_string(&flags).unwrap();
+        assert_eq!(saved, r#"{"gold_found":12,"opened_door":true}"#);
+        assert_eq!(serde_json::from_str::<Flags>(&saved).unwrap(), flags);
+    }
+}
@@ -0,0 +1,247 @@
+use super::config::INVENTORY_CAPACITY;
+use super::player::{Equipment, PlayerStats};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ItemKind {
+    Heal { hp: i32 },
+    Weapon { atk: i32 },
+    Armor { def: i32 },
+    // only there for scripts to check, using it does nothing
+    Key,
+}
+
+/// An item as written in `assets/items/<id>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub kind: ItemKind,
+    // replaces the default "* You ate the ..." line
+    #[serde(default)]
+    pub use_text: Option<String>,
+}
+
+impl Item {
+    pub fn load(item_id: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let data_content = crate::assets::store().load_str(&format!("items/{}.json", item_id))?;
+        let item: Item = serde_json::from_str(&data_content)?;
+        Ok(item)
+    }
+
+    pub fn available_ids() -> Vec<String> {
+        crate::assets::store()
+            .list("items")
+            .into_iter()
+            .filter_map(|name| name.strip_suffix(".json").map(str::to_string))
+            .collect()
+    }
+
+    pub fn is_droppable(&self) -> bool {
+        self.kind != ItemKind::Key
+    }
+
+    pub fn info_text(&self) -> String {
+        let stat = match self.kind {
+            ItemKind::Heal { hp } => format!("Heals {} HP", hp),
+            ItemKind::Weapon { atk } => format!("Weapon AT {}", atk),
+            ItemKind::Armor { def } => format!("Armor DF {}", def),
+            ItemKind::Key => "Key item".to_string(),
+        };
+        format!("* \"{}\" - {}\n* {}", self.name, stat, self.description)
+    }
+}
+
+/// The item ids the player is carrying, in pickup order. Saved as a plain list.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Inventory {
+    items: Vec<String>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= INVENTORY_CAPACITY
+    }
+
+    pub fn contains(&self, item_id: &str) -> bool {
+        self.items.iter().any(|id| id == item_id)
+    }
+
+    pub fn ids(&self) -> &[String] {
+        &self.items
+    }
+
+    /// False when the player can't carry any more.
+    pub fn add(&mut self, item_id: &str) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.items.push(item_id.to_string());
+        true
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<String> {
+        (index < self.items.len()).then(|| self.items.remove(index))
+    }
+
+    /// What the menus list. Items whose file is missing still show up, by id.
+    pub fn names(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .map(|id| {
+                Item::load(id)
+                    .map(|item| item.name)
+                    .unwrap_or_else(|_| id.clone())
+            })
+            .collect()
+    }
+
+    /// USE from the overworld menu or the battle ITEM command. Food gets eaten, equipment
+    /// gets swapped with whatever was worn. Returns the line to show the player.
+    pub fn use_item(&mut self, index: usize, stats: &mut PlayerStats) -> String {
+        let Some(item_id) = self.items.get(index) else {
+            return "* You have no items.".to_string();
+        };
+        let item = match Item::load(item_id) {
+            Ok(item) => item,
+            Err(e) => return format!("* Couldn't use {}: {}", item_id, e),
+        };
+
+        let text = match item.kind {
+            ItemKind::Heal { hp } => {
+                self.items.remove(index);
+                let hp_before = stats.hp;
+                stats.heal(hp);
+                let recovered = if stats.hp >= stats.max_hp {
+                    "* Your HP was maxed out.".to_string()
+                } else {
+                    format!("* You recovered {} HP!", stats.hp - hp_before)
+                };
+                format!("* You ate the {}.\n{}", item.name, recovered)
+            }
+            ItemKind::Weapon { atk } => {
+                let worn = stats.weapon.replace(Equipment {
+                    item: item.id.clone(),
+                    bonus: atk,
+                });
+                self.swap_out(index, worn);
+                format!("* You equipped the {}.", item.name)
+            }
+            ItemKind::Armor { def } => {
+                let worn = stats.armor.replace(Equipment {
+                    item: item.id.clone(),
+                    bonus: def,
+                });
+                self.swap_out(index, worn);
+                format!("* You equipped the {}.", item.name)
+            }
+            ItemKind::Key => "* You can't use that here.".to_string(),
+        };
+        item.use_text.unwrap_or(text)
+    }
+
+    pub fn drop_item(&mut self, index: usize) -> String {
+        let Some(item_id) = self.items.get(index) else {
+            return "* You have no items.".to_string();
+        };
+        match Item::load(item_id) {
+            Ok(item) if !item.is_droppable() => {
+                format!("* You can't throw away the {}.", item.name)
+            }
+            Ok(item) => {
+                self.items.remove(index);
+                format!("* The {} was thrown away.", item.name)
+            }
+            Err(_) => {
+                let item_id = self.items.remove(index);
+                format!("* The {} was thrown away.", item_id)
+            }
+        }
+    }
+
+    pub fn item_info(&self, index: usize) -> String {
+        match self.items.get(index).map(|id| Item::load(id)) {
+            Some(Ok(item)) => item.info_text(),
+            Some(Err(e)) => format!("* {}", e),
+            None => "* You have no items.".to_string(),
+        }
+    }
+
+    // the newly worn item leaves its slot to whatever was worn before
+    fn swap_out(&mut self, index: usize, worn: Option<Equipment>) {
+        match worn {
+            Some(worn) => self.items[index] = worn.item,
+            None => {
+                self.items.remove(index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn food_heals_up_to_max_hp_and_is_used_up() {
+        let mut inventory = Inventory::new();
+        inventory.add("bandage");
+        inventory.add("butterscotch_pie");
+        let mut stats = PlayerStats {
+            hp: 5,
+            ..PlayerStats::default()
+        };
+
+        let text = inventory.use_item(0, &mut stats);
+        assert_eq!(stats.hp, 15);
+        assert!(text.ends_with("* You recovered 10 HP!"), "{}", text);
+        assert_eq!(inventory.ids(), ["butterscotch_pie"]);
+
+        let text = inventory.use_item(0, &mut stats);
+        assert_eq!(stats.hp, stats.max_hp);
+        assert!(text.ends_with("* Your HP was maxed out."), "{}", text);
+        assert!(inventory.is_empty());
+    }
+
+    #[test]
+    fn a_full_inventory_turns_down_a_pickup() {
+        let mut inventory = Inventory::new();
+        for _ in 0..INVENTORY_CAPACITY {
+            assert!(inventory.add("stick"));
+        }
+        assert!(inventory.is_full());
+        assert!(!inventory.add("bandage"));
+        assert_eq!(inventory.len(), INVENTORY_CAPACITY);
+        assert!(!inventory.contains("bandage"));
+    }
+
+    #[test]
+    fn an_unknown_item_stays_put_and_says_why() {
+        assert!(Item::load("not_an_item").is_err());
+
+        let mut inventory = Inventory::new();
+        inventory.add("not_an_item");
+        let mut stats = PlayerStats::default();
+        let text = inventory.use_item(0, &mut stats);
+        assert!(text.starts_with("* Couldn't use not_an_item"), "{}", text);
+        assert_eq!(inventory.ids(), ["not_an_item"]);
+        assert_eq!(inventory.names(), ["not_an_item"]);
+        assert_eq!(stats, PlayerStats::default());
+    }
+}
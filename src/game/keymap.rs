@@ -0,0 +1,346 @@
+use super::save::data_dir;
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything a key can do. Gameplay and the map editor only ever see these, which key
+/// does what is up to the `Keymap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Confirm,
+    Cancel,
+    Menu,
+    // held in the overworld to quit
+    Quit,
+    QuickSave,
+    SaveAndQuit,
+    DarkenScreen,
+    BrightenScreen,
+    DumpState,
+    DebugToggle,
+    DebugUndoWall,
+    DebugRedoWall,
+    // held, toggles the wall under the player every frame
+    DebugToggleWall,
+    DebugSetSpawn,
+    DebugMapKind,
+    DebugSelectBox,
+    DebugTeleportBox,
+    DebugPlaceSprite,
+    DebugNewMap,
+    // held
+    DebugShowCollision,
+}
+
+/// `"Up"`, `"Enter"`, `"F3"`, `"w"`... Letters are case insensitive.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let key = match name.to_ascii_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "tab" => KeyCode::Tab,
+        "space" => KeyCode::Char(' '),
+        lower => {
+            if let Some(number) = lower.strip_prefix('f')
+                && let Ok(number) = number.parse()
+            {
+                KeyCode::F(number)
+            } else {
+                let mut chars = lower.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        }
+    };
+    Some(key)
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(number) => format!("F{}", number),
+        KeyCode::Esc => "Esc".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+// 'Z' and 'z' are the same key as far as bindings go
+fn normalize(key: KeyCode) -> KeyCode {
+    match key {
+        KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+        other => other,
+    }
+}
+
+/// One key bound to more than one action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: KeyCode,
+    pub actions: Vec<Action>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let actions: Vec<_> = self.actions.iter().map(|a| format!("{:?}", a)).collect();
+        write!(
+            f,
+            "'{}' is bound to {}",
+            key_name(self.key),
+            actions.join(" and ")
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum KeymapError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    UnknownKey { action: Action, key: String },
+    Conflicts(Vec<Conflict>),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeymapError::Io(e) => write!(f, "could not read keymap: {}", e),
+            KeymapError::Parse(e) => write!(f, "keymap is not valid: {}", e),
+            KeymapError::UnknownKey { action, key } => {
+                write!(f, "keymap binds {:?} to unknown key {:?}", action, key)
+            }
+            KeymapError::Conflicts(conflicts) => {
+                let conflicts: Vec<_> = conflicts.iter().map(Conflict::to_string).collect();
+                write!(f, "keymap has conflicts: {}", conflicts.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+/// Which keys trigger which action, read from `keymap.json` next to the saves. Actions
+/// the file leaves out keep their default keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Action::*;
+        let bindings = [
+            (MoveUp, vec![KeyCode::Up, KeyCode::Char('w')]),
+            (MoveDown, vec![KeyCode::Down, KeyCode::Char('s')]),
+            (MoveLeft, vec![KeyCode::Left, KeyCode::Char('a')]),
+            (MoveRight, vec![KeyCode::Right, KeyCode::Char('d')]),
+            (Confirm, vec![KeyCode::Enter, KeyCode::Char('z')]),
+            (Cancel, vec![KeyCode::Backspace, KeyCode::Char('x')]),
+            (Menu, vec![KeyCode::Char('c')]),
+            (Quit, vec![KeyCode::Esc]),
+            (QuickSave, vec![KeyCode::Char('p')]),
+            (SaveAndQuit, vec![KeyCode::Char('q')]),
+            (DarkenScreen, vec![KeyCode::Char('+')]),
+            (BrightenScreen, vec![KeyCode::Char('-')]),
+            (DumpState, vec![KeyCode::Char('m')]),
+            (DebugToggle, vec![KeyCode::F(2)]),
+            (DebugUndoWall, vec![KeyCode::Char('r')]),
+            (DebugRedoWall, vec![KeyCode::Char('y')]),
+            (DebugToggleWall, vec![KeyCode::Char('f')]),
+            (DebugSetSpawn, vec![KeyCode::Char('h')]),
+            (DebugMapKind, vec![KeyCode::Char('k')]),
+            (DebugSelectBox, vec![KeyCode::Char('o')]),
+            (DebugTeleportBox, vec![KeyCode::Char('t')]),
+            (DebugPlaceSprite, vec![KeyCode::Char('g')]),
+            (DebugNewMap, vec![KeyCode::F(3)]),
+            (DebugShowCollision, vec![KeyCode::Char('b')]),
+        ];
+        Keymap {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn path() -> PathBuf {
+        data_dir().join("keymap.json")
+    }
+
+    /// The defaults when there's no file yet. A file that can't be read, names a key we
+    /// don't know or binds one key twice is an error rather than something to guess about.
+    pub fn load() -> Result<Self, KeymapError> {
+        match std::fs::read_to_string(Self::path()) {
+            Ok(contents) => Self::from_json(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(KeymapError::Io(e)),
+        }
+    }
+
+    /// e.g. `{ "MoveUp": ["Up", "i"], "DebugSetSpawn": ["F5"] }`
+    pub fn from_json(json: &str) -> Result<Self, KeymapError> {
+        Self::from_names(serde_json::from_str(json).map_err(KeymapError::Parse)?)
+    }
+
+    /// The defaults, with every action in `names` bound to those keys instead.
+    pub fn from_names(names: BTreeMap<Action, Vec<String>>) -> Result<Self, KeymapError> {
+        let mut keymap = Self::default();
+        for (action, names) in names {
+            let keys = names
+                .into_iter()
+                .map(|name| {
+                    parse_key(&name)
+                        .map(normalize)
+                        .ok_or(KeymapError::UnknownKey { action, key: name })
+                })
+                .collect::<Result<_, _>>()?;
+            keymap.bindings.insert(action, keys);
+        }
+        let conflicts = keymap.conflicts();
+        if !conflicts.is_empty() {
+            return Err(KeymapError::Conflicts(conflicts));
+        }
+        Ok(keymap)
+    }
+
+    /// Every binding by key name, what `keymap.json` holds.
+    pub fn to_names(&self) -> BTreeMap<Action, Vec<String>> {
+        self.bindings
+            .iter()
+            .map(|(action, keys)| (*action, keys.iter().copied().map(key_name).collect()))
+            .collect()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file = self.to_names();
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&file)?)
+    }
+
+    /// What `key` does, if anything.
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        let key = normalize(key);
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every key that more than one action wants.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut actions_by_key: Vec<(KeyCode, Vec<Action>)> = Vec::new();
+        for (action, keys) in &self.bindings {
+            for key in keys {
+                match actions_by_key.iter_mut().find(|(k, _)| k == key) {
+                    Some((_, actions)) => actions.push(*action),
+                    None => actions_by_key.push((*key, vec![*action])),
+                }
+            }
+        }
+        actions_by_key
+            .into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|(key, actions)| Conflict { key, actions })
+            .collect()
+    }
+
+    /// The letter shown for `action` in the settings menu, arrows and Enter don't count.
+    pub fn letter(&self, action: Action) -> Option<char> {
+        self.keys(action).iter().find_map(|key| match key {
+            KeyCode::Char(c) => Some(*c),
+            _ => None,
+        })
+    }
+
+    /// Binds `action` to the letter `c` in place of its old one. Whatever had `c` before
+    /// gets the old letter so no two actions end up sharing a key.
+    pub fn rebind(&mut self, action: Action, c: char) {
+        let new = KeyCode::Char(c.to_ascii_lowercase());
+        let old = self.letter(action).map(KeyCode::Char);
+        for keys in self.bindings.values_mut() {
+            if let Some(key) = keys.iter_mut().find(|key| **key == new) {
+                match old {
+                    Some(old) => *key = old,
+                    None => keys.retain(|key| *key != new),
+                }
+            }
+        }
+        let keys = self.bindings.entry(action).or_default();
+        match keys.iter_mut().find(|key| Some(**key) == old) {
+            Some(key) => *key = new,
+            None => keys.push(new),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keymap_has_no_conflicts() {
+        let keymap = Keymap::default();
+        assert!(keymap.conflicts().is_empty());
+        assert_eq!(keymap.action(KeyCode::Char('S')), Some(Action::MoveDown));
+        assert_eq!(
+            keymap.action(KeyCode::Char('h')),
+            Some(Action::DebugSetSpawn)
+        );
+    }
+
+    #[test]
+    fn file_overrides_only_what_it_names() {
+        let keymap =
+            Keymap::from_json(r#"{ "MoveUp": ["Up", "I"], "DebugNewMap": ["F5"] }"#).unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('i')), Some(Action::MoveUp));
+        assert_eq!(keymap.action(KeyCode::Char('w')), None);
+        assert_eq!(keymap.action(KeyCode::F(5)), Some(Action::DebugNewMap));
+        assert_eq!(keymap.action(KeyCode::Char('z')), Some(Action::Confirm));
+    }
+
+    #[test]
+    fn conflicting_and_unknown_keys_are_rejected() {
+        match Keymap::from_json(r#"{ "DebugSetSpawn": ["s"] }"#) {
+            Err(KeymapError::Conflicts(conflicts)) => assert_eq!(
+                conflicts,
+                vec![Conflict {
+                    key: KeyCode::Char('s'),
+                    actions: vec![Action::MoveDown, Action::DebugSetSpawn],
+                }]
+            ),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        assert!(matches!(
+            Keymap::from_json(r#"{ "Menu": ["PageUp"] }"#),
+            Err(KeymapError::UnknownKey { .. })
+        ));
+    }
+
+    #[test]
+    fn rebinding_a_taken_letter_swaps_the_two() {
+        let mut keymap = Keymap::default();
+        keymap.rebind(Action::MoveUp, 'S');
+        assert_eq!(keymap.action(KeyCode::Char('s')), Some(Action::MoveUp));
+        assert_eq!(keymap.action(KeyCode::Char('w')), Some(Action::MoveDown));
+        assert_eq!(keymap.action(KeyCode::Up), Some(Action::MoveUp));
+        assert!(keymap.conflicts().is_empty());
+    }
+}
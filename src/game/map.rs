@@ -1,7 +1,22 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
-use crate::load_map_asset_str;
+use std::sync::{Arc, OnceLock};
+
+use crate::assets::{AssetError, Sprite};
+
+use crate::game::flags::{FlagValue, Flags};
+
+fn default_increment() -> i64 {
+    1
+}
+
+fn default_fade_ms() -> u64 {
+    500
+}
+
+fn default_enemy_id() -> String {
+    crate::game::enemy::DEFAULT_ENEMY_ID.to_string()
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlacedSprite {
@@ -11,20 +26,56 @@ pub struct PlacedSprite {
     pub width: u32,
     pub height: u32,
     pub ansi_content: String,
+    // makes the sprite an NPC, Enter next to it plays this conversation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation: Option<String>,
+    // `ansi_content` parsed, on the first draw
+    #[serde(skip)]
+    pub parsed: OnceLock<Arc<Sprite>>,
 }
 
+impl PlacedSprite {
+    pub fn sprite(&self) -> Result<&Arc<Sprite>, AssetError> {
+        parse_once(&self.parsed, &self.ansi_content, || {
+            format!("placed sprite {}", self.id)
+        })
+    }
 
+    pub fn to_rect(&self) -> ratatui::layout::Rect {
+        ratatui::layout::Rect::new(
+            self.x as u16,
+            self.y as u16,
+            self.width as u16,
+            self.height as u16,
+        )
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BattleZone {
     pub id: u32,
+    #[serde(default = "default_enemy_id")]
+    pub enemy_id: String,
     pub x: u32,
     pub y: u32,
     pub width: u32,
     pub height: u32,
+    // winning sets `killed_<enemy_id>`, sparing sets `spared_<enemy_id>`, so
+    // `unless_flag` is enough for a zone that only fights once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires_flag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unless_flag: Option<String>,
+    // played after the enemy is beaten or spared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation: Option<String>,
 }
 
 impl BattleZone {
+    pub fn is_active(&self, flags: &Flags) -> bool {
+        flags.allows(self.requires_flag.as_deref(), self.unless_flag.as_deref())
+    }
+
     pub fn to_rect(&self) -> ratatui::layout::Rect {
         ratatui::layout::Rect::new(
             self.x as u16,
@@ -35,9 +86,10 @@ impl BattleZone {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
 pub enum MapKind {
     #[serde(rename = "walls")]
+    #[default]
     Walls,
     #[serde(rename = "objects")]
     Objects,
@@ -63,12 +115,6 @@ impl MapKind {
     }
 }
 
-impl Default for MapKind {
-    fn default() -> Self {
-        MapKind::Walls
-    }
-}
-
 #[derive(Debug, Deserialize, Serialize)]
 pub struct MapData {
     pub map_name: String,
@@ -82,6 +128,9 @@ pub struct MapData {
     pub kind: MapKind,
     #[serde(default)]
     pub battle_zones: Vec<BattleZone>,
+    // loops assets/music/<music>.mp3 while the player is on this map
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub music: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -92,6 +141,73 @@ pub enum Event {
         dest_x: u32,
         dest_y: u32,
     },
+    // heals the player and opens the save slot picker, like a SAVE star
+    SavePoint,
+    SetFlag {
+        name: String,
+        value: FlagValue,
+    },
+    IncrementFlag {
+        name: String,
+        #[serde(default = "default_increment")]
+        by: i64,
+    },
+    // plays the conversation in assets/dialogues/<id>.json, the script waits until it's been read
+    ShowDialogue {
+        id: String,
+    },
+    // waits for the battle to end
+    StartBattle {
+        enemy_id: String,
+    },
+    GiveItem {
+        item: String,
+    },
+    // assets/sound/<sound>.mp3
+    PlaySound {
+        sound: String,
+    },
+    Wait {
+        ms: u64,
+    },
+    FadeOut {
+        #[serde(default = "default_fade_ms")]
+        ms: u64,
+    },
+    FadeIn {
+        #[serde(default = "default_fade_ms")]
+        ms: u64,
+    },
+    // walks the player to (x, y) on the current map, through walls if it has to
+    MovePlayer {
+        x: u32,
+        y: u32,
+    },
+    // loops assets/music/<track>.mp3 until the player changes maps, no track stops the music
+    ChangeMusic {
+        #[serde(default)]
+        track: Option<String>,
+    },
+    // runs `then` when the flag is set (or at least `at_least`), `else` otherwise
+    If {
+        flag: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        at_least: Option<i64>,
+        #[serde(default)]
+        then: Vec<Event>,
+        #[serde(default, rename = "else")]
+        otherwise: Vec<Event>,
+    },
+    // fades out to the thank you screen
+    EndGame,
+}
+
+impl Event {
+    /// Teleports fire by walking into their box, everything else waits for the player
+    /// to interact with it.
+    pub fn is_teleport(&self) -> bool {
+        matches!(self, Event::TeleportPlayer { .. })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -104,9 +220,41 @@ pub struct SelectObjectBox {
     pub messages: Vec<String>,
     #[serde(default)]
     pub events: Vec<Event>,
+    // a box whose conditions aren't met can't be talked to or walked into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires_flag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unless_flag: Option<String>,
+    // played before the box's other events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation: Option<String>,
 }
 
 impl SelectObjectBox {
+    pub fn is_active(&self, flags: &Flags) -> bool {
+        flags.allows(self.requires_flag.as_deref(), self.unless_flag.as_deref())
+    }
+
+    /// Whether pressing Enter on the box should run its events.
+    pub fn has_script(&self) -> bool {
+        self.conversation.is_some() || self.events.iter().any(|event| !event.is_teleport())
+    }
+
+    /// The events Enter runs, starting with the box's conversation if it has one.
+    pub fn script_events(&self) -> Vec<Event> {
+        let conversation = self
+            .conversation
+            .iter()
+            .map(|id| Event::ShowDialogue { id: id.clone() });
+        conversation.chain(self.events.iter().cloned()).collect()
+    }
+
+    /// Where walking into the box takes the player, wherever the teleport sits among
+    /// its events.
+    pub fn teleport(&self) -> Option<&Event> {
+        self.events.iter().find(|event| event.is_teleport())
+    }
+
     pub fn to_rect(&self) -> ratatui::layout::Rect {
         ratatui::layout::Rect::new(
             self.x as u16,
@@ -117,8 +265,6 @@ impl SelectObjectBox {
     }
 }
 
-use ansi_to_tui::IntoText;
-
 #[derive(Debug, Clone, Default)]
 pub struct Map {
     pub name: String,
@@ -129,31 +275,48 @@ pub struct Map {
     pub placed_sprites: Vec<PlacedSprite>,
     pub kind: MapKind,
     pub battle_zones: Vec<BattleZone>,
+    pub music: Option<String>,
     pub width: u16,
     pub height: u16,
+    // `ansi_sprite` parsed, it's drawn every frame
+    pub parsed: OnceLock<Arc<Sprite>>,
+}
+
+// art that doesn't parse is tried again next time, and reported every time
+fn parse_once<'a>(
+    parsed: &'a OnceLock<Arc<Sprite>>,
+    content: &str,
+    name: impl FnOnce() -> String,
+) -> Result<&'a Arc<Sprite>, AssetError> {
+    if let Some(sprite) = parsed.get() {
+        return Ok(sprite);
+    }
+    let sprite = Sprite::parse(content).map_err(|e| AssetError::Parse {
+        path: name(),
+        message: e.to_string(),
+    })?;
+    Ok(parsed.get_or_init(|| Arc::new(sprite)))
 }
 
 impl Map {
-    pub fn load(map_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let data_content = load_map_asset_str!(map_name, "data.json");
-        if data_content.is_empty() {
-            return Err(format!("Map data for {} not found", map_name).into());
-        }
-        let map_data: MapData = serde_json::from_str(data_content)?;
+    pub fn sprite(&self) -> Result<&Arc<Sprite>, AssetError> {
+        parse_once(&self.parsed, &self.ansi_sprite, || {
+            format!("map/{}/sprite.ans", self.name)
+        })
+    }
 
-        let ansi_sprite_content = load_map_asset_str!(map_name, "sprite.ans");
-        if ansi_sprite_content.is_empty() {
-            return Err(format!("Map sprite for {} not found", map_name).into());
-        }
+    pub fn load(map_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let store = crate::assets::store();
+        let data_content = store.load_str(&format!("map/{}/data.json", map_name))?;
+        let map_data: MapData = serde_json::from_str(&data_content)?;
 
-        let ansi_sprite = if cfg!(windows) {
-            ansi_sprite_content.replace("\r\n", "\n")
-        } else {
-            ansi_sprite_content.to_string()
-        };
+        let ansi_sprite = store
+            .load_str(&format!("map/{}/sprite.ans", map_name))?
+            .into_owned();
 
         // map dimension
-        let map_text_for_dimensions = ansi_sprite.as_bytes().into_text().unwrap();
+        let sprite = Sprite::parse(&ansi_sprite)?;
+        let map_text_for_dimensions = &sprite.text;
 
         let height = {
             let mut actual_height = 0;
@@ -182,12 +345,24 @@ impl Map {
             placed_sprites: map_data.placed_sprites,
             kind: map_data.kind,
             battle_zones: map_data.battle_zones,
+            music: map_data.music,
 
             width,
             height,
+            parsed: OnceLock::from(Arc::new(sprite)),
         })
     }
 
+    /// Names of every map that has a data.json, embedded or on disk.
+    pub fn available_names() -> Vec<String> {
+        let store = crate::assets::store();
+        store
+            .list("map")
+            .into_iter()
+            .filter(|name| store.exists(&format!("map/{}/data.json", name)))
+            .collect()
+    }
+
     pub fn toggle_wall(&mut self, x: u32, y: u32) {
         let pos = (x, y);
         if let Some(index) = self.walls.iter().position(|&p| p == pos) {
@@ -198,7 +373,10 @@ impl Map {
     }
 
     pub fn save_data(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let base_path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/map")).join(&self.name);
+        let base_path = crate::assets::store()
+            .writable_root()
+            .join("map")
+            .join(&self.name);
         let data_path = base_path.join("data.json");
 
         let map_data = MapData {
@@ -209,6 +387,7 @@ impl Map {
             placed_sprites: self.placed_sprites.clone(),
             kind: self.kind.clone(),
             battle_zones: self.battle_zones.clone(),
+            music: self.music.clone(),
         };
 
         let serialized = serde_json::to_string_pretty(&map_data)?;
@@ -224,10 +403,11 @@ impl Map {
         self.placed_sprites.push(placed_sprite);
     }
 
-    
-
     pub fn create_new(map_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let base_path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/map")).join(map_name);
+        let base_path = crate::assets::store()
+            .writable_root()
+            .join("map")
+            .join(map_name);
         fs::create_dir_all(&base_path)?;
 
         let data_path = base_path.join("data.json");
@@ -241,6 +421,7 @@ impl Map {
             placed_sprites: vec![],
             kind: MapKind::Empty,
             battle_zones: vec![],
+            music: None,
         };
 
         let serialized = serde_json::to_string_pretty(&map_data)?;
@@ -257,8 +438,54 @@ impl Map {
             placed_sprites: vec![],
             kind: MapKind::Empty,
             battle_zones: vec![],
+            music: None,
             width: 0,
             height: 0,
+            parsed: OnceLock::new(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_box_teleports_even_when_other_events_come_first() {
+        let teleport = Event::TeleportPlayer {
+            map_row: 0,
+            map_col: 1,
+            dest_x: 50,
+            dest_y: 20,
+        };
+        let select_box = SelectObjectBox {
+            id: 1,
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 2,
+            messages: Vec::new(),
+            events: vec![
+                Event::SetFlag {
+                    name: "visited".to_string(),
+                    value: FlagValue::Bool(true),
+                },
+                teleport.clone(),
+            ],
+            requires_flag: None,
+            unless_flag: None,
+            conversation: None,
+        };
+        assert!(select_box.has_script());
+        assert!(matches!(
+            select_box.teleport(),
+            Some(Event::TeleportPlayer { map_col: 1, .. })
+        ));
+
+        let talk_only = SelectObjectBox {
+            events: vec![Event::SavePoint],
+            ..select_box
+        };
+        assert!(talk_only.teleport().is_none());
+    }
+}
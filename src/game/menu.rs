@@ -0,0 +1,68 @@
+pub const MENU_OPTIONS: [&str; 4] = ["ITEM", "STAT", "CELL", "SETTINGS"];
+pub const ITEM_ACTIONS: [&str; 3] = ["USE", "INFO", "DROP"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuPage {
+    // ITEM / STAT / CELL
+    Main,
+    Items,
+    // USE / INFO / DROP under the picked item
+    ItemActions,
+    Stat,
+}
+
+/// The overworld menu opened with C. The world is paused while it's open.
+#[derive(Debug, Clone)]
+pub struct OverworldMenu {
+    pub page: MenuPage,
+    pub selected: usize,
+    pub item_index: usize,
+    pub action_index: usize,
+    // names of the carried items, refreshed whenever the inventory changes
+    pub item_names: Vec<String>,
+    // what the last choice had to say, the next key press closes the menu
+    pub text: Option<String>,
+}
+
+impl OverworldMenu {
+    pub fn new(item_names: Vec<String>) -> Self {
+        OverworldMenu {
+            page: MenuPage::Main,
+            selected: 0,
+            item_index: 0,
+            action_index: 0,
+            item_names,
+            text: None,
+        }
+    }
+
+    /// Up/Down on whichever list the current page shows.
+    pub fn move_cursor(&mut self, down: bool) {
+        let (index, len) = match self.page {
+            MenuPage::Main => (&mut self.selected, MENU_OPTIONS.len()),
+            MenuPage::Items => (&mut self.item_index, self.item_names.len()),
+            MenuPage::ItemActions => (&mut self.action_index, ITEM_ACTIONS.len()),
+            MenuPage::Stat => return,
+        };
+        if down {
+            if *index + 1 < len {
+                *index += 1;
+            }
+        } else {
+            *index = index.saturating_sub(1);
+        }
+    }
+
+    /// X goes up a page, false when there is no page left and the menu should close.
+    pub fn back(&mut self) -> bool {
+        if self.text.is_some() {
+            return false;
+        }
+        self.page = match self.page {
+            MenuPage::Main => return false,
+            MenuPage::Items | MenuPage::Stat => MenuPage::Main,
+            MenuPage::ItemActions => MenuPage::Items,
+        };
+        true
+    }
+}
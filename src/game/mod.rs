@@ -1,8 +1,20 @@
+pub mod attack;
+pub mod battle;
+pub mod clock;
 pub mod config;
-pub mod state;
+pub mod deltarune;
+pub mod dialogue;
+pub mod enemy;
+pub mod flags;
+pub mod item;
+pub mod keymap;
 pub mod map;
+pub mod menu;
 pub mod player;
+pub mod rng;
+pub mod save;
+pub mod script;
+pub mod settings;
+pub mod state;
+pub mod typewriter;
 pub mod utils;
-pub mod deltarune;
-pub mod attack;
-pub mod dialogue;
\ No newline at end of file
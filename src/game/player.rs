@@ -1,14 +1,16 @@
-use std::borrow::Cow;
 use super::config::{
-    ANIMATION_FRAME_DURATION, PLAYER_HORIZONTAL_SPEED, PLAYER_SPEED, DEBUG_MOVEMENT_FRAME_INTERVAL,
-    PLAYER_INTERACTION_BOX_HEIGHT, PLAYER_INTERACTION_BOX_WIDTH,
+    ANIMATION_FRAME_DURATION, DEBUG_MOVEMENT_FRAME_INTERVAL, LV_EXP_THRESHOLDS, PLAYER_BASE_ATK,
+    PLAYER_BASE_DEF, PLAYER_HORIZONTAL_SPEED, PLAYER_INTERACTION_BOX_HEIGHT,
+    PLAYER_INTERACTION_BOX_WIDTH, PLAYER_MAX_HP, PLAYER_SPEED,
 };
+use super::keymap::Action;
 use super::map::Map;
-use ansi_to_tui::IntoText;
-use crossterm::event::KeyCode;
-use ratatui::text::Text;
+use super::typewriter::Typewriter;
+use crate::assets::Sprite;
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 const PLAYER_COLLISION_WIDTH: u16 = 21;
@@ -34,6 +36,98 @@ pub enum PlayerDirection {
     BackRight,
 }
 
+/// A worn weapon or armor, with its AT/DF bonus copied from the item file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub item: String,
+    pub bonus: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub hp: i32,
+    pub max_hp: i32,
+    pub lv: u32,
+    pub exp: u32,
+    // base values, equipment comes on top, see total_atk / total_def
+    pub atk: i32,
+    pub def: i32,
+    pub gold: u32,
+    #[serde(default)]
+    pub weapon: Option<Equipment>,
+    #[serde(default)]
+    pub armor: Option<Equipment>,
+}
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        PlayerStats {
+            hp: PLAYER_MAX_HP,
+            max_hp: PLAYER_MAX_HP,
+            lv: 1,
+            exp: 0,
+            atk: PLAYER_BASE_ATK,
+            def: PLAYER_BASE_DEF,
+            gold: 0,
+            weapon: None,
+            armor: None,
+        }
+    }
+}
+
+impl PlayerStats {
+    pub fn is_dead(&self) -> bool {
+        self.hp <= 0
+    }
+
+    pub fn total_atk(&self) -> i32 {
+        self.atk + self.weapon.as_ref().map_or(0, |weapon| weapon.bonus)
+    }
+
+    pub fn total_def(&self) -> i32 {
+        self.def + self.armor.as_ref().map_or(0, |armor| armor.bonus)
+    }
+
+    /// Every 5 points of DEF shave one point off a hit, but a hit always hurts.
+    pub fn take_damage(&mut self, damage: i32) {
+        if damage <= 0 {
+            return;
+        }
+        let damage = (damage - self.total_def() / 5).max(1);
+        self.hp = (self.hp - damage).clamp(0, self.max_hp);
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.hp = (self.hp + amount).clamp(0, self.max_hp);
+    }
+
+    /// Adds EXP and levels up as many times as it covers. Returns true on a level up.
+    pub fn gain_exp(&mut self, amount: u32) -> bool {
+        self.exp = self.exp.saturating_add(amount);
+        let mut leveled_up = false;
+        while let Some(&needed) = LV_EXP_THRESHOLDS.get(self.lv as usize - 1)
+            && self.exp >= needed
+        {
+            self.lv += 1;
+            self.max_hp += 4;
+            self.hp += 4;
+            self.atk += 2;
+            if self.lv.is_multiple_of(4) {
+                self.def += 1;
+            }
+            leveled_up = true;
+        }
+        leveled_up
+    }
+
+    /// EXP still missing for the next LV, `None` at the max level.
+    pub fn exp_to_next_level(&self) -> Option<u32> {
+        LV_EXP_THRESHOLDS
+            .get(self.lv as usize - 1)
+            .map(|needed| needed.saturating_sub(self.exp))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub x: f32,
@@ -48,6 +142,11 @@ pub struct Player {
     #[serde(skip, default = "default_walking_stop_delay")]
     pub walking_stop_delay: Duration,
     pub movement_counter: u8,
+    #[serde(default)]
+    pub stats: PlayerStats,
+    // the frame `sprite_path` named last time and its sprite, asked for many times a frame
+    #[serde(skip)]
+    sprite_cache: RefCell<Option<(&'static str, Arc<Sprite>)>>,
 }
 
 pub struct PlayerUpdateContext<'a> {
@@ -57,106 +156,92 @@ pub struct PlayerUpdateContext<'a> {
     pub debug_mode: bool,
     pub message: &'a mut String,
     pub show_message: &'a mut bool,
-    pub animated_message_content: &'a mut String,
-    pub message_animation_start_time: &'a mut Instant,
+    pub message_typewriter: &'a mut Typewriter,
     pub wall_history: &'a mut Vec<Vec<(u32, u32)>>,
     pub history_index: &'a mut usize,
     pub is_drawing_select_box: bool,
+    // vertical speed from the settings, sideways is twice as fast like PLAYER_HORIZONTAL_SPEED
+    pub walk_speed: f32,
+    // from the game's clock, see `crate::game::clock`
+    pub now: Instant,
     pub block_player_movement_on_message: &'a mut bool,
 }
 
 impl Player {
-    pub fn new(x: f32, y: f32) -> Self {
+    /// `now` comes from the game's clock, the walk animation is timed from it.
+    pub fn new(x: f32, y: f32, now: Instant) -> Self {
         Player {
             x,
             y,
             direction: PlayerDirection::Front,
             animation_frame: 0,
             is_walking: false,
-            animation_timer: Instant::now(),
-            walking_stop_timer: Instant::now(),
+            animation_timer: now,
+            walking_stop_timer: now,
             walking_stop_delay: Duration::from_millis(100),
             movement_counter: 0,
+            stats: PlayerStats::default(),
+            sprite_cache: RefCell::new(None),
         }
     }
 
-    pub fn get_sprite_content(&self) -> (Text<'static>, u16, u16) {
-        let content = self.get_sprite_content_from_binary();
-        let text = content.as_bytes().into_text().unwrap();
-
-        let height = text.lines.len() as u16;
-        let mut max_width = 0;
-        for line in text.lines.iter() {
-            let line_width = line.width() as u16;
-            if line_width > max_width {
-                max_width = line_width;
-            }
+    /// The frame to draw right now. Only goes to the asset store when the frame changes, a
+    /// frame that can't be loaded is drawn as its error.
+    pub fn sprite(&self) -> Arc<Sprite> {
+        let path = self.sprite_path();
+        let mut cache = self.sprite_cache.borrow_mut();
+        if let Some((cached_path, sprite)) = cache.as_ref()
+            && *cached_path == path
+        {
+            return Arc::clone(sprite);
         }
-        (text, max_width, height)
+        let sprite = crate::assets::store()
+            .load_sprite(path)
+            .unwrap_or_else(|e| Arc::new(Sprite::error(&e)));
+        *cache = Some((path, Arc::clone(&sprite)));
+        sprite
     }
 
-    pub fn get_sprite_content_from_binary(&self) -> Cow<'static, str> {
-        let content = match self.direction {
-            PlayerDirection::Front => {
-                if self.is_walking {
-                    match self.animation_frame {
-                        0 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_front.ans")),
-                        1 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/walk/frisk_walk_front_1.ans")),
-                        2 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_front.ans")),
-                        3 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/walk/frisk_walk_front_2.ans")),
-                        _ => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_front.ans")),
-                    }
-                } else {
-                    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_front.ans"))
-                }
-            }
-            PlayerDirection::Back => {
-                if self.is_walking {
-                    match self.animation_frame {
-                        0 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_back.ans")),
-                        1 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/walk/frisk_walk_back_1.ans")),
-                        2 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_back.ans")),
-                        3 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/walk/frisk_walk_back_2.ans")),
-                        _ => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_back.ans")),
-                    }
-                } else {
-                    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_back.ans"))
-                }
-            }
+    pub fn sprite_size(&self) -> (u16, u16) {
+        let sprite = self.sprite();
+        (sprite.width, sprite.height)
+    }
+
+    pub fn sprite_path(&self) -> &'static str {
+        let walking_frame = if self.is_walking {
+            self.animation_frame
+        } else {
+            0
+        };
+        match self.direction {
+            PlayerDirection::Front => match walking_frame {
+                1 => "sprites/frisk/walk/frisk_walk_front_1.ans",
+                3 => "sprites/frisk/walk/frisk_walk_front_2.ans",
+                _ => "sprites/frisk/idle/frisk_idle_front.ans",
+            },
+            PlayerDirection::Back => match walking_frame {
+                1 => "sprites/frisk/walk/frisk_walk_back_1.ans",
+                3 => "sprites/frisk/walk/frisk_walk_back_2.ans",
+                _ => "sprites/frisk/idle/frisk_idle_back.ans",
+            },
             PlayerDirection::Left | PlayerDirection::FrontLeft | PlayerDirection::BackLeft => {
-                if self.is_walking {
-                    match self.animation_frame {
-                        0 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_left.ans")),
-                        1 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/walk/frisk_walk_left.ans")),
-                        _ => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_left.ans")),
-                    }
-                } else {
-                    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_left.ans"))
+                match walking_frame {
+                    1 => "sprites/frisk/walk/frisk_walk_left.ans",
+                    _ => "sprites/frisk/idle/frisk_idle_left.ans",
                 }
             }
             PlayerDirection::Right | PlayerDirection::FrontRight | PlayerDirection::BackRight => {
-                if self.is_walking {
-                    match self.animation_frame {
-                        0 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_right.ans")),
-                        1 => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/walk/frisk_walk_right.ans")),
-                        _ => include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_right.ans")),
-                    }
-                } else {
-                    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sprites/frisk/idle/frisk_idle_right.ans"))
+                match walking_frame {
+                    1 => "sprites/frisk/walk/frisk_walk_right.ans",
+                    _ => "sprites/frisk/idle/frisk_idle_right.ans",
                 }
             }
-        };
-
-        if cfg!(windows) {
-            Cow::Owned(content.replace("\r\n", "\n"))
-        } else {
-            Cow::Borrowed(content)
         }
     }
 
-    pub fn update_animation(&mut self, animation_frame_duration: Duration) {
+    pub fn update_animation(&mut self, animation_frame_duration: Duration, now: Instant) {
         if self.is_walking {
-            if self.animation_timer.elapsed() >= animation_frame_duration {
+            if now.saturating_duration_since(self.animation_timer) >= animation_frame_duration {
                 match self.direction {
                     PlayerDirection::Front | PlayerDirection::Back => {
                         self.animation_frame = (self.animation_frame + 1) % 4;
@@ -170,16 +255,49 @@ impl Player {
                         self.animation_frame = (self.animation_frame + 1) % 2;
                     }
                 }
-                self.animation_timer = Instant::now();
+                self.animation_timer = now;
             }
         } else {
             self.animation_frame = 0;
-            self.animation_timer = Instant::now();
+            self.animation_timer = now;
         }
     }
 
+    /// Cutscene walking: heads for (x, y) at the usual speed, ignoring walls.
+    /// Returns true once the player is standing there.
+    pub fn walk_towards(&mut self, x: f32, y: f32, delta_time: Duration, now: Instant) -> bool {
+        let (dx, dy) = (x - self.x, y - self.y);
+        let step_x = PLAYER_HORIZONTAL_SPEED * delta_time.as_secs_f32();
+        let step_y = PLAYER_SPEED * delta_time.as_secs_f32();
+        if dx.abs() <= step_x && dy.abs() <= step_y {
+            self.x = x;
+            self.y = y;
+            self.is_walking = false;
+            self.update_animation(ANIMATION_FRAME_DURATION, now);
+            return true;
+        }
+
+        self.direction = match (dx.abs() > step_x, dy.abs() > step_y) {
+            (true, true) => match (dx > 0.0, dy > 0.0) {
+                (false, false) => PlayerDirection::BackLeft,
+                (true, false) => PlayerDirection::BackRight,
+                (false, true) => PlayerDirection::FrontLeft,
+                (true, true) => PlayerDirection::FrontRight,
+            },
+            (true, false) if dx > 0.0 => PlayerDirection::Right,
+            (true, false) => PlayerDirection::Left,
+            _ if dy > 0.0 => PlayerDirection::Front,
+            _ => PlayerDirection::Back,
+        };
+        self.x += dx.clamp(-step_x, step_x);
+        self.y += dy.clamp(-step_y, step_y);
+        self.is_walking = true;
+        self.update_animation(ANIMATION_FRAME_DURATION, now);
+        false
+    }
+
     pub fn get_collision_rect(&self) -> ratatui::layout::Rect {
-        let (_, player_sprite_width, player_sprite_height) = self.get_sprite_content();
+        let (player_sprite_width, player_sprite_height) = self.sprite_size();
 
         let collision_box_x = (self.x as u16)
             .saturating_add(player_sprite_width / 2)
@@ -197,7 +315,7 @@ impl Player {
     }
 
     pub fn get_interaction_rect(&self) -> ratatui::layout::Rect {
-        let (_, player_sprite_width, player_sprite_height) = self.get_sprite_content();
+        let (player_sprite_width, player_sprite_height) = self.sprite_size();
 
         let (select_box_x, select_box_y) = match self.direction {
             PlayerDirection::Front | PlayerDirection::FrontLeft | PlayerDirection::FrontRight => (
@@ -235,7 +353,7 @@ impl Player {
     }
 
     fn check_collision(&self, player_x: f32, player_y: f32, context: &PlayerUpdateContext) -> bool {
-        let (_, player_sprite_width, player_sprite_height) = self.get_sprite_content();
+        let (player_sprite_width, player_sprite_height) = self.sprite_size();
         let collision_box_x = (player_x as u16)
             .saturating_add(player_sprite_width / 2)
             .saturating_sub(PLAYER_COLLISION_WIDTH / 2);
@@ -249,13 +367,12 @@ impl Player {
                 let check_x = collision_box_x.saturating_add(x_offset);
 
                 let collision_map_key = (*context.current_map_row, *context.current_map_col);
-                if let Some(collision_map) = context.loaded_maps.get(&collision_map_key) {
-                    if collision_map
+                if let Some(collision_map) = context.loaded_maps.get(&collision_map_key)
+                    && collision_map
                         .walls
                         .contains(&(check_x as u32, check_y as u32))
-                    {
-                        return true; // collision
-                    }
+                {
+                    return true; // collision
                 }
             }
         }
@@ -265,16 +382,16 @@ impl Player {
     pub fn update(
         &mut self,
         context: &mut PlayerUpdateContext,
-        key_states: &HashMap<KeyCode, bool>,
+        key_states: &HashMap<Action, bool>,
         delta_time: Duration,
     ) {
         let original_player_x = self.x;
         let original_player_y = self.y;
 
-        let up = *key_states.get(&KeyCode::Up).unwrap_or(&false);
-        let down = *key_states.get(&KeyCode::Down).unwrap_or(&false);
-        let left = *key_states.get(&KeyCode::Left).unwrap_or(&false);
-        let right = *key_states.get(&KeyCode::Right).unwrap_or(&false);
+        let up = *key_states.get(&Action::MoveUp).unwrap_or(&false);
+        let down = *key_states.get(&Action::MoveDown).unwrap_or(&false);
+        let left = *key_states.get(&Action::MoveLeft).unwrap_or(&false);
+        let right = *key_states.get(&Action::MoveRight).unwrap_or(&false);
 
         self.is_walking = up || down || left || right;
 
@@ -301,7 +418,10 @@ impl Player {
 
         if context.debug_mode {
             self.movement_counter = self.movement_counter.wrapping_add(1);
-            if self.movement_counter % DEBUG_MOVEMENT_FRAME_INTERVAL == 0 {
+            if self
+                .movement_counter
+                .is_multiple_of(DEBUG_MOVEMENT_FRAME_INTERVAL)
+            {
                 if up && !down {
                     new_player_y -= 1.0;
                 } else if down && !up {
@@ -313,26 +433,28 @@ impl Player {
                 }
             }
         } else {
+            let speed = context.walk_speed;
+            let horizontal_speed = speed * PLAYER_HORIZONTAL_SPEED / PLAYER_SPEED;
             let y_mov = if up && !down {
-                -PLAYER_SPEED
+                -speed
             } else if down && !up {
-                PLAYER_SPEED
+                speed
             } else {
                 0.0
             };
 
             let x_mov = if left && !right {
-                -PLAYER_HORIZONTAL_SPEED
+                -horizontal_speed
             } else if right && !left {
-                PLAYER_HORIZONTAL_SPEED
+                horizontal_speed
             } else {
                 0.0
             };
 
             let (final_x_mov, final_y_mov) = if x_mov != 0.0 && y_mov != 0.0 {
                 (
-                    x_mov * (1.0 / (2.0 as f32).sqrt()),
-                    y_mov * (1.0 / (2.0 as f32).sqrt()),
+                    x_mov * (1.0 / 2.0_f32.sqrt()),
+                    y_mov * (1.0 / 2.0_f32.sqrt()),
                 )
             } else {
                 (x_mov, y_mov)
@@ -342,21 +464,21 @@ impl Player {
             new_player_y += final_y_mov * delta_time.as_secs_f32();
         }
 
-        if *key_states.get(&KeyCode::Char('w')).unwrap_or(&false) {
-            if context.debug_mode && !context.is_drawing_select_box {
-                let current_map_key = (*context.current_map_row, *context.current_map_col);
-                if let Some(map_to_modify) = context.loaded_maps.get_mut(&current_map_key) {
-                    map_to_modify.toggle_wall(self.x as u32, self.y as u32);
-                    if let Err(e) = map_to_modify.save_data() {
-                        *context.message = format!("Failed to save map data: {}", e);
-                        *context.show_message = true;
-                        *context.message_animation_start_time = Instant::now();
-                        context.animated_message_content.clear();
-                    }
-                    context.wall_history.truncate(*context.history_index + 1);
-                    context.wall_history.push(map_to_modify.walls.clone());
-                    *context.history_index = context.wall_history.len() - 1;
+        if *key_states.get(&Action::DebugToggleWall).unwrap_or(&false)
+            && context.debug_mode
+            && !context.is_drawing_select_box
+        {
+            let current_map_key = (*context.current_map_row, *context.current_map_col);
+            if let Some(map_to_modify) = context.loaded_maps.get_mut(&current_map_key) {
+                map_to_modify.toggle_wall(self.x as u32, self.y as u32);
+                if let Err(e) = map_to_modify.save_data() {
+                    *context.message = format!("Failed to save map data: {}", e);
+                    *context.show_message = true;
+                    context.message_typewriter.clear();
                 }
+                context.wall_history.truncate(*context.history_index + 1);
+                context.wall_history.push(map_to_modify.walls.clone());
+                *context.history_index = context.wall_history.len() - 1;
             }
         }
 
@@ -375,7 +497,7 @@ impl Player {
 
         let current_map_key = (*context.current_map_row, *context.current_map_col);
         if let Some(current_map) = context.loaded_maps.get(&current_map_key) {
-            let (_, player_sprite_width, player_sprite_height) = self.get_sprite_content();
+            let (player_sprite_width, player_sprite_height) = self.sprite_size();
             self.x = self
                 .x
                 .max(0.0)
@@ -386,8 +508,86 @@ impl Player {
                 .min((current_map.height.saturating_sub(player_sprite_height)) as f32);
         }
 
-        
+        self.update_animation(ANIMATION_FRAME_DURATION, context.now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exp_levels_up_at_each_threshold() {
+        let mut stats = PlayerStats::default();
+        assert!(!stats.gain_exp(LV_EXP_THRESHOLDS[0] - 1));
+        assert_eq!(stats.lv, 1);
+        assert_eq!(stats.exp_to_next_level(), Some(1));
+
+        assert!(stats.gain_exp(1));
+        assert_eq!(stats.lv, 2);
+        assert_eq!(stats.max_hp, PLAYER_MAX_HP + 4);
+        assert_eq!(stats.atk, PLAYER_BASE_ATK + 2);
+
+        // enough for several levels at once
+        assert!(stats.gain_exp(LV_EXP_THRESHOLDS[2] - stats.exp));
+        assert_eq!(stats.lv, 4);
+        assert_eq!(stats.def, PLAYER_BASE_DEF + 1);
+
+        stats.gain_exp(u32::MAX);
+        assert_eq!(stats.lv as usize, LV_EXP_THRESHOLDS.len() + 1);
+        assert_eq!(stats.exp_to_next_level(), None);
+        assert!(!stats.gain_exp(1));
+    }
+
+    #[test]
+    fn damage_is_softened_by_def_and_hp_stops_at_zero() {
+        let mut stats = PlayerStats {
+            def: 10,
+            ..PlayerStats::default()
+        };
+        stats.take_damage(5);
+        assert_eq!(stats.hp, PLAYER_MAX_HP - 3);
+        // DEF never brings a hit below one point
+        stats.take_damage(1);
+        assert_eq!(stats.hp, PLAYER_MAX_HP - 4);
+        stats.take_damage(0);
+        assert_eq!(stats.hp, PLAYER_MAX_HP - 4);
+
+        stats.take_damage(1000);
+        assert_eq!(stats.hp, 0);
+        assert!(stats.is_dead());
+        stats.heal(1000);
+        assert_eq!(stats.hp, stats.max_hp);
+    }
+
+    #[test]
+    fn the_walk_animation_is_timed_from_the_game_clock() {
+        let mut clock = crate::game::clock::Clock::stepped();
+        let mut player = Player::new(0.0, 0.0, clock.now());
+        assert_eq!(player.animation_timer, clock.now());
+        player.is_walking = true;
+
+        let frame_duration = Duration::from_millis(150);
+        clock.advance(frame_duration - Duration::from_millis(1));
+        player.update_animation(frame_duration, clock.now());
+        assert_eq!(player.animation_frame, 0);
+        clock.advance(Duration::from_millis(1));
+        player.update_animation(frame_duration, clock.now());
+        assert_eq!(player.animation_frame, 1);
+    }
 
-        self.update_animation(ANIMATION_FRAME_DURATION);
+    #[test]
+    fn the_sprite_is_only_looked_up_again_when_the_frame_changes() {
+        let mut player = Player::new(0.0, 0.0, Instant::now());
+        let front = player.sprite();
+        assert!(Arc::ptr_eq(&front, &player.sprite()));
+
+        player.direction = PlayerDirection::Left;
+        let left = player.sprite();
+        assert!(!Arc::ptr_eq(&front, &left));
+        let stored = crate::assets::store()
+            .load_sprite(player.sprite_path())
+            .unwrap();
+        assert!(Arc::ptr_eq(&left, &stored));
     }
 }
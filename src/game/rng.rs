@@ -0,0 +1,35 @@
+//! Random numbers for anything that changes the game's state. They all come from one
+//! generator, so a session seeded the same plays out the same, see `crate::replay`.
+//! Purely cosmetic randomness (text shaking, sound pitch) stays on `thread_rng`, drawing
+//! it doesn't happen the same number of times from one run to the next.
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::cell::RefCell;
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn with<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        seed(7);
+        let first: Vec<u32> = (0..5).map(|_| with(|rng| rng.gen_range(0..1000))).collect();
+        seed(7);
+        let second: Vec<u32> = (0..5).map(|_| with(|rng| rng.gen_range(0..1000))).collect();
+        assert_eq!(first, second);
+    }
+}
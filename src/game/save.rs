@@ -0,0 +1,490 @@
+use super::flags::Flags;
+use super::item::Inventory;
+use super::player::{PlayerDirection, PlayerStats};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const SAVE_SLOT_COUNT: usize = 3;
+
+/// Overrides where saves go, handy for testing without touching real saves.
+pub const DATA_DIR_ENV_VAR: &str = "UNDER_TERM_DATA_DIR";
+
+/// Bump this and add a step to `MIGRATIONS` whenever `SaveData` changes shape.
+pub const SAVE_VERSION: u32 = 2;
+
+/// Everything a save slot remembers. Editor state and timers never end up in here.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveData {
+    pub map_name: String,
+    pub player_x: f32,
+    pub player_y: f32,
+    pub direction: PlayerDirection,
+    pub stats: PlayerStats,
+    #[serde(default)]
+    pub inventory: Inventory,
+    #[serde(default)]
+    pub flags: Flags,
+    #[serde(default)]
+    pub play_time_secs: u64,
+}
+
+/// What actually goes on disk: the save plus the version of the schema it was written with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u32,
+    pub save: SaveData,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Corrupt(serde_json::Error),
+    // written by a newer build, we can't know what its fields mean
+    TooNew { found: u32, supported: u32 },
+    Migration { from: u32, message: String },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "{}", e),
+            SaveError::Corrupt(e) => write!(f, "save file is corrupt: {}", e),
+            SaveError::TooNew { found, supported } => write!(
+                f,
+                "save is from a newer version of the game (format v{}, this build reads up to v{})",
+                found, supported
+            ),
+            SaveError::Migration { from, message } => {
+                write!(
+                    f,
+                    "could not upgrade save from format v{}: {}",
+                    from, message
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SaveError::Io(e) => Some(e),
+            SaveError::Corrupt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Corrupt(e)
+    }
+}
+
+impl From<SaveError> for io::Error {
+    fn from(e: SaveError) -> Self {
+        match e {
+            SaveError::Io(e) => e,
+            other => io::Error::other(other),
+        }
+    }
+}
+
+type Migration = fn(Value) -> Result<Value, String>;
+
+// MIGRATIONS[n] turns a v`n` save into a v`n + 1` one
+const MIGRATIONS: [Migration; SAVE_VERSION as usize] = [migrate_v0_to_v1, migrate_v1_to_v2];
+
+// v0 is the old game_data.json: just `current_map_name`, later with a position and stats
+fn migrate_v0_to_v1(mut value: Value) -> Result<Value, String> {
+    let object = value.as_object_mut().ok_or("expected an object")?;
+    let map_name = object
+        .remove("current_map_name")
+        .ok_or("missing current_map_name")?;
+    let (player_x, player_y) = match object.remove("player_position") {
+        Some(Value::Array(position)) if position.len() == 2 => {
+            (position[0].clone(), position[1].clone())
+        }
+        _ => {
+            // never saved a position, put them on the map's spawn point
+            let map_name = map_name
+                .as_str()
+                .ok_or("current_map_name is not a string")?;
+            let map = crate::game::map::Map::load(map_name).map_err(|e| e.to_string())?;
+            (json!(map.player_spawn.0), json!(map.player_spawn.1))
+        }
+    };
+    let stats = object
+        .remove("stats")
+        .unwrap_or_else(|| json!(PlayerStats::default()));
+    Ok(json!({
+        "map_name": map_name,
+        "player_x": player_x,
+        "player_y": player_y,
+        "direction": PlayerDirection::Front,
+        "stats": stats,
+    }))
+}
+
+// v1 saves were a bare SaveData without a version field
+fn migrate_v1_to_v2(value: Value) -> Result<Value, String> {
+    Ok(json!({ "version": 2, "save": value }))
+}
+
+fn detect_version(value: &Value) -> u32 {
+    if let Some(version) = value.get("version").and_then(Value::as_u64) {
+        return version as u32;
+    }
+    if value.get("current_map_name").is_some() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Upgrades a save of any known version to the current `SaveFile`.
+pub fn parse_save(contents: &str) -> Result<SaveFile, SaveError> {
+    let mut value: Value = serde_json::from_str(contents)?;
+    let mut version = detect_version(&value);
+    if version > SAVE_VERSION {
+        return Err(SaveError::TooNew {
+            found: version,
+            supported: SAVE_VERSION,
+        });
+    }
+    while version < SAVE_VERSION {
+        value = MIGRATIONS[version as usize](value).map_err(|message| SaveError::Migration {
+            from: version,
+            message,
+        })?;
+        version += 1;
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+thread_local! {
+    static DATA_DIR_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Points `data_dir` at `dir` for the rest of this thread, `None` points it back. A replay
+/// plays on a scratch copy of the slots this way, see `crate::replay::ReplaySaves`.
+pub fn redirect_data_dir(dir: Option<PathBuf>) {
+    DATA_DIR_OVERRIDE.with(|data_dir| *data_dir.borrow_mut() = dir);
+}
+
+/// Per-user data directory, e.g. `~/.local/share/under_term` on Linux.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR_OVERRIDE.with(|data_dir| data_dir.borrow().clone()) {
+        return dir;
+    }
+    if let Some(dir) = std::env::var_os(DATA_DIR_ENV_VAR) {
+        return PathBuf::from(dir);
+    }
+    if cfg!(windows)
+        && let Some(app_data) = std::env::var_os("APPDATA")
+    {
+        return PathBuf::from(app_data).join("under_term");
+    }
+    if let Some(data_home) = std::env::var_os("XDG_DATA_HOME")
+        && !data_home.is_empty()
+    {
+        return PathBuf::from(data_home).join("under_term");
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        if cfg!(target_os = "macos") {
+            return PathBuf::from(home).join("Library/Application Support/under_term");
+        }
+        return PathBuf::from(home).join(".local/share/under_term");
+    }
+    PathBuf::from(".under_term")
+}
+
+pub fn slot_path(slot: usize) -> PathBuf {
+    data_dir().join(format!("save_{}.json", slot + 1))
+}
+
+pub fn write_slot(slot: usize, save_data: &SaveData) -> Result<(), SaveError> {
+    write_save(&slot_path(slot), save_data)
+}
+
+/// `Ok(None)` for a slot that was never saved to. Older saves get upgraded on the way in.
+pub fn read_slot(slot: usize) -> Result<Option<SaveData>, SaveError> {
+    read_save(&slot_path(slot))
+}
+
+/// The slot's file exactly as it is on disk, `None` when it was never saved to.
+pub fn read_slot_file(slot: usize) -> io::Result<Option<String>> {
+    match std::fs::read_to_string(slot_path(slot)) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Puts back a slot file `read_slot_file` returned, `None` empties the slot.
+pub fn restore_slot_file(slot: usize, contents: Option<&str>) -> io::Result<()> {
+    let path = slot_path(slot);
+    match contents {
+        Some(contents) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, contents)
+        }
+        None => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+fn write_save(path: &Path, save_data: &SaveData) -> Result<(), SaveError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let save_file = SaveFile {
+        version: SAVE_VERSION,
+        save: save_data.clone(),
+    };
+    let serialized = serde_json::to_string_pretty(&save_file)?;
+    std::fs::write(path, serialized)?;
+    Ok(())
+}
+
+fn read_save(path: &Path) -> Result<Option<SaveData>, SaveError> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(parse_save(&contents)?.save)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Where builds from before save slots kept their one save.
+pub fn legacy_save_path() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/game_data.json"))
+}
+
+/// Moves a `game_data.json` from before save slots into slot 0 and renames the old file to
+/// `game_data.json.imported`, so this only happens once. Leaves it alone when slot 0 is
+/// already taken. Returns whether anything was imported.
+pub fn import_legacy_save() -> Result<bool, SaveError> {
+    import_legacy_save_into(&legacy_save_path(), &slot_path(0))
+}
+
+fn import_legacy_save_into(legacy_path: &Path, slot_path: &Path) -> Result<bool, SaveError> {
+    let contents = match std::fs::read_to_string(legacy_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    if slot_path.exists() {
+        return Ok(false);
+    }
+    write_save(slot_path, &parse_save(&contents)?.save)?;
+    std::fs::rename(legacy_path, legacy_path.with_extension("json.imported"))?;
+    Ok(true)
+}
+
+pub fn format_play_time(play_time: Duration) -> String {
+    let secs = play_time.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotPickerMode {
+    // picking a file to play after the intro, empty slots start a new game
+    Title,
+    // opened by a SAVE point
+    Save,
+}
+
+/// What a slot looks like in the picker, a broken file still gets a row.
+#[derive(Debug, Clone)]
+pub enum SlotSummary {
+    Empty,
+    Saved(Box<SaveData>),
+    Unreadable(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SlotPicker {
+    pub mode: SlotPickerMode,
+    pub selected: usize,
+    pub slots: Vec<SlotSummary>,
+    // "File saved." stays up until the next key press
+    pub saved: bool,
+    // opened by save and quit, the game closes as soon as the file is written
+    pub quit_after_save: bool,
+}
+
+impl SlotPicker {
+    pub fn new(mode: SlotPickerMode, selected: usize) -> Self {
+        let slots = (0..SAVE_SLOT_COUNT)
+            .map(|slot| match read_slot(slot) {
+                Ok(Some(save_data)) => SlotSummary::Saved(Box::new(save_data)),
+                Ok(None) => SlotSummary::Empty,
+                Err(e) => SlotSummary::Unreadable(e.to_string()),
+            })
+            .collect();
+        SlotPicker {
+            mode,
+            selected: selected.min(SAVE_SLOT_COUNT - 1),
+            slots,
+            saved: false,
+            quit_after_save: false,
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.slots.len() {
+            self.selected += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::flags::FlagValue;
+
+    fn sample_save() -> SaveData {
+        let mut inventory = Inventory::new();
+        inventory.add("bandage");
+        let mut flags = Flags::new();
+        flags.set("talked_to_sign", FlagValue::Bool(true));
+        SaveData {
+            map_name: "map_1_2".to_string(),
+            player_x: 40.5,
+            player_y: 12.0,
+            direction: PlayerDirection::Left,
+            stats: PlayerStats {
+                hp: 7,
+                gold: 30,
+                ..PlayerStats::default()
+            },
+            inventory,
+            flags,
+            play_time_secs: 3723,
+        }
+    }
+
+    #[test]
+    fn a_slot_reads_back_what_was_written() {
+        let dir = std::env::temp_dir().join(format!("under_term_save_{}", std::process::id()));
+        let path = dir.join("save_1.json");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(read_save(&path).unwrap().is_none());
+
+        write_save(&path, &sample_save()).unwrap();
+        assert_eq!(read_save(&path).unwrap(), Some(sample_save()));
+        let on_disk: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk["version"], SAVE_VERSION);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_v0_save_without_a_position_starts_on_the_map_spawn() {
+        // what the game wrote to game_data.json before saves had anything else in them
+        let save_file = parse_save(r#"{"current_map_name":"map_1_2"}"#).unwrap();
+        assert_eq!(save_file.version, SAVE_VERSION);
+        let save = save_file.save;
+        let spawn = crate::game::map::Map::load("map_1_2").unwrap().player_spawn;
+        assert_eq!(save.map_name, "map_1_2");
+        assert_eq!(
+            (save.player_x, save.player_y),
+            (spawn.0 as f32, spawn.1 as f32)
+        );
+        assert_eq!(save.direction, PlayerDirection::Front);
+        assert_eq!(save.stats, PlayerStats::default());
+        assert!(save.inventory.is_empty());
+        assert_eq!(save.play_time_secs, 0);
+
+        assert!(matches!(
+            parse_save(r#"{"current_map_name":"map_that_never_was"}"#),
+            Err(SaveError::Migration { from: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn a_v0_save_keeps_its_position_and_stats() {
+        let v0 = json!({
+            "current_map_name": "map_1_2",
+            "player_position": [40.5, 12],
+            "stats": PlayerStats { hp: 7, lv: 2, gold: 3, ..PlayerStats::default() },
+        });
+        let save = parse_save(&v0.to_string()).unwrap().save;
+        assert_eq!((save.player_x, save.player_y), (40.5, 12.0));
+        assert_eq!((save.stats.hp, save.stats.lv, save.stats.gold), (7, 2, 3));
+    }
+
+    #[test]
+    fn a_v1_save_is_wrapped_with_its_version() {
+        let mut v1 = serde_json::to_value(sample_save()).unwrap();
+        v1.as_object_mut().unwrap().remove("play_time_secs");
+        let save_file = parse_save(&v1.to_string()).unwrap();
+        assert_eq!(save_file.version, SAVE_VERSION);
+        assert_eq!(
+            save_file.save,
+            SaveData {
+                play_time_secs: 0,
+                ..sample_save()
+            }
+        );
+    }
+
+    #[test]
+    fn a_current_save_is_read_as_is() {
+        let current = serde_json::to_string(&SaveFile {
+            version: SAVE_VERSION,
+            save: sample_save(),
+        })
+        .unwrap();
+        assert_eq!(parse_save(&current).unwrap().save, sample_save());
+    }
+
+    #[test]
+    fn a_save_from_a_newer_build_is_refused() {
+        let newer = json!({ "version": SAVE_VERSION + 1, "save": sample_save() }).to_string();
+        match parse_save(&newer) {
+            Err(SaveError::TooNew { found, supported }) => {
+                assert_eq!((found, supported), (SAVE_VERSION + 1, SAVE_VERSION))
+            }
+            other => panic!("expected TooNew, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn the_legacy_save_moves_into_slot_zero_once() {
+        let dir = std::env::temp_dir().join(format!("under_term_legacy_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let legacy_path = dir.join("game_data.json");
+        let slot_path = dir.join("save_1.json");
+        std::fs::write(&legacy_path, r#"{"current_map_name":"map_0_0"}"#).unwrap();
+
+        assert!(import_legacy_save_into(&legacy_path, &slot_path).unwrap());
+        let save = read_save(&slot_path).unwrap().unwrap();
+        assert_eq!(save.map_name, "map_0_0");
+        assert!(!legacy_path.exists());
+        assert!(dir.join("game_data.json.imported").exists());
+
+        assert!(!import_legacy_save_into(&legacy_path, &slot_path).unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
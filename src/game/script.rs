@@ -0,0 +1,165 @@
+use super::map::Event;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// What the script is stuck on before it can run its next event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Waiting {
+    Dialogue,
+    Battle,
+    SlotPicker,
+    Timer {
+        remaining: Duration,
+    },
+    Fade {
+        from: u8,
+        to: u8,
+        elapsed: Duration,
+        duration: Duration,
+    },
+    Move {
+        x: f32,
+        y: f32,
+    },
+}
+
+/// A list of box events played one after another. Events that finish right away all run
+/// in the same frame, the others park the script in `waiting` until they're done.
+/// The player can't move or interact while a script is running.
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    pending: VecDeque<Event>,
+    pub waiting: Option<Waiting>,
+}
+
+impl Script {
+    pub fn new(events: &[Event]) -> Self {
+        Script {
+            pending: events.iter().cloned().collect(),
+            waiting: None,
+        }
+    }
+
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.pending.pop_front()
+    }
+
+    /// Runs `events` before whatever was left, used by the branches of an `If`.
+    pub fn push_front(&mut self, events: &[Event]) {
+        for event in events.iter().rev() {
+            self.pending.push_front(event.clone());
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty() && self.waiting.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::flags::FlagValue;
+    use crate::game::map::Map;
+    use crate::harness::TestHarness;
+
+    fn set(name: &str) -> Event {
+        Event::SetFlag {
+            name: name.to_string(),
+            value: FlagValue::Bool(true),
+        }
+    }
+
+    fn harness() -> TestHarness {
+        TestHarness::new(Map {
+            name: "map_0_0".to_string(),
+            width: 120,
+            height: 40,
+            ..Map::default()
+        })
+    }
+
+    fn flag_name(event: Option<Event>) -> Option<String> {
+        match event {
+            Some(Event::SetFlag { name, .. }) => Some(name),
+            other => panic!("expected a SetFlag, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn events_run_in_order_and_a_branch_goes_before_the_rest() {
+        let mut script = Script::new(&[set("first"), set("last")]);
+        assert_eq!(flag_name(script.next_event()).as_deref(), Some("first"));
+
+        script.push_front(&[set("then_a"), set("then_b")]);
+        assert_eq!(flag_name(script.next_event()).as_deref(), Some("then_a"));
+        assert_eq!(flag_name(script.next_event()).as_deref(), Some("then_b"));
+        assert_eq!(flag_name(script.next_event()).as_deref(), Some("last"));
+        assert!(script.next_event().is_none());
+        assert!(script.is_finished());
+    }
+
+    #[test]
+    fn a_script_that_is_waiting_is_not_finished() {
+        let mut script = Script::new(&[]);
+        script.waiting = Some(Waiting::Timer {
+            remaining: Duration::from_millis(500),
+        });
+        assert!(!script.is_finished());
+        script.waiting = None;
+        assert!(script.is_finished());
+    }
+
+    #[test]
+    fn a_wait_holds_the_script_until_its_time_is_up() {
+        let mut harness = harness();
+        harness
+            .game_state
+            .run_box_events(&[set("before"), Event::Wait { ms: 500 }, set("after")]);
+
+        harness.update();
+        assert!(harness.game_state.flags.is_set("before"));
+        assert!(matches!(
+            harness.game_state.script.as_ref().unwrap().waiting,
+            Some(Waiting::Timer { .. })
+        ));
+        harness.wait(Duration::from_millis(400));
+        assert!(!harness.game_state.flags.is_set("after"));
+        harness.wait(Duration::from_millis(150));
+        assert!(harness.game_state.flags.is_set("after"));
+        assert!(!harness.game_state.is_script_running());
+    }
+
+    #[test]
+    fn if_takes_the_branch_the_flags_call_for() {
+        let branch = Event::If {
+            flag: "times_died".to_string(),
+            at_least: Some(2),
+            then: vec![set("seen_it_all")],
+            otherwise: vec![set("still_new"), Event::Wait { ms: 100 }],
+        };
+        let mut harness = harness();
+        harness.game_state.run_box_events(&[
+            Event::IncrementFlag {
+                name: "times_died".to_string(),
+                by: 1,
+            },
+            branch.clone(),
+            set("done"),
+        ]);
+        harness.update();
+        assert!(harness.game_state.flags.is_set("still_new"));
+        assert!(!harness.game_state.flags.is_set("done"));
+        harness.wait(Duration::from_millis(150));
+        assert!(harness.game_state.flags.is_set("done"));
+        assert!(!harness.game_state.flags.is_set("seen_it_all"));
+
+        harness
+            .game_state
+            .flags
+            .set("times_died", FlagValue::Int(2));
+        harness.game_state.run_box_events(&[branch]);
+        harness.update();
+        assert!(harness.game_state.flags.is_set("seen_it_all"));
+    }
+}
@@ -0,0 +1,366 @@
+use super::config::{DEFAULT_MUSIC_VOLUME, DEFAULT_SFX_VOLUME, FRAME_RATE, PLAYER_SPEED};
+use super::keymap::{Action, Keymap};
+use super::save::data_dir;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const VOLUME_STEP: f32 = 0.1;
+const FRAME_RATES: [u64; 4] = [15, 24, 30, 60];
+const WALK_SPEED_STEP: f32 = 10.0;
+const MIN_WALK_SPEED: f32 = 20.0;
+const MAX_WALK_SPEED: f32 = 80.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TextSpeed {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl TextSpeed {
+    /// How far a typewriter gets in `delta_time`, so every pace keeps its own rhythm.
+    pub fn scale(self, delta_time: Duration) -> Duration {
+        match self {
+            TextSpeed::Slow => delta_time.mul_f32(0.5),
+            TextSpeed::Normal => delta_time,
+            TextSpeed::Fast => delta_time.mul_f32(2.0),
+        }
+    }
+}
+
+/// How many colours the terminal can show. Sprites are truecolor, older terminals get them
+/// squashed down to the nearest colour they do have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorMode {
+    #[default]
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+// the 16 basic colours with the rgb xterm shows them as
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+impl ColorMode {
+    pub fn convert(self, color: Color) -> Color {
+        let Color::Rgb(r, g, b) = color else {
+            return color;
+        };
+        match self {
+            ColorMode::TrueColor => color,
+            ColorMode::Ansi256 => {
+                // the 6x6x6 cube, greys go to the 24 step ramp
+                if r == g && g == b {
+                    return match r {
+                        0..=7 => Color::Indexed(16),
+                        248..=255 => Color::Indexed(231),
+                        _ => Color::Indexed(232 + (r - 8) / 10),
+                    };
+                }
+                let level = |c: u8| ((c as u16 * 5 + 127) / 255) as u8;
+                Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+            }
+            ColorMode::Ansi16 => {
+                let distance = |(cr, cg, cb): (u8, u8, u8)| {
+                    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+                    d(r, cr) + d(g, cg) + d(b, cb)
+                };
+                ANSI_16
+                    .iter()
+                    .min_by_key(|(_, rgb)| distance(*rgb))
+                    .map_or(color, |(ansi, _)| *ansi)
+            }
+        }
+    }
+}
+
+/// Options from the settings menu, kept in `settings.json` next to the saves. Anything
+/// missing from the file falls back to the values in `config.rs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub text_speed: TextSpeed,
+    pub frame_rate: u64,
+    pub walk_speed: f32,
+    pub color_mode: ColorMode,
+    // the red flashes before a battle
+    pub screen_flicker: bool,
+    // fading to and from black between maps, battles and the game over screen
+    pub fade_effects: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 1.0,
+            music_volume: DEFAULT_MUSIC_VOLUME,
+            sfx_volume: DEFAULT_SFX_VOLUME,
+            text_speed: TextSpeed::default(),
+            frame_rate: FRAME_RATE,
+            walk_speed: PLAYER_SPEED,
+            color_mode: ColorMode::default(),
+            screen_flicker: true,
+            fade_effects: true,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        data_dir().join("settings.json")
+    }
+
+    /// The defaults when there's no file yet. Like `Keymap::load`, a file that can't be
+    /// read or parsed is an error, the game won't start on it rather than quietly reset
+    /// every option. Fields the file leaves out do get their defaults.
+    pub fn load() -> io::Result<Self> {
+        Self::load_from(&Self::path())
+    }
+
+    fn load_from(path: &Path) -> io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| {
+                io::Error::other(format!(
+                    "settings in {} are not valid: {}",
+                    path.display(),
+                    e
+                ))
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn apply_volume(&self, audio: &mut crate::audio::Audio) {
+        audio.set_music_volume(self.master_volume * self.music_volume);
+        audio.set_sfx_volume(self.master_volume * self.sfx_volume);
+    }
+
+    /// The shortest time between two draws.
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_millis(1000 / self.frame_rate.max(1))
+    }
+
+    pub fn value(&self, row: SettingsRow, keymap: &Keymap) -> String {
+        let percent = |volume: f32| format!("{}%", (volume * 100.0).round() as i32);
+        let toggle = |on: bool| if on { "ON" } else { "OFF" }.to_string();
+        match row {
+            SettingsRow::MasterVolume => percent(self.master_volume),
+            SettingsRow::MusicVolume => percent(self.music_volume),
+            SettingsRow::SfxVolume => percent(self.sfx_volume),
+            SettingsRow::TextSpeed => format!("{:?}", self.text_speed).to_uppercase(),
+            SettingsRow::FrameRate => format!("{} FPS", self.frame_rate),
+            SettingsRow::WalkSpeed => format!("{}", self.walk_speed as i32),
+            SettingsRow::ColorMode => match self.color_mode {
+                ColorMode::TrueColor => "TRUECOLOR",
+                ColorMode::Ansi256 => "256 COLORS",
+                ColorMode::Ansi16 => "16 COLORS",
+            }
+            .to_string(),
+            SettingsRow::ScreenFlicker => toggle(self.screen_flicker),
+            SettingsRow::FadeEffects => toggle(self.fade_effects),
+            SettingsRow::Key(action) => keymap
+                .letter(action)
+                .map_or("-".to_string(), |c| c.to_ascii_uppercase().to_string()),
+        }
+    }
+
+    /// Left/Right on a row. Toggles and lists flip through their values either way.
+    pub fn adjust(&mut self, row: SettingsRow, forward: bool) {
+        let step_volume = |volume: &mut f32| {
+            let step = if forward { VOLUME_STEP } else { -VOLUME_STEP };
+            *volume = ((*volume + step) * 10.0).round().clamp(0.0, 10.0) / 10.0;
+        };
+        match row {
+            SettingsRow::MasterVolume => step_volume(&mut self.master_volume),
+            SettingsRow::MusicVolume => step_volume(&mut self.music_volume),
+            SettingsRow::SfxVolume => step_volume(&mut self.sfx_volume),
+            SettingsRow::TextSpeed => {
+                self.text_speed = match (self.text_speed, forward) {
+                    (TextSpeed::Slow, true) | (TextSpeed::Fast, false) => TextSpeed::Normal,
+                    (TextSpeed::Normal, true) => TextSpeed::Fast,
+                    (TextSpeed::Normal, false) => TextSpeed::Slow,
+                    (speed, _) => speed,
+                }
+            }
+            SettingsRow::FrameRate => {
+                let index = FRAME_RATES
+                    .iter()
+                    .position(|&rate| rate >= self.frame_rate)
+                    .unwrap_or(FRAME_RATES.len() - 1);
+                let index = if forward {
+                    (index + 1).min(FRAME_RATES.len() - 1)
+                } else {
+                    index.saturating_sub(1)
+                };
+                self.frame_rate = FRAME_RATES[index];
+            }
+            SettingsRow::WalkSpeed => {
+                let step = if forward {
+                    WALK_SPEED_STEP
+                } else {
+                    -WALK_SPEED_STEP
+                };
+                self.walk_speed = (self.walk_speed + step).clamp(MIN_WALK_SPEED, MAX_WALK_SPEED);
+            }
+            SettingsRow::ColorMode => {
+                self.color_mode = match (self.color_mode, forward) {
+                    (ColorMode::TrueColor, true) | (ColorMode::Ansi16, false) => ColorMode::Ansi256,
+                    (ColorMode::Ansi256, true) => ColorMode::Ansi16,
+                    (ColorMode::Ansi256, false) => ColorMode::TrueColor,
+                    (mode, _) => mode,
+                }
+            }
+            SettingsRow::ScreenFlicker => self.screen_flicker = !self.screen_flicker,
+            SettingsRow::FadeEffects => self.fade_effects = !self.fade_effects,
+            SettingsRow::Key(_) => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsRow {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    TextSpeed,
+    FrameRate,
+    WalkSpeed,
+    ColorMode,
+    ScreenFlicker,
+    FadeEffects,
+    Key(Action),
+}
+
+pub const SETTINGS_ROWS: [SettingsRow; 16] = [
+    SettingsRow::MasterVolume,
+    SettingsRow::MusicVolume,
+    SettingsRow::SfxVolume,
+    SettingsRow::TextSpeed,
+    SettingsRow::FrameRate,
+    SettingsRow::WalkSpeed,
+    SettingsRow::ColorMode,
+    SettingsRow::ScreenFlicker,
+    SettingsRow::FadeEffects,
+    SettingsRow::Key(Action::MoveUp),
+    SettingsRow::Key(Action::MoveDown),
+    SettingsRow::Key(Action::MoveLeft),
+    SettingsRow::Key(Action::MoveRight),
+    SettingsRow::Key(Action::Confirm),
+    SettingsRow::Key(Action::Cancel),
+    SettingsRow::Key(Action::Menu),
+];
+
+impl SettingsRow {
+    pub fn label(self) -> &'static str {
+        match self {
+            SettingsRow::MasterVolume => "MASTER VOLUME",
+            SettingsRow::MusicVolume => "MUSIC VOLUME",
+            SettingsRow::SfxVolume => "SFX VOLUME",
+            SettingsRow::TextSpeed => "TEXT SPEED",
+            SettingsRow::FrameRate => "FRAME RATE",
+            SettingsRow::WalkSpeed => "WALK SPEED",
+            SettingsRow::ColorMode => "COLORS",
+            SettingsRow::ScreenFlicker => "SCREEN FLICKER",
+            SettingsRow::FadeEffects => "FADE EFFECTS",
+            SettingsRow::Key(Action::MoveUp) => "KEY: UP",
+            SettingsRow::Key(Action::MoveDown) => "KEY: DOWN",
+            SettingsRow::Key(Action::MoveLeft) => "KEY: LEFT",
+            SettingsRow::Key(Action::MoveRight) => "KEY: RIGHT",
+            SettingsRow::Key(Action::Confirm) => "KEY: CONFIRM",
+            SettingsRow::Key(Action::Cancel) => "KEY: CANCEL",
+            SettingsRow::Key(Action::Menu) => "KEY: MENU",
+            SettingsRow::Key(_) => "KEY",
+        }
+    }
+}
+
+/// The settings screen, opened from the overworld menu. Changes apply right away and are
+/// written to disk when it closes.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsMenu {
+    pub selected: usize,
+    // waiting for the letter to bind the selected key row to
+    pub rebinding: bool,
+}
+
+impl SettingsMenu {
+    pub fn row(&self) -> SettingsRow {
+        SETTINGS_ROWS[self.selected]
+    }
+
+    pub fn move_cursor(&mut self, down: bool) {
+        if down {
+            self.selected = (self.selected + 1).min(SETTINGS_ROWS.len() - 1);
+        } else {
+            self.selected = self.selected.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_fall_back_to_the_config_defaults() {
+        let settings: Settings = serde_json::from_str(r#"{ "frame_rate": 30 }"#).unwrap();
+        assert_eq!(settings.frame_rate, 30);
+        assert_eq!(settings.walk_speed, PLAYER_SPEED);
+    }
+
+    #[test]
+    fn a_broken_settings_file_is_an_error_and_a_missing_one_the_defaults() {
+        let dir = std::env::temp_dir().join(format!("under_term_settings_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        assert_eq!(Settings::load_from(&path).unwrap(), Settings::default());
+
+        std::fs::write(&path, r#"{ "frame_rate": "fast" }"#).unwrap();
+        let error = Settings::load_from(&path).unwrap_err();
+        assert!(error.to_string().contains("settings.json"), "{}", error);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn colors_are_squashed_to_the_nearest_one_the_terminal_has() {
+        let red = Color::Rgb(250, 10, 10);
+        assert_eq!(ColorMode::TrueColor.convert(red), red);
+        assert_eq!(ColorMode::Ansi256.convert(red), Color::Indexed(196));
+        assert_eq!(ColorMode::Ansi16.convert(red), Color::LightRed);
+        assert_eq!(ColorMode::Ansi16.convert(Color::Reset), Color::Reset);
+    }
+}
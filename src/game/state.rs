@@ -1,12 +1,24 @@
+use super::battle::{BattleOutcome, BattleState};
+use super::clock::Clock;
+use super::config::{
+    GAME_OVER_FADE_DURATION, MUSIC_FADE_DURATION, TELEPORT_FADE_IN_DURATION,
+    TELEPORT_FADE_OUT_DURATION,
+};
 use super::deltarune::Deltarune;
-use super::dialogue::DialogueManager;
+use super::dialogue::{ConversationRegistry, DialogueManager};
+use super::enemy::Enemy;
+use super::flags::Flags;
+use super::item::Inventory;
 
+use super::keymap::{Action, Keymap};
 use super::map::Map;
+use super::menu::{MenuPage, OverworldMenu};
 use super::player::{Player, PlayerUpdateContext};
-use ansi_to_tui::IntoText;
-use crossterm::event::KeyCode;
-use rand::thread_rng;
-use rand::Rng;
+use super::save::{self, SaveData, SlotPicker, SlotPickerMode, SlotSummary};
+use super::script::{Script, Waiting};
+use super::settings::{Settings, SettingsMenu};
+use super::typewriter::{Pace, Typewriter};
+use crate::audio::Voice;
 use ratatui::layout::Rect;
 use ratatui::style::Color;
 use ratatui::text::{Line, Span, Text};
@@ -47,12 +59,8 @@ pub struct IntroState {
     pub frames: Vec<IntroFrame>,
     pub current_frame_index: usize,
     pub current_text_index: usize,
-    pub animated_text: String,
-    #[serde(skip, default = "default_instant")]
-    pub text_animation_timer: Instant,
     #[serde(skip)]
-    pub text_animation_interval: Duration,
-    pub text_animation_finished: bool,
+    pub typewriter: Typewriter,
     #[serde(skip)]
     pub post_text_delay_timer: Option<Instant>,
     pub is_fading_out: bool,
@@ -60,6 +68,12 @@ pub struct IntroState {
     pub fade_out_timer: Option<Instant>,
 }
 
+impl Default for IntroState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IntroState {
     pub fn new() -> Self {
         let frames = vec![
@@ -104,14 +118,12 @@ impl IntroState {
                 ],
             },
         ];
+        let typewriter = Typewriter::new(&frames[0].texts[0], Pace::INTRO);
         IntroState {
             frames,
             current_frame_index: 0,
             current_text_index: 0,
-            animated_text: String::new(),
-            text_animation_timer: Instant::now(),
-            text_animation_interval: Duration::from_millis(50),
-            text_animation_finished: false,
+            typewriter,
             post_text_delay_timer: None,
             is_fading_out: false,
             fade_out_timer: None,
@@ -126,16 +138,15 @@ pub struct GameState {
     pub camera_y: u16,
     pub message: String,
     pub show_message: bool,
-    pub animated_message_content: String,
-    #[serde(skip, default = "default_instant")]
-    pub message_animation_start_time: Instant,
+    // types out `message`, restarted whenever the two don't match
     #[serde(skip)]
-    pub message_animation_interval: Duration,
+    pub message_typewriter: Typewriter,
     #[serde(skip)]
     pub message_animation_finished: bool,
-    #[serde(skip)]
-    pub previous_chars_shown: usize,
     pub sound_error: Option<String>,
+    // set by a ChangeMusic event, the map's own track comes back on the next map
+    #[serde(skip)]
+    pub music_override: Option<Option<String>>,
     #[serde(skip)]
     pub loaded_maps: std::collections::HashMap<(i32, i32), Map>,
     pub debug_mode: bool,
@@ -153,14 +164,13 @@ pub struct GameState {
     pub is_map_kind_selection_active: bool,
     pub is_placing_sprite: bool,
     pub pending_placed_sprite: Option<crate::game::map::PlacedSprite>,
-    pub is_flickering: bool,
-    #[serde(skip, default = "default_instant")]
-    pub flicker_timer: Instant,
-    pub flicker_duration: Duration,
-    pub flicker_count: u8,
-    pub show_flicker_black_screen: bool,
     pub show_enemy_ansi: bool,
+    #[serde(default)]
+    pub battle: Option<BattleState>,
+    pub recently_fought_zone_id: Option<u32>,
     pub dialogue_manager: DialogueManager,
+    #[serde(skip)]
+    pub conversations: ConversationRegistry,
     pub dialogue_active: bool,
     pub current_text_page_index: usize,
     pub current_dialogue_text_pages: Vec<String>,
@@ -198,7 +208,36 @@ pub struct GameState {
     pub teleport_transition_timer: Option<Instant>,
     #[serde(skip)]
     pub pending_teleport_destination: Option<(u16, u16, i32, i32, String, u32)>,
+    #[serde(default)]
+    pub inventory: Inventory,
+    #[serde(default)]
+    pub flags: Flags,
+    #[serde(default)]
+    pub play_time: Duration,
+    #[serde(skip)]
+    pub current_save_slot: Option<usize>,
+    #[serde(skip)]
+    pub slot_picker: Option<SlotPicker>,
+    // a box's events being played out, see `update_script`
+    #[serde(skip)]
+    pub script: Option<Script>,
+    #[serde(skip)]
+    pub menu: Option<OverworldMenu>,
+    #[serde(skip)]
+    pub settings: Settings,
+    #[serde(skip)]
+    pub settings_menu: Option<SettingsMenu>,
+    #[serde(skip)]
+    pub keymap: Keymap,
+    // stepped by the game loop, see `crate::game::clock`
+    #[serde(skip)]
+    pub clock: Clock,
     pub game_over_active: bool,
+    #[serde(skip)]
+    pub game_over_timer: Option<Instant>,
+    // 0 = continue, 1 = quit
+    #[serde(default)]
+    pub game_over_selection: usize,
     pub resized: bool,
     pub intro_active: bool,
     pub intro_state: IntroState,
@@ -207,11 +246,6 @@ pub struct GameState {
     pub fade_in_from_intro_timer: Option<Instant>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct SaveData {
-    current_map_name: String,
-}
-
 impl GameState {
     pub fn from_map(map: Map) -> Self {
         let player_spawn_x = map.player_spawn.0;
@@ -223,17 +257,15 @@ impl GameState {
 
         let mut loaded_maps = HashMap::new();
         loaded_maps.insert((current_map_row, current_map_col), map.clone());
+        let clock = Clock::default();
         GameState {
-            player: Player::new(player_spawn_x as f32, player_spawn_y as f32),
+            player: Player::new(player_spawn_x as f32, player_spawn_y as f32, clock.now()),
             camera_x: 0,
             camera_y: 0,
             message: String::new(),
             show_message: false,
-            animated_message_content: String::new(),
-            message_animation_start_time: Instant::now(),
-            message_animation_interval: Duration::from_millis(thread_rng().gen_range(50..=100)),
+            message_typewriter: Typewriter::default(),
             message_animation_finished: false,
-            previous_chars_shown: 0,
             current_map_name: format!("map_{}_{}", current_map_row, current_map_col),
             loaded_maps,
             debug_mode: false,
@@ -251,18 +283,17 @@ impl GameState {
             is_map_kind_selection_active: false,
             is_placing_sprite: false,
             pending_placed_sprite: None,
-            is_flickering: false,
-            flicker_timer: Instant::now(),
-            flicker_duration: Duration::from_millis(25),
-            flicker_count: 10,
-            show_flicker_black_screen: false,
             show_enemy_ansi: false,
-            dialogue_manager: DialogueManager::new(),
+            battle: None,
+            recently_fought_zone_id: None,
+            dialogue_manager: DialogueManager::default(),
+            conversations: ConversationRegistry::new(),
             dialogue_active: false,
             current_text_page_index: 0,
             current_dialogue_text_pages: Vec::new(),
             last_paginated_dialogue_text: String::new(),
             sound_error: None,
+            music_override: None,
             current_map_row,
             current_map_col,
             wall_history: vec![map.walls.clone()],
@@ -283,7 +314,20 @@ impl GameState {
             teleport_state: TeleportState::None,
             teleport_transition_timer: None,
             pending_teleport_destination: None,
+            inventory: Inventory::new(),
+            flags: Flags::new(),
+            play_time: Duration::ZERO,
+            current_save_slot: None,
+            slot_picker: None,
+            script: None,
+            menu: None,
+            settings: Settings::default(),
+            settings_menu: None,
+            keymap: Keymap::default(),
+            clock,
             game_over_active: false,
+            game_over_timer: None,
+            game_over_selection: 0,
             resized: false,
             intro_active: true,
             intro_state: IntroState::new(),
@@ -292,60 +336,501 @@ impl GameState {
         }
     }
 
+    pub fn to_save_data(&self) -> SaveData {
+        SaveData {
+            map_name: self.current_map_name.clone(),
+            player_x: self.player.x,
+            player_y: self.player.y,
+            direction: self.player.direction,
+            stats: self.player.stats.clone(),
+            inventory: self.inventory.clone(),
+            flags: self.flags.clone(),
+            play_time_secs: self.play_time.as_secs(),
+        }
+    }
+
+    /// Switches to `clock` and restarts the player's timers on it, so no timer gets
+    /// measured across two clocks.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+        self.player.animation_timer = clock.now();
+        self.player.walking_stop_timer = clock.now();
+    }
+
+    /// Writes the current progress to the slot this run was started from. A run that
+    /// never picked a slot gets an error rather than a guess, ask with the slot picker.
     pub fn save_game_state(&mut self) -> io::Result<()> {
-        let save_data = SaveData {
-            current_map_name: self.current_map_name.clone(),
-        };
-        let serialized = serde_json::to_string(&save_data)?;
-        std::fs::write(
-            concat!(env!("CARGO_MANIFEST_DIR"), "/game_data.json"),
-            serialized,
-        )?;
+        let slot = self
+            .current_save_slot
+            .ok_or_else(|| io::Error::other("no save slot picked for this run"))?;
+        self.save_to_slot(slot)
+    }
+
+    pub fn save_to_slot(&mut self, slot: usize) -> io::Result<()> {
+        save::write_slot(slot, &self.to_save_data())?;
+        self.current_save_slot = Some(slot);
         Ok(())
     }
 
     pub fn load_game_state() -> io::Result<Self> {
-        let map = Map::load("map_0_0").map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to load default map: {}", e),
-            )
-        })?;
+        let map = Map::load("map_0_0")
+            .map_err(|e| io::Error::other(format!("Failed to load default map: {}", e)))?;
         let mut game_state = GameState::from_map(map.clone());
         game_state.player.x = map.player_spawn.0 as f32;
         game_state.player.y = map.player_spawn.1 as f32;
         Ok(game_state)
     }
 
+    /// Throws away the current run and starts over from `save_data`, or from the start of
+    /// the first map when there is none. The screen fades in from black either way.
+    pub fn restart_from(&mut self, slot: usize, save_data: Option<SaveData>) -> io::Result<()> {
+        let map_name = save_data
+            .as_ref()
+            .map(|save| save.map_name.clone())
+            .unwrap_or_else(|| "map_0_0".to_string());
+        let map = Map::load(&map_name)
+            .map_err(|e| io::Error::other(format!("Failed to load map {}: {}", map_name, e)))?;
+
+        let mut game_state = GameState::from_map(map);
+        if let Some(save_data) = save_data {
+            game_state.player.x = save_data.player_x;
+            game_state.player.y = save_data.player_y;
+            game_state.player.direction = save_data.direction;
+            game_state.player.stats = save_data.stats;
+            if game_state.player.stats.is_dead() {
+                game_state.player.stats.hp = game_state.player.stats.max_hp;
+            }
+            game_state.inventory = save_data.inventory;
+            game_state.flags = save_data.flags;
+            game_state.play_time = Duration::from_secs(save_data.play_time_secs);
+        }
+        game_state.intro_active = false;
+        game_state.debug_mode = self.debug_mode;
+        game_state.settings = self.settings.clone();
+        game_state.keymap = self.keymap.clone();
+        game_state.set_clock(self.clock);
+        game_state.current_save_slot = Some(slot);
+        game_state.deltarune.level = 100;
+        game_state.is_fading_in_from_intro = true;
+
+        *self = game_state;
+        Ok(())
+    }
+
+    /// Game over's "continue": back to whatever the current slot last saved. A run that
+    /// never picked a slot gets the title's slot picker to load one from instead.
+    pub fn continue_from_last_save(&mut self) -> io::Result<()> {
+        let Some(slot) = self.current_save_slot else {
+            self.open_slot_picker(SlotPickerMode::Title);
+            return Ok(());
+        };
+        let save_data = save::read_slot(slot)?;
+        self.restart_from(slot, save_data)
+    }
+
+    pub fn open_slot_picker(&mut self, mode: SlotPickerMode) {
+        self.slot_picker = Some(SlotPicker::new(mode, self.current_save_slot.unwrap_or(0)));
+    }
+
+    /// Z/Enter on the slot picker: load or start a file from the title, write one at a
+    /// SAVE point.
+    pub fn confirm_slot_picker(&mut self) -> io::Result<()> {
+        let Some(picker) = self.slot_picker.take() else {
+            return Ok(());
+        };
+        let slot = picker.selected;
+        match picker.mode {
+            SlotPickerMode::Title => match &picker.slots[slot] {
+                SlotSummary::Saved(save_data) => {
+                    self.restart_from(slot, Some(save_data.as_ref().clone()))?
+                }
+                SlotSummary::Empty => self.restart_from(slot, None)?,
+                SlotSummary::Unreadable(_) => {
+                    // nothing to load, stay on the picker
+                    self.slot_picker = Some(picker);
+                }
+            },
+            SlotPickerMode::Save if picker.saved => {}
+            SlotPickerMode::Save => {
+                self.save_to_slot(slot)?;
+                let quit_after_save = picker.quit_after_save;
+                let mut picker = SlotPicker::new(SlotPickerMode::Save, slot);
+                picker.saved = true;
+                picker.quit_after_save = quit_after_save;
+                self.slot_picker = Some(picker);
+            }
+        }
+        Ok(())
+    }
+
+    /// Events that fire once a box's messages have all been read. They run as a script
+    /// from the next update on.
+    pub fn run_box_events(&mut self, events: &[crate::game::map::Event]) {
+        if events.iter().any(|event| !event.is_teleport()) {
+            self.script = Some(Script::new(events));
+        }
+    }
+
+    pub fn is_script_running(&self) -> bool {
+        self.script.is_some()
+    }
+
+    /// Finishes whatever the script is waiting on, then runs events until one of them
+    /// has to wait again.
+    fn update_script(&mut self, delta_time: Duration, audio: &mut crate::audio::Audio) {
+        let Some(mut script) = self.script.take() else {
+            return;
+        };
+
+        if let Some(waiting) = &mut script.waiting {
+            let done = match waiting {
+                Waiting::Dialogue => !self.dialogue_active,
+                Waiting::Battle => self.battle.is_none(),
+                Waiting::SlotPicker => self.slot_picker.is_none(),
+                Waiting::Timer { remaining } => {
+                    *remaining = remaining.saturating_sub(delta_time);
+                    remaining.is_zero()
+                }
+                Waiting::Fade {
+                    from,
+                    to,
+                    elapsed,
+                    duration,
+                } => {
+                    *elapsed += delta_time;
+                    let progress = if duration.is_zero() {
+                        1.0
+                    } else {
+                        (elapsed.as_secs_f32() / duration.as_secs_f32()).min(1.0)
+                    };
+                    self.deltarune.level =
+                        (*from as f32 + (*to as f32 - *from as f32) * progress) as u8;
+                    progress >= 1.0
+                }
+                Waiting::Move { x, y } => {
+                    self.player
+                        .walk_towards(*x, *y, delta_time, self.clock.now())
+                }
+            };
+            if done {
+                script.waiting = None;
+            }
+        }
+
+        while script.waiting.is_none()
+            && let Some(event) = script.next_event()
+        {
+            script.waiting = self.run_script_event(event, &mut script, audio);
+        }
+
+        if !script.is_finished() {
+            self.script = Some(script);
+        }
+    }
+
+    fn run_script_event(
+        &mut self,
+        event: crate::game::map::Event,
+        script: &mut Script,
+        audio: &mut crate::audio::Audio,
+    ) -> Option<Waiting> {
+        use crate::game::map::Event;
+
+        match event {
+            Event::SavePoint => {
+                self.player.stats.hp = self.player.stats.max_hp;
+                self.open_slot_picker(SlotPickerMode::Save);
+                Some(Waiting::SlotPicker)
+            }
+            Event::SetFlag { name, value } => {
+                self.flags.set(&name, value);
+                None
+            }
+            Event::IncrementFlag { name, by } => {
+                self.flags.increment(&name, by);
+                None
+            }
+            Event::ShowDialogue { id } => match self.conversations.start(&id) {
+                Ok(dialogue_manager) => {
+                    self.dialogue_manager = dialogue_manager;
+                    self.dialogue_active = true;
+                    Some(Waiting::Dialogue)
+                }
+                Err(e) => {
+                    self.show_text_message(format!("Failed to load dialogue {}: {}", id, e));
+                    None
+                }
+            },
+            Event::StartBattle { enemy_id } => self
+                .start_battle(&enemy_id, None, audio)
+                .then_some(Waiting::Battle),
+            Event::GiveItem { item } => {
+                if !self.inventory.add(&item) {
+                    self.show_text_message("* You're carrying too much.".to_string());
+                }
+                None
+            }
+            Event::PlaySound { sound } => {
+                if let Err(e) = audio.play_sound(&sound) {
+                    self.sound_error = Some(format!("Failed to play sound {}: {}", sound, e));
+                }
+                None
+            }
+            Event::ChangeMusic { track } => {
+                self.music_override = Some(track);
+                None
+            }
+            Event::Wait { ms } => Some(Waiting::Timer {
+                remaining: Duration::from_millis(ms),
+            }),
+            Event::FadeOut { ms } => Some(Waiting::Fade {
+                from: self.deltarune.level,
+                to: 100,
+                elapsed: Duration::ZERO,
+                duration: Duration::from_millis(ms),
+            }),
+            Event::FadeIn { ms } => Some(Waiting::Fade {
+                from: self.deltarune.level,
+                to: 0,
+                elapsed: Duration::ZERO,
+                duration: Duration::from_millis(ms),
+            }),
+            Event::MovePlayer { x, y } => Some(Waiting::Move {
+                x: x as f32,
+                y: y as f32,
+            }),
+            Event::If {
+                flag,
+                at_least,
+                then,
+                otherwise,
+            } => {
+                let condition_met = match at_least {
+                    Some(at_least) => self.flags.get_int(&flag) >= at_least,
+                    None => self.flags.is_set(&flag),
+                };
+                script.push_front(if condition_met { &then } else { &otherwise });
+                None
+            }
+            Event::EndGame => {
+                self.teleport_state = TeleportState::FadingOutToThankYou;
+                self.teleport_transition_timer = Some(self.clock.now());
+                None
+            }
+            // teleports go off when walking into the box, see update
+            Event::TeleportPlayer { .. } => None,
+        }
+    }
+
+    /// The track that should be playing: the enemy's in battle, otherwise the map's. While
+    /// fading out of a teleport it's already the destination's, so the two crossfade
+    /// over the whole transition.
+    fn wanted_music(&self) -> Option<String> {
+        if self.game_over_active {
+            return None;
+        }
+        if let Some(battle) = &self.battle
+            && battle.music.is_some()
+        {
+            return battle.music.clone();
+        }
+        if let Some(track) = &self.music_override {
+            return track.clone();
+        }
+        let current_map_key = (self.current_map_row, self.current_map_col);
+        let map_key = match &self.pending_teleport_destination {
+            Some((_, _, map_row, map_col, _, _)) => (*map_row, *map_col),
+            None => current_map_key,
+        };
+        self.loaded_maps
+            .get(&map_key)
+            .or_else(|| self.loaded_maps.get(&current_map_key))
+            .and_then(|map| map.music.clone())
+    }
+
+    fn update_music(&mut self, audio: &mut crate::audio::Audio) {
+        let fade = if self.teleport_state == TeleportState::FadingOut {
+            TELEPORT_FADE_OUT_DURATION + TELEPORT_FADE_IN_DURATION
+        } else {
+            MUSIC_FADE_DURATION
+        };
+        if let Err(e) = audio.play_music(self.wanted_music().as_deref(), fade) {
+            self.sound_error = Some(format!("Failed to play music: {}", e));
+        }
+    }
+
+    /// Returns false (and says why) when the enemy couldn't be loaded.
+    fn start_battle(
+        &mut self,
+        enemy_id: &str,
+        conversation: Option<String>,
+        audio: &mut crate::audio::Audio,
+    ) -> bool {
+        match Enemy::load(enemy_id).and_then(|enemy| BattleState::new(&enemy)) {
+            Ok(mut battle) => {
+                battle.conversation = conversation;
+                battle.player_atk = self.player.stats.total_atk();
+                battle.items = self.inventory.names();
+                battle.text_speed = self.settings.text_speed;
+                self.battle = Some(battle);
+                if let Err(e) = audio.play_sound("enemy_encounter") {
+                    self.sound_error = Some(format!("Failed to play sound enemy_encounter: {}", e));
+                }
+                true
+            }
+            Err(e) => {
+                self.show_text_message(format!("Failed to start battle: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Enter next to a placed sprite with a conversation. False when there's nobody to talk to.
+    pub fn talk_to_npc(&mut self) -> bool {
+        let interaction_rect = self.player.get_interaction_rect();
+        let conversation = self
+            .loaded_maps
+            .get(&(self.current_map_row, self.current_map_col))
+            .and_then(|map| {
+                map.placed_sprites.iter().find(|sprite| {
+                    sprite.conversation.is_some() && sprite.to_rect().intersects(interaction_rect)
+                })
+            })
+            .and_then(|sprite| sprite.conversation.clone());
+        match conversation {
+            Some(id) => {
+                self.run_box_events(&[crate::game::map::Event::ShowDialogue { id }]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn show_text_message(&mut self, message: String) {
+        self.message = message;
+        self.show_message = true;
+        self.message_typewriter.clear();
+    }
+
+    /// The last line of a conversation was confirmed, back to the overworld. Whatever its
+    /// lines and choices triggered runs now, before the rest of a script that showed it.
+    pub fn finish_dialogue(&mut self) {
+        self.dialogue_active = false;
+        let events = std::mem::take(&mut self.dialogue_manager.pending_events);
+        match &mut self.script {
+            Some(script) => script.push_front(&events),
+            None => self.run_box_events(&events),
+        }
+    }
+
+    pub fn open_menu(&mut self) {
+        self.menu = Some(OverworldMenu::new(self.inventory.names()));
+    }
+
+    /// Z/Enter in the overworld menu.
+    pub fn confirm_menu(&mut self) {
+        let Some(menu) = &mut self.menu else {
+            return;
+        };
+        if menu.text.is_some() {
+            self.menu = None;
+            return;
+        }
+        match menu.page {
+            MenuPage::Main => match menu.selected {
+                0 if menu.item_names.is_empty() => {}
+                0 => {
+                    menu.page = MenuPage::Items;
+                    menu.item_index = menu.item_index.min(menu.item_names.len() - 1);
+                }
+                1 => menu.page = MenuPage::Stat,
+                2 => menu.text = Some("* ...There's no signal down here.".to_string()),
+                _ => {
+                    self.menu = None;
+                    self.settings_menu = Some(SettingsMenu::default());
+                }
+            },
+            MenuPage::Items => {
+                menu.page = MenuPage::ItemActions;
+                menu.action_index = 0;
+            }
+            MenuPage::ItemActions => {
+                let index = menu.item_index;
+                // same order as ITEM_ACTIONS
+                let text = match menu.action_index {
+                    0 => self.inventory.use_item(index, &mut self.player.stats),
+                    1 => self.inventory.item_info(index),
+                    _ => self.inventory.drop_item(index),
+                };
+                menu.item_names = self.inventory.names();
+                menu.page = MenuPage::Main;
+                menu.text = Some(text);
+            }
+            MenuPage::Stat => {}
+        }
+    }
+
+    fn start_game_over(&mut self) {
+        self.game_over_active = true;
+        self.game_over_timer = Some(self.clock.now());
+        self.game_over_selection = 0;
+        self.deltarune.level = 100;
+        self.dialogue_active = false;
+        self.script = None;
+        self.dismiss_message();
+    }
+
     pub fn dismiss_message(&mut self) {
         self.show_message = false;
         self.message.clear();
-        self.animated_message_content.clear();
+        self.message_typewriter.clear();
         self.message_animation_finished = false;
         self.block_player_movement_on_message = false;
-        self.previous_chars_shown = 0;
-        self.message_animation_start_time = Instant::now();
     }
 
     pub fn update(
         &mut self,
-        key_states: &HashMap<KeyCode, bool>,
+        key_states: &HashMap<Action, bool>,
         frame_size: ratatui::layout::Rect,
         delta_time: std::time::Duration,
         audio: &mut crate::audio::Audio,
     ) {
         if self.intro_active {
-            self.update_intro(key_states, audio);
+            self.update_intro(key_states, delta_time, audio);
+            return;
+        }
+
+        self.update_music(audio);
+
+        // the world waits while a file is being picked or the menu is open
+        if self.slot_picker.is_some() || self.menu.is_some() || self.settings_menu.is_some() {
+            return;
+        }
+
+        if self.game_over_active {
+            // fade the game over screen in from black
+            if let Some(timer) = self.game_over_timer {
+                let elapsed = self.clock.since(timer);
+                if elapsed >= GAME_OVER_FADE_DURATION {
+                    self.deltarune.level = 0;
+                    self.game_over_timer = None;
+                } else {
+                    let progress = elapsed.as_secs_f32() / GAME_OVER_FADE_DURATION.as_secs_f32();
+                    self.deltarune.level = (100.0 - (progress * 100.0)).max(0.0) as u8;
+                }
+            }
             return;
         }
 
+        self.play_time += delta_time;
+
         if self.is_fading_in_from_intro {
             let fade_duration = Duration::from_secs(1);
             if self.fade_in_from_intro_timer.is_none() {
-                self.fade_in_from_intro_timer = Some(Instant::now());
+                self.fade_in_from_intro_timer = Some(self.clock.now());
             }
             if let Some(timer) = self.fade_in_from_intro_timer {
-                let elapsed = timer.elapsed();
+                let elapsed = self.clock.since(timer);
                 if elapsed >= fade_duration {
                     self.deltarune.level = 0;
                     self.is_fading_in_from_intro = false;
@@ -356,37 +841,70 @@ impl GameState {
             }
         }
 
-        if self.is_flickering {
-            if self.flicker_timer.elapsed() >= self.flicker_duration {
-                self.show_flicker_black_screen = !self.show_flicker_black_screen;
-                self.flicker_count = self.flicker_count.saturating_sub(1);
-                self.flicker_timer = Instant::now();
+        self.update_script(delta_time, audio);
 
-                if self.flicker_count == 0 {
-                    self.is_flickering = false;
-                    self.dialogue_active = true;
-                    self.show_flicker_black_screen = false;
+        if let Some(battle) = &mut self.battle {
+            if let Some(index) = battle.item_to_use {
+                let text = self.inventory.use_item(index, &mut self.player.stats);
+                battle.items = self.inventory.names();
+                battle.player_atk = self.player.stats.total_atk();
+                battle.show_item_used(text);
+            }
+            let damage = battle.update(
+                key_states,
+                delta_time,
+                audio,
+                self.clock.now(),
+                &mut self.sound_error,
+            );
+            if damage > 0 {
+                self.player.stats.take_damage(damage);
+                if self.player.stats.is_dead() {
+                    battle.lose();
+                }
+            }
+            if let Some(outcome) = battle.finished_outcome() {
+                let (exp_reward, gold_reward) = (battle.exp_reward, battle.gold_reward);
+                let enemy_id = battle.enemy_id.clone();
+                let conversation = battle.conversation.take();
+                self.battle = None;
+                match outcome {
+                    BattleOutcome::Won => {
+                        self.player.stats.gain_exp(exp_reward);
+                        self.player.stats.gold += gold_reward;
+                        self.flags.increment(&format!("killed_{}", enemy_id), 1);
+                    }
+                    BattleOutcome::Spared => {
+                        self.player.stats.gold += gold_reward;
+                        self.flags.increment(&format!("spared_{}", enemy_id), 1);
+                    }
+                    BattleOutcome::Fled => {}
+                    BattleOutcome::Lost => self.start_game_over(),
+                }
+                if matches!(outcome, BattleOutcome::Won | BattleOutcome::Spared)
+                    && let Some(id) = conversation
+                {
+                    self.run_box_events(&[crate::game::map::Event::ShowDialogue { id }]);
                 }
             }
             return;
         }
 
         if self.dialogue_active {
-            if let Some(dialogue) = self.dialogue_manager.current_dialogue() {
-                if !self.dialogue_manager.text_animation_finished {
-                    // animate text
-                    if self.message_animation_start_time.elapsed()
-                        >= self.message_animation_interval
-                    {
-                        let current_len = self.dialogue_manager.visible_text_len;
-                        if current_len < dialogue.text.chars().count() {
-                            self.dialogue_manager.visible_text_len += 1;
-                            audio.play_text_sound();
-                            self.message_animation_start_time = Instant::now();
-                        } else {
-                            self.dialogue_manager.text_animation_finished = true;
-                        }
-                    }
+            if self.dialogue_manager.current_dialogue().is_some() {
+                if self
+                    .dialogue_manager
+                    .typewriter
+                    .update(self.settings.text_speed.scale(delta_time))
+                    > 0
+                    && let Some(typed) = self.dialogue_manager.typewriter.last_char()
+                    && let Err(e) = audio.play_voice(
+                        &self.dialogue_manager.current_voice(),
+                        typed,
+                        self.clock.now(),
+                    )
+                {
+                    self.sound_error = Some(format!("Failed to play voice: {}", e));
                 }
             } else {
                 // No more dialogues, transition to thank you screen
@@ -399,39 +917,23 @@ impl GameState {
             self.dismiss_message();
         }
         if self.is_confirming_select_box
-            && (*key_states.get(&KeyCode::Up).unwrap_or(&false)
-                || *key_states.get(&KeyCode::Down).unwrap_or(&false)
-                || *key_states.get(&KeyCode::Left).unwrap_or(&false)
-                || *key_states.get(&KeyCode::Right).unwrap_or(&false))
+            && (*key_states.get(&Action::MoveUp).unwrap_or(&false)
+                || *key_states.get(&Action::MoveDown).unwrap_or(&false)
+                || *key_states.get(&Action::MoveLeft).unwrap_or(&false)
+                || *key_states.get(&Action::MoveRight).unwrap_or(&false))
         {
             self.dismiss_message();
         }
 
         if self.show_message {
-            if self.message_animation_start_time.elapsed() >= self.message_animation_interval {
-                let current_len = self.animated_message_content.chars().count();
-                if current_len < self.message.chars().count() {
-                    let next_char_index = self.animated_message_content.chars().count();
-                    self.animated_message_content
-                        .push(self.message.chars().nth(next_char_index).unwrap());
-
-                    self.message_animation_interval =
-                        Duration::from_millis(thread_rng().gen_range(20..=50));
-                    self.message_animation_start_time = Instant::now();
-                } else {
-                    self.message_animation_finished = true;
-                }
-            }
+            self.sync_message_typewriter();
+            self.message_typewriter
+                .update(self.settings.text_speed.scale(delta_time));
         } else {
-            self.animated_message_content.clear();
-            self.previous_chars_shown = 0;
+            self.message_typewriter.clear();
         }
 
-        if self.animated_message_content.len() == self.message.len() {
-            self.message_animation_finished = true;
-        } else {
-            self.message_animation_finished = false;
-        }
+        self.message_animation_finished = self.message_typewriter.is_finished();
 
         let mut context = PlayerUpdateContext {
             current_map_row: &mut self.current_map_row,
@@ -440,11 +942,12 @@ impl GameState {
             debug_mode: self.debug_mode,
             message: &mut self.message,
             show_message: &mut self.show_message,
-            animated_message_content: &mut self.animated_message_content,
-            message_animation_start_time: &mut self.message_animation_start_time,
+            message_typewriter: &mut self.message_typewriter,
             wall_history: &mut self.wall_history,
             history_index: &mut self.history_index,
             is_drawing_select_box: self.is_drawing_select_box,
+            walk_speed: self.settings.walk_speed,
+            now: self.clock.now(),
             block_player_movement_on_message: &mut self.block_player_movement_on_message,
         };
 
@@ -455,6 +958,7 @@ impl GameState {
             && self.teleport_state == TeleportState::None
             && !self.is_placing_sprite
             && !self.is_fading_in_from_intro
+            && self.script.is_none()
         {
             self.player.update(&mut context, key_states, delta_time);
 
@@ -501,7 +1005,7 @@ impl GameState {
                         {
                             self.just_teleported = false;
                             self.last_teleport_destination_box_id = None;
-                            self.teleport_cooldown_timer = Some(Instant::now());
+                            self.teleport_cooldown_timer = Some(self.clock.now());
                         }
                     } else {
                         self.just_teleported = false;
@@ -516,8 +1020,7 @@ impl GameState {
             }
         }
 
-        let (_player_sprite_content, player_sprite_width, player_sprite_height) =
-            self.player.get_sprite_content();
+        let (player_sprite_width, player_sprite_height) = self.player.sprite_size();
 
         let player_center_x = self.player.x + (player_sprite_width as f32) / 2.0;
         let player_center_y = self.player.y + (player_sprite_height as f32) / 2.0;
@@ -561,107 +1064,115 @@ impl GameState {
 
         let mut teleport_destination: Option<(u16, u16, i32, i32, String, u32)> = None;
         let mut interacting_with_box_this_frame = false;
-        if self.teleport_state == TeleportState::None {
-            if let Some(current_map) = self
+        if self.teleport_state == TeleportState::None
+            && self.script.is_none()
+            && let Some(current_map) = self
                 .loaded_maps
                 .get(&(self.current_map_row, self.current_map_col))
-            {
-                for select_box in &current_map.select_object_boxes {
-                    if select_box.to_rect().intersects(player_interaction_rect) {
-                        interacting_with_box_this_frame = true;
-
-                        if self.recently_teleported_from_box_id == Some(select_box.id) {
-                            if select_box.to_rect().intersects(player_collision_rect) {
-                                if select_box.events.iter().any(|e| {
-                                    matches!(e, crate::game::map::Event::TeleportPlayer { .. })
-                                }) {
-                                    // if we just teleported in a tp box, we don't tp, until we get out
-                                    // of it :3
-                                }
-                            }
-                            continue;
+        {
+            for select_box in &current_map.select_object_boxes {
+                if !select_box.is_active(&self.flags) {
+                    continue;
+                }
+                if select_box.to_rect().intersects(player_interaction_rect) {
+                    interacting_with_box_this_frame = true;
+
+                    if self.recently_teleported_from_box_id == Some(select_box.id) {
+                        if select_box.to_rect().intersects(player_collision_rect)
+                            && select_box.events.iter().any(|e| {
+                                matches!(e, crate::game::map::Event::TeleportPlayer { .. })
+                            })
+                        {
+                            // if we just teleported in a tp box, we don't tp, until we get out
+                            // of it :3
                         }
+                        continue;
+                    }
 
-                        if self.current_interaction_box_id == Some(select_box.id) {
-                        } else if !select_box.messages.is_empty() {
-                            self.current_interaction_box_id = Some(select_box.id);
-                            self.current_message_index = 0;
-                        }
+                    if self.current_interaction_box_id == Some(select_box.id) {
+                    } else if !select_box.messages.is_empty() || select_box.has_script() {
+                        self.current_interaction_box_id = Some(select_box.id);
+                        self.current_message_index = 0;
                     }
+                }
 
-                    if select_box.to_rect().intersects(player_collision_rect) {
-                        if self.just_teleported {
-                            continue;
-                        }
-                        if let Some(timer) = self.teleport_cooldown_timer {
-                            if timer.elapsed() < crate::game::config::TELEPORT_COOLDOWN_DURATION {
-                                continue;
+                if select_box.to_rect().intersects(player_collision_rect) {
+                    if self.just_teleported {
+                        continue;
+                    }
+                    if let Some(timer) = self.teleport_cooldown_timer
+                        && self.clock.since(timer) < crate::game::config::TELEPORT_COOLDOWN_DURATION
+                    {
+                        continue;
+                    }
+                    if self.recently_teleported_from_box_id == Some(select_box.id) {
+                        continue;
+                    }
+
+                    if let Some(crate::game::map::Event::TeleportPlayer {
+                        map_row,
+                        map_col,
+                        dest_x,
+                        dest_y,
+                    }) = select_box.teleport()
+                    {
+                        let new_map_name = format!("map_{}_{}", map_row, map_col);
+                        let new_map_key = (*map_row, *map_col);
+                        let mut loaded_map: Option<Map> = None;
+                        let mut map_load_failed = false;
+                        if !self.loaded_maps.contains_key(&new_map_key) {
+                            if let Ok(map) = crate::game::map::Map::load(&new_map_name) {
+                                loaded_map = Some(map);
+                            } else {
+                                self.message = format!("Failed to load map: {}", new_map_name);
+                                self.show_message = true;
+                                self.message_typewriter.clear();
+                                map_load_failed = true;
                             }
                         }
-                        if self.recently_teleported_from_box_id == Some(select_box.id) {
-                            continue;
-                        }
-
-                        for event in &select_box.events {
-                            match event {
-                                crate::game::map::Event::TeleportPlayer {
-                                    map_row,
-                                    map_col,
-                                    dest_x,
-                                    dest_y,
-                                } => {
-                                    let new_map_name = format!("map_{}_{}", map_row, map_col);
-                                    let new_map_key = (*map_row, *map_col);
-                                    let mut loaded_map: Option<Map> = None;
-                                    if !self.loaded_maps.contains_key(&new_map_key) {
-                                        if let Ok(map) = crate::game::map::Map::load(&new_map_name)
-                                        {
-                                            loaded_map = Some(map);
-                                        } else {
-                                            self.message =
-                                                format!("Failed to load map: {}", new_map_name);
-                                            self.show_message = true;
-                                            self.message_animation_start_time = Instant::now();
-                                            self.animated_message_content.clear();
-                                            break;
-                                        }
-                                    }
 
-                                    let map_is_available =
-                                        self.loaded_maps.contains_key(&new_map_key)
-                                            || loaded_map.is_some();
+                        let map_is_available = !map_load_failed
+                            && (self.loaded_maps.contains_key(&new_map_key)
+                                || loaded_map.is_some());
 
-                                    if let Some(map) = loaded_map {
-                                        map_to_insert_after_loop = Some((new_map_key, map));
-                                    }
-
-                                    if map_is_available {
-                                        teleport_destination = Some((
-                                            *dest_x as u16,
-                                            *dest_y as u16,
-                                            *map_row,
-                                            *map_col,
-                                            new_map_name,
-                                            select_box.id,
-                                        ));
-                                    }
-                                    break;
-                                }
-                            }
+                        if let Some(map) = loaded_map {
+                            map_to_insert_after_loop = Some((new_map_key, map));
                         }
-                        if teleport_destination.is_some() {
-                            break;
+
+                        if map_is_available {
+                            teleport_destination = Some((
+                                *dest_x as u16,
+                                *dest_y as u16,
+                                *map_row,
+                                *map_col,
+                                new_map_name,
+                                select_box.id,
+                            ));
                         }
                     }
+                    if teleport_destination.is_some() {
+                        break;
+                    }
                 }
+            }
 
-                for battle_zone in &current_map.battle_zones {
-                    if battle_zone.to_rect().intersects(player_collision_rect) {
-                        self.is_flickering = true;
-                        self.flicker_count = 10;
-                        audio.play_enemy_encounter_sound();
-                    }
+            let touched_battle_zone = current_map
+                .battle_zones
+                .iter()
+                .find(|zone| {
+                    zone.is_active(&self.flags) && zone.to_rect().intersects(player_collision_rect)
+                })
+                .map(|zone| (zone.id, zone.enemy_id.clone(), zone.conversation.clone()));
+            match touched_battle_zone {
+                // you have to step out of a zone before it can start another battle
+                Some((zone_id, enemy_id, conversation))
+                    if self.recently_fought_zone_id != Some(zone_id) =>
+                {
+                    self.recently_fought_zone_id = Some(zone_id);
+                    self.start_battle(&enemy_id, conversation, audio);
                 }
+                Some(_) => {}
+                None => self.recently_fought_zone_id = None,
             }
         }
 
@@ -673,14 +1184,14 @@ impl GameState {
         if teleport_destination.is_some() && self.teleport_state == TeleportState::None {
             self.pending_teleport_destination = teleport_destination;
             self.teleport_state = TeleportState::FadingOut;
-            self.teleport_transition_timer = Some(Instant::now());
+            self.teleport_transition_timer = Some(self.clock.now());
         }
 
         match self.teleport_state {
             TeleportState::FadingOut => {
                 if let Some(timer) = self.teleport_transition_timer {
-                    let elapsed = timer.elapsed();
-                    let fade_duration = Duration::from_millis(500);
+                    let elapsed = self.clock.since(timer);
+                    let fade_duration = TELEPORT_FADE_OUT_DURATION;
 
                     if elapsed >= fade_duration {
                         self.deltarune.level = 100;
@@ -700,6 +1211,7 @@ impl GameState {
                             self.current_map_row = map_row;
                             self.current_map_col = map_col;
                             self.current_map_name = new_map_name;
+                            self.music_override = None;
 
                             let dest_map_key = (self.current_map_row, self.current_map_col);
 
@@ -743,7 +1255,7 @@ impl GameState {
                             self.last_teleport_destination_box_id = Some(box_id);
                         }
                         self.teleport_state = TeleportState::FadingIn;
-                        self.teleport_transition_timer = Some(Instant::now());
+                        self.teleport_transition_timer = Some(self.clock.now());
                     } else {
                         let progress = elapsed.as_secs_f32() / fade_duration.as_secs_f32();
                         self.deltarune.level = (progress * 100.0).min(100.0) as u8;
@@ -752,8 +1264,8 @@ impl GameState {
             }
             TeleportState::FadingIn => {
                 if let Some(timer) = self.teleport_transition_timer {
-                    let elapsed = timer.elapsed();
-                    let fade_duration = Duration::from_millis(750);
+                    let elapsed = self.clock.since(timer);
+                    let fade_duration = TELEPORT_FADE_IN_DURATION;
 
                     if elapsed >= fade_duration {
                         self.deltarune.level = 0;
@@ -768,13 +1280,13 @@ impl GameState {
             TeleportState::None => {}
             TeleportState::FadingOutToThankYou => {
                 if let Some(timer) = self.teleport_transition_timer {
-                    let elapsed = timer.elapsed();
+                    let elapsed = self.clock.since(timer);
                     let fade_duration = Duration::from_millis(500);
 
                     if elapsed >= fade_duration {
                         self.deltarune.level = 100;
                         self.teleport_state = TeleportState::ThankYouScreen;
-                        self.teleport_transition_timer = Some(Instant::now());
+                        self.teleport_transition_timer = Some(self.clock.now());
                     } else {
                         let progress = elapsed.as_secs_f32() / fade_duration.as_secs_f32();
                         self.deltarune.level = (progress * 100.0).min(100.0) as u8;
@@ -782,18 +1294,18 @@ impl GameState {
                 }
             }
             TeleportState::ThankYouScreen => {
-                if let Some(timer) = self.teleport_transition_timer {
-                    if self.deltarune.level > 0 {
-                        let elapsed = timer.elapsed();
-                        let fade_duration = Duration::from_millis(500);
-
-                        if elapsed >= fade_duration {
-                            self.deltarune.level = 0;
-                            self.teleport_transition_timer = None;
-                        } else {
-                            let progress = elapsed.as_secs_f32() / fade_duration.as_secs_f32();
-                            self.deltarune.level = (100.0 - (progress * 100.0)).max(0.0_f32) as u8;
-                        }
+                if let Some(timer) = self.teleport_transition_timer
+                    && self.deltarune.level > 0
+                {
+                    let elapsed = self.clock.since(timer);
+                    let fade_duration = Duration::from_millis(500);
+
+                    if elapsed >= fade_duration {
+                        self.deltarune.level = 0;
+                        self.teleport_transition_timer = None;
+                    } else {
+                        let progress = elapsed.as_secs_f32() / fade_duration.as_secs_f32();
+                        self.deltarune.level = (100.0 - (progress * 100.0)).max(0.0_f32) as u8;
                     }
                 }
             }
@@ -845,19 +1357,21 @@ impl GameState {
 
     pub fn update_intro(
         &mut self,
-        _key_states: &HashMap<KeyCode, bool>,
+        _key_states: &HashMap<Action, bool>,
+        delta_time: Duration,
         audio: &mut crate::audio::Audio,
     ) {
+        let clock = self.clock;
         let intro = &mut self.intro_state;
 
         if intro.is_fading_out {
             let fade_duration = Duration::from_secs(1);
             if let Some(timer) = intro.fade_out_timer {
-                let elapsed = timer.elapsed();
+                let elapsed = clock.since(timer);
                 if elapsed >= fade_duration {
                     self.deltarune.level = 100;
                     self.intro_active = false;
-                    self.is_fading_in_from_intro = true;
+                    self.open_slot_picker(SlotPickerMode::Title);
                 } else {
                     let progress = elapsed.as_secs_f32() / fade_duration.as_secs_f32();
                     self.deltarune.level = (progress * 100.0).min(100.0) as u8;
@@ -868,53 +1382,50 @@ impl GameState {
 
         if intro.current_frame_index >= intro.frames.len() {
             intro.is_fading_out = true;
-            intro.fade_out_timer = Some(Instant::now());
+            intro.fade_out_timer = Some(clock.now());
             return;
         }
 
         let current_frame = &intro.frames[intro.current_frame_index];
-        let current_text = &current_frame.texts[intro.current_text_index];
-
-        if !intro.text_animation_finished {
-            if intro.text_animation_timer.elapsed() >= intro.text_animation_interval {
-                let current_len = intro.animated_text.chars().count();
-                if current_len < current_text.chars().count() {
-                    intro
-                        .animated_text
-                        .push(current_text.chars().nth(current_len).unwrap());
-                    audio.play_text_sound();
-                    intro.text_animation_timer = Instant::now();
-                    intro.text_animation_interval =
-                        Duration::from_millis(thread_rng().gen_range(70..=120));
-                } else {
-                    intro.text_animation_finished = true;
-                    intro.post_text_delay_timer = Some(Instant::now());
-                }
-            }
+
+        if intro
+            .typewriter
+            .update(self.settings.text_speed.scale(delta_time))
+            > 0
+            && let Some(typed) = intro.typewriter.last_char()
+            && let Err(e) = audio.play_voice(&Voice::default(), typed, self.clock.now())
+        {
+            self.sound_error = Some(format!("Failed to play voice: {}", e));
+        }
+        // nobody presses anything during the intro, a [wait_input] just waits like the end does
+        if intro.post_text_delay_timer.is_none()
+            && (intro.typewriter.is_finished() || intro.typewriter.is_waiting_for_input())
+        {
+            intro.post_text_delay_timer = Some(clock.now());
         }
 
-        if intro.text_animation_finished {
-            if let Some(timer) = intro.post_text_delay_timer {
-                if timer.elapsed() >= Duration::from_millis(1200) {
-                    if intro.current_text_index < current_frame.texts.len() - 1 {
-                        intro.current_text_index += 1;
-                        intro.animated_text.clear();
-                        intro.text_animation_finished = false;
-                        intro.post_text_delay_timer = None;
-                    } else {
-                        intro.current_frame_index += 1;
-                        intro.current_text_index = 0;
-                        intro.animated_text.clear();
-                        intro.text_animation_finished = false;
-                        intro.post_text_delay_timer = None;
-
-                        if intro.current_frame_index >= intro.frames.len() {
-                            intro.is_fading_out = true;
-                            intro.fade_out_timer = Some(Instant::now());
-                        }
-                    }
+        if let Some(timer) = intro.post_text_delay_timer
+            && clock.since(timer) >= Duration::from_millis(1200)
+        {
+            intro.post_text_delay_timer = None;
+            if intro.typewriter.is_waiting_for_input() {
+                intro.typewriter.skip();
+                return;
+            }
+            if intro.current_text_index < current_frame.texts.len() - 1 {
+                intro.current_text_index += 1;
+            } else {
+                intro.current_frame_index += 1;
+                intro.current_text_index = 0;
+
+                if intro.current_frame_index >= intro.frames.len() {
+                    intro.is_fading_out = true;
+                    intro.fade_out_timer = Some(clock.now());
+                    return;
                 }
             }
+            let text = &intro.frames[intro.current_frame_index].texts[intro.current_text_index];
+            intro.typewriter = Typewriter::new(text, Pace::INTRO);
         }
     }
 
@@ -945,25 +1456,34 @@ impl GameState {
             if let Err(e) = map.save_data() {
                 self.message = format!("Failed to save map data: {}", e);
                 self.show_message = true;
-                self.message_animation_start_time = Instant::now();
-                self.animated_message_content.clear();
+                self.message_typewriter.clear();
                 self.message_animation_finished = false;
             } else {
                 self.message = "Spawn point saved.".to_string();
                 self.show_message = true;
-                self.message_animation_start_time = Instant::now();
-                self.animated_message_content.clear();
+                self.message_typewriter.clear();
                 self.message_animation_finished = false;
             }
         }
     }
 
     pub fn skip_message_animation(&mut self) {
-        self.animated_message_content = self.message.clone();
-        self.message_animation_finished = true;
+        self.sync_message_typewriter();
+        self.message_typewriter.skip();
+        self.message_animation_finished = self.message_typewriter.is_finished();
+    }
+
+    // `message` is set all over the place, the typewriter catches up with it here
+    fn sync_message_typewriter(&mut self) {
+        if self.message_typewriter.source() != self.message {
+            self.message_typewriter = Typewriter::new(&self.message, Pace::MESSAGE);
+        }
     }
 
     pub fn darken_text(&self, original_text: Text<'static>, darkness_level: u8) -> Text<'static> {
+        if !self.settings.fade_effects {
+            return original_text;
+        }
         let mut new_text = Text::default();
         for line in original_text.lines {
             let mut new_spans = Vec::new();
@@ -986,11 +1506,24 @@ impl GameState {
         new_text
     }
 
-    pub fn get_combined_map_text(&self, _frame_size: Rect, deltarune_level: u8) -> Text<'static> {
+    /// The rows of the current map the camera sees, starting at `camera_y`.
+    pub fn get_combined_map_text(&self, frame_size: Rect, deltarune_level: u8) -> Text<'static> {
         let current_map_key = (self.current_map_row, self.current_map_col);
         if let Some(map) = self.loaded_maps.get(&current_map_key) {
-            let original_text = map.ansi_sprite.as_bytes().into_text().unwrap();
-            self.darken_text(original_text, deltarune_level)
+            let sprite = match map.sprite() {
+                Ok(sprite) => sprite,
+                Err(e) => return Text::raw(e.to_string()),
+            };
+            // only what's on screen gets darkened and drawn, however big the map is
+            let visible_lines: Vec<Line<'static>> = sprite
+                .text
+                .lines
+                .iter()
+                .skip(self.camera_y as usize)
+                .take(frame_size.height as usize)
+                .cloned()
+                .collect();
+            self.darken_text(Text::from(visible_lines), deltarune_level)
         } else {
             Text::default()
         }
@@ -1008,4 +1541,22 @@ fn darken_color(color: Color, darkness_level: u8) -> Color {
         ),
         _ => color,
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_run_without_a_slot_asks_for_one_instead_of_using_slot_0() {
+        let mut game_state = GameState::from_map(Map::load("map_0_0").unwrap());
+        assert_eq!(game_state.current_save_slot, None);
+        assert!(game_state.save_game_state().is_err());
+
+        game_state.start_game_over();
+        game_state.continue_from_last_save().unwrap();
+        let picker = game_state.slot_picker.as_ref().unwrap();
+        assert_eq!(picker.mode, SlotPickerMode::Title);
+        assert_eq!(game_state.current_save_slot, None);
+    }
+}
@@ -0,0 +1,441 @@
+use rand::Rng;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How fast text types out when its markup doesn't say otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pace {
+    pub min_ms: u64,
+    pub max_ms: u64,
+    // spaces, commas and dots hold the text for a moment
+    pub punctuation: bool,
+}
+
+impl Pace {
+    pub const MESSAGE: Pace = Pace {
+        min_ms: 20,
+        max_ms: 50,
+        punctuation: false,
+    };
+    pub const DIALOGUE: Pace = Pace {
+        min_ms: 50,
+        max_ms: 50,
+        punctuation: false,
+    };
+    pub const BATTLE: Pace = Pace {
+        min_ms: 10,
+        max_ms: 30,
+        punctuation: true,
+    };
+    pub const INTRO: Pace = Pace {
+        min_ms: 70,
+        max_ms: 120,
+        punctuation: false,
+    };
+}
+
+impl Default for Pace {
+    fn default() -> Self {
+        Pace::MESSAGE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    Slow,
+    Normal,
+    Fast,
+    // a fixed delay per character, 0 prints the rest at once
+    Millis(u64),
+}
+
+/// One piece of marked up text, see [`parse`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Char(char),
+    Pause(Duration),
+    Speed(Speed),
+    Color(Color),
+    EndColor,
+    Shake,
+    EndShake,
+    WaitInput,
+}
+
+/// Splits text into characters and control codes:
+/// `[pause=500]`, `[speed=slow|normal|fast|<ms>]`, `[color=red]...[/color]`,
+/// `[shake]...[/shake]` and `[wait_input]`. Anything else in brackets is kept as text.
+pub fn parse(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '['
+            && let Some(end) = rest.find(']')
+            && let Some(token) = parse_tag(&rest[1..end])
+        {
+            tokens.push(token);
+            rest = &rest[end + 1..];
+            continue;
+        }
+        tokens.push(Token::Char(c));
+        rest = &rest[c.len_utf8()..];
+    }
+    tokens
+}
+
+fn parse_tag(tag: &str) -> Option<Token> {
+    let (name, value) = match tag.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (tag.trim(), None),
+    };
+    match (name, value) {
+        ("pause", Some(ms)) => ms
+            .parse()
+            .ok()
+            .map(|ms| Token::Pause(Duration::from_millis(ms))),
+        ("speed", Some("slow")) => Some(Token::Speed(Speed::Slow)),
+        ("speed", Some("normal")) => Some(Token::Speed(Speed::Normal)),
+        ("speed", Some("fast")) => Some(Token::Speed(Speed::Fast)),
+        ("speed", Some(ms)) => ms.parse().ok().map(|ms| Token::Speed(Speed::Millis(ms))),
+        ("color", Some(color)) => Color::from_str(color).ok().map(Token::Color),
+        ("/color", None) => Some(Token::EndColor),
+        ("shake", None) => Some(Token::Shake),
+        ("/shake", None) => Some(Token::EndShake),
+        ("wait_input", None) => Some(Token::WaitInput),
+        _ => None,
+    }
+}
+
+/// The text with its markup taken out.
+pub fn plain_text(text: &str) -> String {
+    parse(text)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Char(c) => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A typed out character and how it should look.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    pub ch: char,
+    pub color: Option<Color>,
+    pub shake: bool,
+}
+
+impl Glyph {
+    fn same_look(&self, other: &Glyph) -> bool {
+        self.color == other.color && self.shake == other.shake
+    }
+
+    pub fn style(&self) -> Style {
+        match self.color {
+            Some(color) => Style::default().fg(color),
+            None => Style::default(),
+        }
+    }
+}
+
+/// Types marked up text out one character at a time. Used by overworld messages,
+/// conversations, the intro and battle narration.
+#[derive(Debug, Clone, Default)]
+pub struct Typewriter {
+    source: String,
+    tokens: Vec<Token>,
+    next_token: usize,
+    glyphs: Vec<Glyph>,
+    pace: Pace,
+    speed: Option<Speed>,
+    colors: Vec<Color>,
+    shaking: bool,
+    // time left before the next token
+    delay: Duration,
+    waiting_for_input: bool,
+}
+
+impl Typewriter {
+    pub fn new(text: &str, pace: Pace) -> Self {
+        Typewriter {
+            source: text.to_string(),
+            tokens: parse(text),
+            pace,
+            ..Default::default()
+        }
+    }
+
+    /// The text as it was given, markup included.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Forgets the text, nothing is shown until a new one is given.
+    pub fn clear(&mut self) {
+        *self = Typewriter {
+            pace: self.pace,
+            ..Default::default()
+        };
+    }
+
+    /// Types whatever is due after `delta_time`. Returns how many characters appeared.
+    pub fn update(&mut self, delta_time: Duration) -> usize {
+        let mut budget = delta_time;
+        let mut typed = 0;
+        while !self.waiting_for_input && self.next_token < self.tokens.len() {
+            if self.delay > budget {
+                self.delay -= budget;
+                break;
+            }
+            budget -= self.delay;
+            self.delay = Duration::ZERO;
+            if self.step() {
+                typed += 1;
+            }
+        }
+        typed
+    }
+
+    /// Confirm while typing: shows everything up to the next `[wait_input]`,
+    /// or moves past it when the text is already stopped there.
+    pub fn skip(&mut self) {
+        if self.waiting_for_input {
+            self.waiting_for_input = false;
+            return;
+        }
+        while !self.waiting_for_input && self.next_token < self.tokens.len() {
+            self.step();
+        }
+        self.delay = Duration::ZERO;
+    }
+
+    pub fn is_waiting_for_input(&self) -> bool {
+        self.waiting_for_input
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.waiting_for_input && self.next_token >= self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    pub fn glyphs(&self) -> &[Glyph] {
+        &self.glyphs
+    }
+
+    /// The newest typed character, what a voice blip is picked for.
+    pub fn last_char(&self) -> Option<char> {
+        self.glyphs.last().map(|glyph| glyph.ch)
+    }
+
+    pub fn visible_text(&self) -> String {
+        self.glyphs.iter().map(|glyph| glyph.ch).collect()
+    }
+
+    // runs one token, true when it typed a character
+    fn step(&mut self) -> bool {
+        let token = self.tokens[self.next_token].clone();
+        self.next_token += 1;
+        match token {
+            Token::Char(c) => {
+                self.glyphs.push(Glyph {
+                    ch: c,
+                    color: self.colors.last().copied(),
+                    shake: self.shaking,
+                });
+                self.delay = self.char_delay(c);
+                return true;
+            }
+            Token::Pause(pause) => self.delay = pause,
+            Token::Speed(Speed::Normal) => self.speed = None,
+            Token::Speed(speed) => self.speed = Some(speed),
+            Token::Color(color) => self.colors.push(color),
+            Token::EndColor => {
+                self.colors.pop();
+            }
+            Token::Shake => self.shaking = true,
+            Token::EndShake => self.shaking = false,
+            Token::WaitInput => self.waiting_for_input = true,
+        }
+        false
+    }
+
+    fn char_delay(&self, c: char) -> Duration {
+        if let Some(Speed::Millis(ms)) = self.speed {
+            return Duration::from_millis(ms);
+        }
+        let ms = match c {
+            ' ' if self.pace.punctuation => 100,
+            ',' if self.pace.punctuation => 250,
+            '.' if self.pace.punctuation => {
+                let rest = &self.tokens[self.next_token..];
+                if rest.starts_with(&[Token::Char('.'), Token::Char('.')]) {
+                    400
+                } else {
+                    300
+                }
+            }
+            _ => crate::game::rng::with(|rng| rng.gen_range(self.pace.min_ms..=self.pace.max_ms)),
+        };
+        match self.speed {
+            Some(Speed::Slow) => Duration::from_millis(ms * 2),
+            Some(Speed::Fast) => Duration::from_millis(ms / 3),
+            _ => Duration::from_millis(ms),
+        }
+    }
+}
+
+/// Breaks glyphs into lines of at most `width`, on spaces where it can and on `\n` always.
+pub fn wrap_glyphs(glyphs: &[Glyph], width: u16) -> Vec<Vec<Glyph>> {
+    let width = (width as usize).max(1);
+    let mut lines = Vec::new();
+    for text_line in glyphs.split(|glyph| glyph.ch == '\n') {
+        let mut line: Vec<Glyph> = Vec::new();
+        for (i, word) in text_line.split(|glyph| glyph.ch == ' ').enumerate() {
+            if !line.is_empty() && line.len() + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut line));
+            } else if i > 0 {
+                line.push(Glyph {
+                    ch: ' ',
+                    color: None,
+                    shake: false,
+                });
+            }
+            // words longer than a line get cut wherever they hit the edge
+            for glyph in word {
+                if line.len() >= width {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(*glyph);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Consecutive glyphs that look the same, as (text, look) pairs.
+pub fn runs(glyphs: &[Glyph]) -> Vec<(String, Glyph)> {
+    let mut runs: Vec<(String, Glyph)> = Vec::new();
+    for glyph in glyphs {
+        match runs.last_mut() {
+            Some((text, look)) if look.same_look(glyph) => text.push(glyph.ch),
+            _ => runs.push((glyph.ch.to_string(), *glyph)),
+        }
+    }
+    runs
+}
+
+/// Shaking text jumps a column left or right every frame, all its rows together.
+pub fn jitter(rows: &mut [String]) {
+    let to_right = rand::thread_rng().gen_bool(0.5);
+    for row in rows {
+        if to_right {
+            row.insert(0, ' ');
+        } else {
+            row.push(' ');
+        }
+    }
+}
+
+pub fn to_line(glyphs: &[Glyph]) -> Line<'static> {
+    let spans: Vec<Span<'static>> = runs(glyphs)
+        .into_iter()
+        .map(|(text, look)| {
+            let mut rows = [text];
+            if look.shake {
+                jitter(&mut rows);
+            }
+            let [text] = rows;
+            Span::styled(text, look.style())
+        })
+        .collect();
+    Line::from(spans)
+}
+
+/// Wraps glyphs to `width` and styles them, ready for a Paragraph.
+pub fn to_lines(glyphs: &[Glyph], width: u16) -> Vec<Line<'static>> {
+    wrap_glyphs(glyphs, width)
+        .iter()
+        .map(|line| to_line(line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup_becomes_tokens_and_unknown_tags_stay_text() {
+        let tokens = parse("a[pause=500][color=red]b[/color][nope]");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Char('a'),
+                Token::Pause(Duration::from_millis(500)),
+                Token::Color(Color::Red),
+                Token::Char('b'),
+                Token::EndColor,
+                Token::Char('['),
+                Token::Char('n'),
+                Token::Char('o'),
+                Token::Char('p'),
+                Token::Char('e'),
+                Token::Char(']'),
+            ]
+        );
+        assert_eq!(plain_text("[shake]hey[/shake] [speed=fast]you"), "hey you");
+    }
+
+    #[test]
+    fn typing_waits_for_pauses_and_input() {
+        let mut typewriter =
+            Typewriter::new("[speed=10]ab[pause=100]c[wait_input]d", Pace::MESSAGE);
+        assert_eq!(typewriter.update(Duration::from_millis(15)), 2);
+        assert_eq!(typewriter.update(Duration::from_millis(50)), 0);
+        assert_eq!(typewriter.update(Duration::from_millis(100)), 1);
+        assert!(typewriter.is_waiting_for_input());
+        assert_eq!(typewriter.update(Duration::from_secs(1)), 0);
+
+        typewriter.skip();
+        assert!(!typewriter.is_waiting_for_input());
+        typewriter.skip();
+        assert!(typewriter.is_finished());
+        assert_eq!(typewriter.visible_text(), "abcd");
+    }
+
+    #[test]
+    fn skipping_stops_at_the_next_wait() {
+        let mut typewriter = Typewriter::new("one[wait_input] two", Pace::BATTLE);
+        typewriter.skip();
+        assert_eq!(typewriter.visible_text(), "one");
+        typewriter.skip();
+        typewriter.skip();
+        assert_eq!(typewriter.visible_text(), "one two");
+    }
+
+    #[test]
+    fn colors_and_shake_stick_to_their_glyphs() {
+        let mut typewriter =
+            Typewriter::new("a[color=yellow][shake]b[/shake]c[/color]d", Pace::MESSAGE);
+        typewriter.skip();
+        let looks: Vec<(Option<Color>, bool)> = typewriter
+            .glyphs()
+            .iter()
+            .map(|glyph| (glyph.color, glyph.shake))
+            .collect();
+        assert_eq!(
+            looks,
+            vec![
+                (None, false),
+                (Some(Color::Yellow), true),
+                (Some(Color::Yellow), false),
+                (None, false),
+            ]
+        );
+    }
+}
@@ -13,8 +13,8 @@ pub fn wrap_text_to_width(text: &str, width: u16) -> String {
             }
             // long word handling
             if word.len() as u16 > width {
-                let mut chars = word.chars();
-                while let Some(c) = chars.next() {
+                let chars = word.chars();
+                for c in chars {
                     if current_line_len >= width {
                         result.push('\n');
                         current_line_len = 0;
@@ -32,14 +32,22 @@ pub fn wrap_text_to_width(text: &str, width: u16) -> String {
 
     text.lines()
         .map(|line| {
-            let leading_whitespace: String = line.chars().take_while(|&c| c.is_whitespace()).collect();
+            let leading_whitespace: String =
+                line.chars().take_while(|&c| c.is_whitespace()).collect();
             let trimmed_line = line.trim_start();
             if trimmed_line.is_empty() {
                 return line.to_string();
             }
-            let wrapped_trimmed_line = wrap_line(trimmed_line, width.saturating_sub(leading_whitespace.len() as u16));
-            wrapped_trimmed_line.lines().map(|l| format!("{}{}", leading_whitespace, l)).collect::<Vec<_>>().join("\n")
+            let wrapped_trimmed_line = wrap_line(
+                trimmed_line,
+                width.saturating_sub(leading_whitespace.len() as u16),
+            );
+            wrapped_trimmed_line
+                .lines()
+                .map(|l| format!("{}{}", leading_whitespace, l))
+                .collect::<Vec<_>>()
+                .join("\n")
         })
         .collect::<Vec<_>>()
         .join("\n")
-}
\ No newline at end of file
+}
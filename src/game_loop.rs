@@ -4,65 +4,212 @@ use std::{
     time::{Duration, Instant},
 };
 
-
-use ratatui::{backend::CrosstermBackend, Terminal};
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{Terminal, backend::CrosstermBackend, layout::Rect};
 
 use crate::{
     audio::Audio,
-    game::{config::FRAME_RATE, state::GameState},
-    input, ui,
+    game::{clock::Clock, config::SIMULATION_STEP, keymap::Action, state::GameState},
+    input,
+    replay::Session,
+    ui,
 };
 
+// after a stall (a suspended process, a slow terminal) the game catches up on this many
+// steps at most instead of fast-forwarding through everything it missed
+const MAX_CATCH_UP_STEPS: u32 = 5;
+
+/// Turns wall clock time into a whole number of fixed `step`s, keeping the remainder for
+/// the next frame.
+#[derive(Debug)]
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    last_tick: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(step: Duration, now: Instant) -> Self {
+        FixedTimestep {
+            step,
+            accumulator: Duration::ZERO,
+            last_tick: now,
+        }
+    }
+
+    /// How many steps are due at `now`.
+    pub fn tick(&mut self, now: Instant) -> u32 {
+        self.accumulator += now.saturating_duration_since(self.last_tick);
+        self.last_tick = now;
+        self.accumulator = self.accumulator.min(self.step * MAX_CATCH_UP_STEPS);
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// How long from `now` until the next step is due.
+    pub fn until_next_step(&self, now: Instant) -> Duration {
+        let pending = self.accumulator + now.saturating_duration_since(self.last_tick);
+        self.step.saturating_sub(pending)
+    }
+}
+
+// the pad's presses go after the keyboard's, as the keys they're bound to
+#[cfg(feature = "gamepad")]
+fn with_gamepad_events(
+    mut events: Vec<Event>,
+    gamepad: &mut Option<crate::gamepad::Gamepad>,
+    keymap: &crate::game::keymap::Keymap,
+) -> Vec<Event> {
+    if let Some(gamepad) = gamepad {
+        events.extend(
+            gamepad
+                .poll()
+                .into_iter()
+                .filter_map(|(action, pressed)| input::action_event(action, pressed, keymap)),
+        );
+    }
+    events
+}
+
+/// One update: the clock moves a fixed step and the game follows.
+pub fn step(
+    game_state: &mut GameState,
+    key_states: &HashMap<Action, bool>,
+    frame_size: Rect,
+    audio: &mut Audio,
+) {
+    game_state.clock.advance(SIMULATION_STEP);
+    game_state.update(key_states, frame_size, SIMULATION_STEP, audio);
+    audio.update(SIMULATION_STEP);
+}
+
 pub fn run(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     game_state: &mut GameState,
+    mut audio: Audio,
+    session: &mut Session,
 ) -> io::Result<()> {
-    let mut last_frame_time = Instant::now();
-    let mut audio = Audio::new().unwrap();
+    game_state.set_clock(Clock::stepped());
+    let mut timestep = FixedTimestep::new(SIMULATION_STEP, Instant::now());
+    let mut steps_done: u64 = 0;
+    let mut last_draw: Option<Instant> = None;
+    let mut needs_draw = true;
 
-    let mut key_states: HashMap<crossterm::event::KeyCode, bool> = HashMap::new();
+    let mut key_states: HashMap<Action, bool> = HashMap::new();
+    #[cfg(feature = "gamepad")]
+    let mut gamepad = crate::gamepad::Gamepad::open_default();
 
     loop {
-        let elapsed_time = last_frame_time.elapsed();
-        if elapsed_time >= Duration::from_millis(1000 / FRAME_RATE) {
-            let delta_time = last_frame_time.elapsed();
-            last_frame_time = Instant::now();
+        // waiting on input is the loop's sleep, a key press still gets handled right away
+        let timeout = timestep.until_next_step(Instant::now());
+        let events = input::read_events(timeout)?;
+        #[cfg(feature = "gamepad")]
+        let events = with_gamepad_events(events, &mut gamepad, &game_state.keymap);
 
-            if input::process_inputs(game_state, &mut key_states, &mut audio)? {
-                return Ok(());
+        for event in events {
+            match session {
+                Session::Replay(_) => {
+                    if let Event::Key(key) = event
+                        && key.code == KeyCode::Esc
+                        && key.kind == KeyEventKind::Press
+                    {
+                        return Ok(());
+                    }
+                    if let Event::Resize(..) = event {
+                        game_state.resized = true;
+                    }
+                    continue;
+                }
+                Session::Record(recording) => recording.push(steps_done, &event),
+                Session::Live => {}
             }
-
-            if game_state.esc_hold_dots >= 4 {
+            if input::process_event(event, game_state, &mut key_states, &mut audio)? {
                 return Ok(());
             }
+        }
 
-            let current_frame_size = terminal.size()?;
+        if game_state.esc_hold_dots >= 4 {
+            return Ok(());
+        }
 
-            let game_should_exit = false;
-            game_state.update(
-                &key_states,
-                ratatui::layout::Rect::new(
-                    0,
-                    0,
-                    current_frame_size.width,
-                    current_frame_size.height,
-                ),
-                delta_time,
-                &mut audio,
-            );
-
-            if game_state.resized {
-                terminal.clear()?;
-                game_state.resized = false; // Reset the flag
+        let steps = timestep.tick(Instant::now());
+        if steps > 0 {
+            let current_frame_size = terminal.size()?;
+            let live_frame_size =
+                Rect::new(0, 0, current_frame_size.width, current_frame_size.height);
+            for _ in 0..steps {
+                let mut frame_size = live_frame_size;
+                if let Session::Replay(replay) = session {
+                    if replay.is_finished(steps_done) {
+                        return Ok(());
+                    }
+                    for event in replay.events_before(steps_done) {
+                        if input::process_event(event, game_state, &mut key_states, &mut audio)? {
+                            return Ok(());
+                        }
+                    }
+                    // the size the session was recorded at, not this terminal's
+                    frame_size = replay.frame_size();
+                }
+                step(game_state, &key_states, frame_size, &mut audio);
+                steps_done += 1;
             }
-
-            if game_should_exit {
-                return Ok(());
+            if let Session::Record(recording) = session {
+                recording.steps = steps_done;
             }
+            needs_draw = true;
+        }
+
+        if game_state.resized {
+            terminal.clear()?;
+            game_state.resized = false; // Reset the flag
+            needs_draw = true;
+        }
 
+        let frame_due = last_draw
+            .is_none_or(|last_draw| last_draw.elapsed() >= game_state.settings.frame_duration());
+        if needs_draw && frame_due {
             terminal.draw(|frame| {
                 ui::draw(frame, game_state);
             })?;
+            last_draw = Some(Instant::now());
+            needs_draw = false;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uneven_frames_become_whole_steps() {
+        let start = Instant::now();
+        let step = Duration::from_millis(10);
+        let mut timestep = FixedTimestep::new(step, start);
+
+        assert_eq!(timestep.tick(start + Duration::from_millis(4)), 0);
+        assert_eq!(
+            timestep.until_next_step(start + Duration::from_millis(7)),
+            Duration::from_millis(3)
+        );
+        // 4 + 21 = 25ms: two steps, 5ms carried over
+        assert_eq!(timestep.tick(start + Duration::from_millis(25)), 2);
+        assert_eq!(timestep.tick(start + Duration::from_millis(30)), 1);
+        assert_eq!(
+            timestep.until_next_step(start + Duration::from_millis(30)),
+            step
+        );
+
+        // a long stall only catches up on a few steps
+        assert_eq!(
+            timestep.tick(start + Duration::from_secs(10)),
+            MAX_CATCH_UP_STEPS
+        );
+    }
+}
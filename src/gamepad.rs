@@ -0,0 +1,255 @@
+//! Controllers through the Linux joystick API (`/dev/input/js*`). The pad is turned into the
+//! same `Action`s the keyboard produces, so everything past `input` can't tell them apart.
+
+use crate::game::keymap::Action;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Overrides which device gets opened, e.g. `/dev/input/js1`.
+pub const GAMEPAD_ENV_VAR: &str = "UNDER_TERM_GAMEPAD";
+const DEFAULT_DEVICE: &str = "/dev/input/js0";
+
+const JS_EVENT_BUTTON: u8 = 0x01;
+const JS_EVENT_AXIS: u8 = 0x02;
+// set on the burst of events describing the pad's state right after it's opened
+const JS_EVENT_INIT: u8 = 0x80;
+
+// how far a stick has to lean before it counts as a direction
+const STICK_DEADZONE: i16 = 16_000;
+
+/// One `struct js_event` from the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsEvent {
+    pub time_ms: u32,
+    pub value: i16,
+    pub kind: u8,
+    pub number: u8,
+}
+
+impl JsEvent {
+    pub const SIZE: usize = 8;
+
+    pub fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        JsEvent {
+            time_ms: u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            value: i16::from_ne_bytes([bytes[4], bytes[5]]),
+            kind: bytes[6],
+            number: bytes[7],
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.time_ms.to_ne_bytes());
+        bytes[4..6].copy_from_slice(&self.value.to_ne_bytes());
+        bytes[6] = self.kind;
+        bytes[7] = self.number;
+        bytes
+    }
+
+    /// `Ok(None)` once the stream runs out.
+    pub fn read(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut bytes = [0; Self::SIZE];
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(Self::from_bytes(bytes))),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Turns raw pad events into action presses and releases. Sticks and the d-pad both move,
+/// A confirms, B cancels and X/Y open the menu (xpad button numbering).
+#[derive(Debug, Default)]
+pub struct GamepadMapper {
+    // which directions each axis pair is holding, so releases only go out for held ones
+    held: Vec<Action>,
+}
+
+impl GamepadMapper {
+    pub fn feed(&mut self, event: JsEvent) -> Vec<(Action, bool)> {
+        let kind = event.kind & !JS_EVENT_INIT;
+        if kind == JS_EVENT_BUTTON {
+            // the init burst reports buttons that are already down, they'd fire out of nowhere
+            if event.kind & JS_EVENT_INIT != 0 {
+                return Vec::new();
+            }
+            let action = match event.number {
+                0 => Action::Confirm,
+                1 => Action::Cancel,
+                2 | 3 => Action::Menu,
+                _ => return Vec::new(),
+            };
+            return vec![(action, event.value != 0)];
+        }
+        if kind != JS_EVENT_AXIS {
+            return Vec::new();
+        }
+        // 0/1 is the left stick, 6/7 the d-pad on most pads
+        let (negative, positive) = match event.number {
+            0 | 6 => (Action::MoveLeft, Action::MoveRight),
+            1 | 7 => (Action::MoveUp, Action::MoveDown),
+            _ => return Vec::new(),
+        };
+        let wanted = if event.value <= -STICK_DEADZONE {
+            Some(negative)
+        } else if event.value >= STICK_DEADZONE {
+            Some(positive)
+        } else {
+            None
+        };
+
+        let mut changes = Vec::new();
+        for action in [negative, positive] {
+            let was_held = self.held.contains(&action);
+            let is_held = wanted == Some(action);
+            if was_held && !is_held {
+                self.held.retain(|held| *held != action);
+                changes.push((action, false));
+            } else if is_held && !was_held {
+                self.held.push(action);
+                changes.push((action, true));
+            }
+        }
+        changes
+    }
+}
+
+/// Every action a recorded event stream turns into, in order.
+pub fn replay(mut reader: impl Read) -> io::Result<Vec<(Action, bool)>> {
+    let mut mapper = GamepadMapper::default();
+    let mut actions = Vec::new();
+    while let Some(event) = JsEvent::read(&mut reader)? {
+        actions.extend(mapper.feed(event));
+    }
+    Ok(actions)
+}
+
+/// A pad being read on its own thread, so a quiet device never holds up a frame.
+pub struct Gamepad {
+    events: Receiver<JsEvent>,
+    mapper: GamepadMapper,
+}
+
+impl Gamepad {
+    /// `UNDER_TERM_GAMEPAD` or the first joystick. No pad plugged in is not an error.
+    pub fn open_default() -> Option<Self> {
+        let path = std::env::var(GAMEPAD_ENV_VAR).unwrap_or_else(|_| DEFAULT_DEVICE.to_string());
+        Self::open(path).ok()
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_reader(File::open(path)?))
+    }
+
+    /// Reads events from anything, a device or a recording.
+    pub fn from_reader(mut reader: impl Read + Send + 'static) -> Self {
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(Some(event)) = JsEvent::read(&mut reader) {
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        Gamepad {
+            events,
+            mapper: GamepadMapper::default(),
+        }
+    }
+
+    /// Whatever the pad did since the last call.
+    pub fn poll(&mut self) -> Vec<(Action, bool)> {
+        let mut actions = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            actions.extend(self.mapper.feed(event));
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn recording(events: &[(u8, u8, i16)]) -> Vec<u8> {
+        events
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &(kind, number, value))| {
+                JsEvent {
+                    time_ms: i as u32 * 16,
+                    value,
+                    kind,
+                    number,
+                }
+                .to_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn buttons_and_dpad_become_actions() {
+        let stream = recording(&[
+            // init burst: A already down, sticks centered
+            (JS_EVENT_BUTTON | JS_EVENT_INIT, 0, 1),
+            (JS_EVENT_AXIS | JS_EVENT_INIT, 0, 0),
+            (JS_EVENT_AXIS, 7, -32767),
+            (JS_EVENT_AXIS, 7, 0),
+            (JS_EVENT_BUTTON, 0, 1),
+            (JS_EVENT_BUTTON, 0, 0),
+            (JS_EVENT_BUTTON, 1, 1),
+            (JS_EVENT_BUTTON, 3, 1),
+        ]);
+        assert_eq!(
+            replay(Cursor::new(stream)).unwrap(),
+            vec![
+                (Action::MoveUp, true),
+                (Action::MoveUp, false),
+                (Action::Confirm, true),
+                (Action::Confirm, false),
+                (Action::Cancel, true),
+                (Action::Menu, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn stick_needs_to_leave_the_deadzone_and_can_flip_sides() {
+        let stream = recording(&[
+            (JS_EVENT_AXIS, 0, 3000),
+            (JS_EVENT_AXIS, 0, 30000),
+            (JS_EVENT_AXIS, 0, 31000),
+            (JS_EVENT_AXIS, 0, -30000),
+            (JS_EVENT_AXIS, 0, 100),
+        ]);
+        assert_eq!(
+            replay(Cursor::new(stream)).unwrap(),
+            vec![
+                (Action::MoveRight, true),
+                (Action::MoveLeft, true),
+                (Action::MoveRight, false),
+                (Action::MoveLeft, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn threaded_reader_delivers_the_recording() {
+        let stream = recording(&[(JS_EVENT_BUTTON, 2, 1), (JS_EVENT_BUTTON, 2, 0)]);
+        let mut gamepad = Gamepad::from_reader(Cursor::new(stream));
+        let mut actions = Vec::new();
+        for _ in 0..100 {
+            actions.extend(gamepad.poll());
+            if actions.len() == 2 {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(actions, vec![(Action::Menu, true), (Action::Menu, false)]);
+    }
+}
@@ -0,0 +1,391 @@
+//! Runs the overworld without a terminal or a sound device: a `GameState` on a stepped clock,
+//! fed scripted presses down the same path as the keyboard, one fixed step at a time.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use ratatui::layout::Rect;
+
+use crate::audio::Audio;
+use crate::game::clock::Clock;
+use crate::game::dialogue::DialogueGraph;
+use crate::game::keymap::{Action, Keymap};
+use crate::game::map::Map;
+use crate::game::settings::Settings;
+use crate::game::state::GameState;
+use crate::input;
+
+/// The same fixed step the game loop updates with.
+pub const DEFAULT_STEP: Duration = crate::game::config::SIMULATION_STEP;
+
+/// One entry of an input script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Press(Action),
+    Release(Action),
+    // keeps updating, whatever is pressed stays pressed
+    Wait(Duration),
+}
+
+pub struct TestHarness {
+    pub game_state: GameState,
+    pub audio: Audio,
+    pub key_states: HashMap<Action, bool>,
+    pub frame_size: Rect,
+    pub step: Duration,
+    // set once the input asked the game to quit
+    pub quit: bool,
+}
+
+impl TestHarness {
+    /// A new game on `map`, past the intro, with default settings and keys. The map's name
+    /// has to look like `map_<row>_<col>`, and its width and height bound where the player
+    /// can walk.
+    pub fn new(map: Map) -> Self {
+        let mut game_state = GameState::from_map(map);
+        game_state.intro_active = false;
+        game_state.settings = Settings::default();
+        game_state.keymap = Keymap::default();
+        game_state.set_clock(Clock::stepped());
+        TestHarness {
+            game_state,
+            audio: Audio::null(),
+            key_states: HashMap::new(),
+            frame_size: Rect::new(0, 0, 80, 24),
+            step: DEFAULT_STEP,
+            quit: false,
+        }
+    }
+
+    /// Makes another map available, so teleports into it don't go looking for files.
+    pub fn with_map(mut self, map: Map) -> Self {
+        let parts: Vec<&str> = map.name.split('_').collect();
+        let row = parts.get(1).and_then(|row| row.parse().ok()).unwrap_or(0);
+        let col = parts.get(2).and_then(|col| col.parse().ok()).unwrap_or(0);
+        self.game_state.loaded_maps.insert((row, col), map);
+        self
+    }
+
+    /// Makes a conversation available under `id`, in place of `assets/dialogues/<id>.json`.
+    pub fn with_conversation(mut self, id: &str, graph: DialogueGraph) -> Self {
+        self.game_state.conversations.insert(id, graph);
+        self
+    }
+
+    /// Goes through the input code like a key bound to `action` going down.
+    pub fn press(&mut self, action: Action) {
+        self.send(action, true);
+    }
+
+    pub fn release(&mut self, action: Action) {
+        self.send(action, false);
+    }
+
+    /// Press, one step, release.
+    pub fn tap(&mut self, action: Action) {
+        self.press(action);
+        self.update();
+        self.release(action);
+    }
+
+    /// Press, keep it down for `duration`, release.
+    pub fn hold(&mut self, action: Action, duration: Duration) {
+        self.press(action);
+        self.wait(duration);
+        self.release(action);
+    }
+
+    fn send(&mut self, action: Action, pressed: bool) {
+        let quit = input::process_action(
+            action,
+            pressed,
+            &mut self.game_state,
+            &mut self.key_states,
+            &mut self.audio,
+        )
+        .unwrap_or_else(|e| panic!("input for {:?} failed: {}", action, e));
+        self.quit |= quit;
+    }
+
+    /// One frame: the clock moves by `step`, then the game updates.
+    pub fn update(&mut self) {
+        self.game_state.clock.advance(self.step);
+        self.game_state.update(
+            &self.key_states,
+            self.frame_size,
+            self.step,
+            &mut self.audio,
+        );
+        self.audio.update(self.step);
+    }
+
+    /// Updates for at least `duration`, in whole steps.
+    pub fn wait(&mut self, duration: Duration) {
+        let mut waited = Duration::ZERO;
+        while waited < duration {
+            self.update();
+            waited += self.step;
+        }
+    }
+
+    /// Updates until `done` holds, for at most `timeout`. Returns whether it ever did.
+    pub fn wait_until(&mut self, timeout: Duration, done: impl Fn(&GameState) -> bool) -> bool {
+        let mut waited = Duration::ZERO;
+        while !done(&self.game_state) {
+            if waited >= timeout {
+                return false;
+            }
+            self.update();
+            waited += self.step;
+        }
+        true
+    }
+
+    pub fn run(&mut self, script: &[Step]) {
+        for step in script {
+            match *step {
+                Step::Press(action) => self.press(action),
+                Step::Release(action) => self.release(action),
+                Step::Wait(duration) => self.wait(duration),
+            }
+        }
+    }
+
+    pub fn position(&self) -> (f32, f32) {
+        (self.game_state.player.x, self.game_state.player.y)
+    }
+
+    pub fn current_map(&self) -> (i32, i32) {
+        (
+            self.game_state.current_map_row,
+            self.game_state.current_map_col,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::config::{PLAYER_HORIZONTAL_SPEED, PLAYER_SPEED};
+    use crate::game::map::{BattleZone, Event, SelectObjectBox};
+    use crate::game::save::SlotPickerMode;
+    use crate::game::state::TeleportState;
+
+    fn field(name: &str) -> Map {
+        Map {
+            name: name.to_string(),
+            player_spawn: (10, 10),
+            width: 120,
+            height: 40,
+            ..Map::default()
+        }
+    }
+
+    fn select_box(id: u32, x: u32, y: u32, events: Vec<Event>) -> SelectObjectBox {
+        SelectObjectBox {
+            id,
+            x,
+            y,
+            width: 4,
+            height: 4,
+            messages: Vec::new(),
+            events,
+            requires_flag: None,
+            unless_flag: None,
+            conversation: None,
+        }
+    }
+
+    #[test]
+    fn walking_follows_the_clock_and_stops_at_walls() {
+        let mut harness = TestHarness::new(field("map_0_0"));
+        harness.run(&[
+            Step::Press(Action::MoveRight),
+            Step::Wait(DEFAULT_STEP * 30),
+            Step::Release(Action::MoveRight),
+        ]);
+        let (x, y) = harness.position();
+        let horizontal_speed =
+            Settings::default().walk_speed * PLAYER_HORIZONTAL_SPEED / PLAYER_SPEED;
+        let expected = 10.0 + horizontal_speed * (DEFAULT_STEP * 30).as_secs_f32();
+        assert!(
+            (x - expected).abs() < 0.01,
+            "x = {}, expected {}",
+            x,
+            expected
+        );
+        assert_eq!(y, 10.0);
+
+        // a wall right under the player's feet
+        let mut map = field("map_0_0");
+        let feet = harness.game_state.player.get_collision_rect();
+        map.walls = (0..120)
+            .map(|x| (x, (feet.y + feet.height) as u32))
+            .collect();
+        let mut harness = TestHarness::new(map);
+        harness.hold(Action::MoveDown, Duration::from_secs(2));
+        assert!(harness.position().1 < 11.0);
+    }
+
+    #[test]
+    fn teleport_box_fades_over_to_the_other_map() {
+        let mut map = field("map_0_0");
+        let feet = TestHarness::new(field("map_0_0"))
+            .game_state
+            .player
+            .get_collision_rect();
+        map.select_object_boxes.push(select_box(
+            1,
+            feet.x as u32 + 12,
+            feet.y as u32,
+            vec![Event::TeleportPlayer {
+                map_row: 0,
+                map_col: 1,
+                dest_x: 50,
+                dest_y: 20,
+            }],
+        ));
+        let mut harness = TestHarness::new(map).with_map(field("map_0_1"));
+
+        harness.press(Action::MoveRight);
+        assert!(harness.wait_until(Duration::from_secs(2), |game_state| {
+            game_state.teleport_state == TeleportState::FadingOut
+        }));
+        harness.release(Action::MoveRight);
+        assert!(harness.wait_until(Duration::from_secs(2), |game_state| {
+            game_state.teleport_state == TeleportState::None
+        }));
+        assert_eq!(harness.current_map(), (0, 1));
+        assert_eq!(harness.position(), (50.0, 20.0));
+        assert_eq!(harness.game_state.deltarune.level, 0);
+    }
+
+    #[test]
+    fn teleport_fires_even_when_it_is_not_the_first_event() {
+        let mut map = field("map_0_0");
+        let feet = TestHarness::new(field("map_0_0"))
+            .game_state
+            .player
+            .get_collision_rect();
+        map.select_object_boxes.push(select_box(
+            1,
+            feet.x as u32 + 12,
+            feet.y as u32,
+            vec![
+                Event::SetFlag {
+                    name: "visited".to_string(),
+                    value: crate::game::flags::FlagValue::Bool(true),
+                },
+                Event::TeleportPlayer {
+                    map_row: 0,
+                    map_col: 1,
+                    dest_x: 50,
+                    dest_y: 20,
+                },
+            ],
+        ));
+        let mut harness = TestHarness::new(map).with_map(field("map_0_1"));
+
+        harness.press(Action::MoveRight);
+        assert!(harness.wait_until(Duration::from_secs(2), |game_state| {
+            game_state.teleport_state == TeleportState::FadingOut
+        }));
+    }
+
+    #[test]
+    fn saving_before_a_file_was_picked_asks_for_a_slot() {
+        let mut harness = TestHarness::new(field("map_0_0"));
+        assert_eq!(harness.game_state.current_save_slot, None);
+
+        harness.tap(Action::QuickSave);
+        let picker = harness.game_state.slot_picker.as_ref().unwrap();
+        assert_eq!(picker.mode, SlotPickerMode::Save);
+        assert!(!picker.quit_after_save);
+        harness.tap(Action::Cancel);
+        assert!(harness.game_state.slot_picker.is_none());
+
+        harness.tap(Action::SaveAndQuit);
+        assert!(!harness.quit);
+        assert!(
+            harness
+                .game_state
+                .slot_picker
+                .as_ref()
+                .unwrap()
+                .quit_after_save
+        );
+        assert!(harness.game_state.save_game_state().is_err());
+    }
+
+    #[test]
+    fn talking_to_a_box_steps_through_its_conversation() {
+        let graph: DialogueGraph = serde_json::from_str(
+            r#"{"start": "hi", "nodes": {
+                "hi": {"text": "* Hello.", "next": "bye"},
+                "bye": {"text": "* Bye.", "events": [{"SetFlag": {"name": "met", "value": true}}]}
+            }}"#,
+        )
+        .unwrap();
+        let mut map = field("map_0_0");
+        let reach = TestHarness::new(field("map_0_0"))
+            .game_state
+            .player
+            .get_interaction_rect();
+        let mut sign = select_box(1, reach.x as u32, reach.y as u32, Vec::new());
+        sign.conversation = Some("greeting".to_string());
+        map.select_object_boxes.push(sign);
+        let mut harness = TestHarness::new(map).with_conversation("greeting", graph);
+
+        harness.update();
+        harness.tap(Action::Confirm);
+        assert!(harness.game_state.dialogue_active);
+        assert_eq!(harness.game_state.dialogue_manager.current_node, "hi");
+
+        // the first Confirm only finishes the line that's still typing
+        harness.wait(Duration::from_secs(1));
+        harness.tap(Action::Confirm);
+        assert_eq!(harness.game_state.dialogue_manager.current_node, "bye");
+        assert!(!harness.game_state.dialogue_manager.is_text_finished());
+        harness.wait(Duration::from_secs(1));
+        harness.tap(Action::Confirm);
+        assert!(!harness.game_state.dialogue_active);
+        assert!(harness.game_state.flags.is_set("met"));
+    }
+
+    #[test]
+    fn walking_into_a_battle_zone_starts_a_fight_once() {
+        let mut map = field("map_0_0");
+        let feet = TestHarness::new(field("map_0_0"))
+            .game_state
+            .player
+            .get_collision_rect();
+        map.battle_zones.push(BattleZone {
+            id: 7,
+            enemy_id: crate::game::enemy::DEFAULT_ENEMY_ID.to_string(),
+            x: feet.x as u32,
+            y: (feet.y + feet.height) as u32 + 2,
+            width: 20,
+            height: 3,
+            requires_flag: None,
+            unless_flag: None,
+            conversation: None,
+        });
+        let mut harness = TestHarness::new(map);
+
+        harness.update();
+        assert!(harness.game_state.battle.is_none());
+        harness.press(Action::MoveDown);
+        assert!(harness.wait_until(Duration::from_secs(2), |game_state| {
+            game_state.battle.is_some()
+        }));
+        harness.release(Action::MoveDown);
+        assert_eq!(harness.game_state.recently_fought_zone_id, Some(7));
+        assert!(
+            harness
+                .audio
+                .requested()
+                .iter()
+                .any(|sound| *sound == crate::audio::Sound::Effect("enemy_encounter".into()))
+        );
+    }
+}
@@ -1,104 +1,304 @@
-use std::{collections::HashMap, io, time::Instant};
+use std::{collections::HashMap, io, time::Duration};
 
 use crossterm::event::{self, Event, KeyCode};
 
 use serde_json;
 
+use crate::game::keymap::{Action, Keymap};
+use crate::game::settings::SettingsRow;
 use crate::game::state::{GameState, TeleportCreationState};
 
 #[cfg(windows)]
 #[cfg_attr(windows, path = "windows_input.rs")]
 mod windows_input;
 
-fn map_key(key_code: KeyCode) -> KeyCode {
-    match key_code {
-        KeyCode::Char('W') | KeyCode::Char('w') => KeyCode::Up,
-        KeyCode::Char('A') | KeyCode::Char('a') => KeyCode::Left,
-        KeyCode::Char('S') | KeyCode::Char('s') => KeyCode::Down,
-        KeyCode::Char('D') | KeyCode::Char('d') => KeyCode::Right,
-        _ => key_code,
+// the settings screen: Up/Down picks a row, Left/Right changes it, Confirm rebinds a key
+// and Cancel closes it and writes the settings and keymap to disk
+fn process_settings_key(
+    key_code: KeyCode,
+    action: Option<Action>,
+    game_state: &mut GameState,
+    audio: &mut crate::audio::Audio,
+) {
+    let Some(menu) = &mut game_state.settings_menu else {
+        return;
+    };
+    let row = menu.row();
+    if menu.rebinding {
+        // the raw key, whatever it's bound to right now
+        menu.rebinding = false;
+        if let (SettingsRow::Key(action), KeyCode::Char(c)) = (row, key_code)
+            && c.is_ascii_alphanumeric()
+        {
+            game_state.keymap.rebind(action, c);
+        }
+        return;
+    }
+    match action {
+        Some(Action::MoveUp) => menu.move_cursor(false),
+        Some(Action::MoveDown) => menu.move_cursor(true),
+        Some(direction @ (Action::MoveLeft | Action::MoveRight)) => {
+            game_state
+                .settings
+                .adjust(row, direction == Action::MoveRight);
+            game_state.settings.apply_volume(audio);
+        }
+        Some(Action::Confirm) => {
+            if matches!(row, SettingsRow::Key(_)) {
+                menu.rebinding = true;
+            } else {
+                game_state.settings.adjust(row, true);
+                game_state.settings.apply_volume(audio);
+            }
+        }
+        Some(Action::Cancel | Action::Quit) => {
+            game_state.settings_menu = None;
+            if let Err(e) = game_state
+                .settings
+                .save()
+                .and_then(|()| game_state.keymap.save())
+            {
+                game_state.message = format!("Failed to save settings: {}", e);
+                game_state.show_message = true;
+                game_state.message_typewriter.clear();
+            }
+        }
+        _ => {}
     }
 }
 
+/// Every event that comes in within `timeout`, sleeping until then when nothing does.
 #[cfg(windows)]
-pub fn process_inputs(
-    game_state: &mut GameState,
-    key_states: &mut HashMap<KeyCode, bool>,
-    audio: &mut crate::audio::Audio,
-) -> io::Result<bool> {
-    let events = windows_input::read_key_events()?;
-    let mut should_quit = false;
-    for event in events {
-        if process_event(event, game_state, key_states, audio)? {
-            should_quit = true;
+pub fn read_events(timeout: Duration) -> io::Result<Vec<Event>> {
+    // the keyboard state can't be waited on, only read
+    std::thread::sleep(timeout);
+    windows_input::read_key_events()
+}
+
+/// Every event that comes in within `timeout`, sleeping until then when nothing does.
+#[cfg(not(windows))]
+pub fn read_events(timeout: Duration) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    if !event::poll(timeout)? {
+        return Ok(events);
+    }
+    // whatever else queued up goes in the same batch, so a burst isn't spread over frames
+    loop {
+        events.push(event::read()?);
+        if !event::poll(Duration::ZERO)? {
+            return Ok(events);
         }
     }
-    Ok(should_quit)
 }
 
-#[cfg(not(windows))]
-pub fn process_inputs(
+/// An action from somewhere other than the keyboard, e.g. a gamepad, as a press or release
+/// of the first key bound to it. `None` when nothing is bound to it.
+pub fn action_event(action: Action, pressed: bool, keymap: &Keymap) -> Option<Event> {
+    let &key_code = keymap.keys(action).first()?;
+    let kind = if pressed {
+        event::KeyEventKind::Press
+    } else {
+        event::KeyEventKind::Release
+    };
+    Some(Event::Key(event::KeyEvent::new_with_kind(
+        key_code,
+        event::KeyModifiers::NONE,
+        kind,
+    )))
+}
+
+/// `action_event` sent down the same path as the keyboard.
+pub fn process_action(
+    action: Action,
+    pressed: bool,
     game_state: &mut GameState,
-    key_states: &mut HashMap<KeyCode, bool>,
+    key_states: &mut HashMap<Action, bool>,
     audio: &mut crate::audio::Audio,
 ) -> io::Result<bool> {
-    if event::poll(std::time::Duration::from_millis(16))? {
-        let event = event::read()?;
-        return process_event(event, game_state, key_states, audio);
+    match action_event(action, pressed, &game_state.keymap) {
+        Some(event) => process_event(event, game_state, key_states, audio),
+        None => Ok(false),
     }
-    Ok(false)
 }
 
 pub fn process_event(
     event: Event,
     game_state: &mut GameState,
-    key_states: &mut HashMap<KeyCode, bool>,
+    key_states: &mut HashMap<Action, bool>,
     audio: &mut crate::audio::Audio,
 ) -> io::Result<bool> {
     match event {
         Event::Key(key) => {
-            if game_state.teleport_state == crate::game::state::TeleportState::ThankYouScreen {
-                if key.kind == event::KeyEventKind::Press {
-                    return Ok(true);
-                }
+            let action = game_state.keymap.action(key.code);
+            let pressed = matches!(
+                key.kind,
+                event::KeyEventKind::Press | event::KeyEventKind::Repeat
+            );
+
+            if game_state.teleport_state == crate::game::state::TeleportState::ThankYouScreen
+                && key.kind == event::KeyEventKind::Press
+            {
+                return Ok(true);
             }
 
             if game_state.intro_active {
-                match key.kind {
-                    event::KeyEventKind::Press | event::KeyEventKind::Repeat => {
-                        key_states.insert(map_key(key.code), true);
+                if let Some(action) = action {
+                    key_states.insert(action, pressed);
+                }
+                return Ok(false);
+            }
+
+            if let Some(picker) = &mut game_state.slot_picker {
+                if key.kind != event::KeyEventKind::Press {
+                    return Ok(false);
+                }
+                match action {
+                    Some(Action::MoveUp) => picker.select_previous(),
+                    Some(Action::MoveDown) => picker.select_next(),
+                    Some(Action::Confirm) => {
+                        key_states.clear();
+                        if let Err(e) = game_state.confirm_slot_picker() {
+                            game_state.slot_picker = None;
+                            game_state.message = format!("Failed to use save slot: {}", e);
+                            game_state.show_message = true;
+                            game_state.message_typewriter.clear();
+                        } else if game_state
+                            .slot_picker
+                            .as_ref()
+                            .is_some_and(|picker| picker.saved && picker.quit_after_save)
+                        {
+                            return Ok(true);
+                        }
                     }
-                    event::KeyEventKind::Release => {
-                        key_states.insert(map_key(key.code), false);
+                    Some(Action::Cancel | Action::Quit)
+                        if picker.mode == crate::game::save::SlotPickerMode::Save =>
+                    {
+                        game_state.slot_picker = None;
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+
+            if game_state.game_over_active {
+                let Some(action) = action else {
+                    return Ok(false);
+                };
+                if !pressed {
+                    key_states.insert(action, false);
+                    return Ok(false);
+                }
+                match action {
+                    Action::MoveUp | Action::MoveLeft => game_state.game_over_selection = 0,
+                    Action::MoveDown | Action::MoveRight => game_state.game_over_selection = 1,
+                    Action::Confirm if key.kind == event::KeyEventKind::Press => {
+                        if game_state.game_over_selection == 1 {
+                            return Ok(true);
+                        }
+                        key_states.clear();
+                        if let Err(e) = game_state.continue_from_last_save() {
+                            game_state.message = format!("Failed to load save: {}", e);
+                            game_state.show_message = true;
+                            game_state.message_typewriter.clear();
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+
+            if let Some(battle) = &mut game_state.battle {
+                if let Some(action) = action {
+                    key_states.insert(action, pressed);
+                    if pressed {
+                        battle.handle_action(action);
                     }
                 }
                 return Ok(false);
             }
 
             if game_state.dialogue_active {
-                if map_key(key.code) == KeyCode::Enter
-                    && (key.kind == event::KeyEventKind::Press
-                        || key.kind == event::KeyEventKind::Repeat)
-                {
-                    if game_state.dialogue_manager.text_animation_finished {
+                if action == Some(Action::Confirm) && pressed {
+                    if game_state.dialogue_manager.is_text_finished() {
                         let is_last_dialogue = game_state.dialogue_manager.advance_dialogue();
                         if is_last_dialogue {
-                            game_state.dialogue_active = false;
-                            game_state.teleport_state =
-                                crate::game::state::TeleportState::FadingOutToThankYou;
-                            game_state.teleport_transition_timer = Some(Instant::now());
+                            game_state.finish_dialogue();
                         }
                     } else {
                         game_state.dialogue_manager.skip_animation();
                     }
-                } else if key.kind == event::KeyEventKind::Press
-                    || key.kind == event::KeyEventKind::Repeat
+                } else if !game_state.dialogue_manager.visible_choices().is_empty() {
+                    if key.kind == event::KeyEventKind::Press {
+                        match action {
+                            Some(Action::MoveUp | Action::MoveLeft) => {
+                                game_state.dialogue_manager.select_previous_choice()
+                            }
+                            Some(Action::MoveDown | Action::MoveRight) => {
+                                game_state.dialogue_manager.select_next_choice()
+                            }
+                            _ => {}
+                        }
+                    }
+                } else if pressed
+                    && !game_state
+                        .dialogue_manager
+                        .typewriter
+                        .is_waiting_for_input()
                 {
+                    // only Confirm goes past a [wait_input]
                     game_state.dialogue_manager.skip_animation();
                 }
                 return Ok(false);
             }
 
+            // cutscenes keep the keyboard, releases still count so no key gets stuck down
+            if game_state.is_script_running() {
+                if !pressed && let Some(action) = action {
+                    key_states.insert(action, false);
+                }
+                return Ok(false);
+            }
+
+            if game_state.settings_menu.is_some() {
+                if pressed {
+                    process_settings_key(key.code, action, game_state, audio);
+                } else if let Some(action) = action {
+                    key_states.insert(action, false);
+                }
+                return Ok(false);
+            }
+
+            if let Some(menu) = &mut game_state.menu {
+                let Some(action) = action else {
+                    return Ok(false);
+                };
+                if !pressed {
+                    key_states.insert(action, false);
+                    return Ok(false);
+                }
+                match action {
+                    Action::MoveUp => menu.move_cursor(false),
+                    Action::MoveDown => menu.move_cursor(true),
+                    Action::Confirm => {
+                        game_state.confirm_menu();
+                        if game_state.settings_menu.is_some()
+                            && let Err(e) = audio.play_sound("open_settings")
+                        {
+                            game_state.sound_error =
+                                Some(format!("Failed to play sound open_settings: {}", e));
+                        }
+                    }
+                    Action::Cancel | Action::Quit | Action::Menu => {
+                        let went_back = menu.back();
+                        if !went_back {
+                            game_state.menu = None;
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+
             if game_state.show_enemy_ansi {
                 if key.kind == event::KeyEventKind::Press || key.kind == event::KeyEventKind::Repeat
                 {
@@ -108,7 +308,7 @@ pub fn process_event(
             }
 
             if game_state.is_text_input_active {
-                match map_key(key.code) {
+                match key.code {
                     KeyCode::Char(c) => {
                         if game_state.teleport_creation_state
                             == TeleportCreationState::EnteringMapName
@@ -148,8 +348,7 @@ pub fn process_event(
                             game_state.is_text_input_active = false;
                             game_state.text_input_buffer.clear();
                             game_state.show_message = true;
-                            game_state.message_animation_start_time = Instant::now();
-                            game_state.animated_message_content.clear();
+                            game_state.message_typewriter.clear();
                         } else if game_state.teleport_creation_state
                             == TeleportCreationState::EnteringMapName
                         {
@@ -160,8 +359,7 @@ pub fn process_event(
                             if target_map_name.is_empty() {
                                 game_state.message = "Target map name cannot be empty.".to_string();
                                 game_state.show_message = true;
-                                game_state.message_animation_start_time = Instant::now();
-                                game_state.animated_message_content.clear();
+                                game_state.message_typewriter.clear();
                             } else {
                                 let parts: Vec<&str> = target_map_name.split('_').collect();
                                 if parts.len() == 3 && parts[0] == "map" {
@@ -220,8 +418,10 @@ pub fn process_event(
                                             }
                                             Err(_) => {
                                                 game_state.message = format!(
-                                                    "Failed to load map data for '{}'.",
-                                                    target_map_name
+                                                    "Failed to load map data for '{}'. Known maps: {}",
+                                                    target_map_name,
+                                                    crate::game::map::Map::available_names()
+                                                        .join(", ")
                                                 );
                                             }
                                         }
@@ -243,8 +443,7 @@ pub fn process_event(
                                             .to_string();
                                 }
                                 game_state.show_message = true;
-                                game_state.message_animation_start_time = Instant::now();
-                                game_state.animated_message_content.clear();
+                                game_state.message_typewriter.clear();
                             }
                         } else if let Some(ref mut pending_box) = game_state.pending_select_box {
                             pending_box
@@ -256,12 +455,11 @@ pub fn process_event(
                                 pending_box.messages.len()
                             );
                             game_state.show_message = true;
-                            game_state.message_animation_start_time = Instant::now();
-                            game_state.animated_message_content.clear();
+                            game_state.message_typewriter.clear();
                         }
                     }
                     KeyCode::Esc => {
-                        game_state.esc_press_start_time = Some(Instant::now());
+                        game_state.esc_press_start_time = Some(game_state.clock.now());
                         game_state.is_text_input_active = false;
                         game_state.text_input_buffer.clear();
                         if game_state.is_creating_map {
@@ -280,8 +478,7 @@ pub fn process_event(
                                     .to_string();
                         }
                         game_state.show_message = true;
-                        game_state.message_animation_start_time = Instant::now();
-                        game_state.animated_message_content.clear();
+                        game_state.message_typewriter.clear();
                     }
                     _ => {}
                 }
@@ -289,7 +486,7 @@ pub fn process_event(
             }
 
             if game_state.is_event_input_active {
-                match map_key(key.code) {
+                match key.code {
                     KeyCode::Char(c) => game_state.text_input_buffer.push(c),
                     KeyCode::Backspace => {
                         game_state.text_input_buffer.pop();
@@ -336,13 +533,82 @@ pub fn process_event(
                                         "Invalid map name format. Expected: map_row_col"
                                             .to_string();
                                 }
+                            } else if (parts.len() == 2 || parts.len() == 3) && parts[0] == "flag" {
+                                // flag <name> [true|false|number]
+                                let value = match parts.get(2) {
+                                    None | Some(&"true") => {
+                                        Some(crate::game::flags::FlagValue::Bool(true))
+                                    }
+                                    Some(&"false") => {
+                                        Some(crate::game::flags::FlagValue::Bool(false))
+                                    }
+                                    Some(number) => {
+                                        number.parse().ok().map(crate::game::flags::FlagValue::Int)
+                                    }
+                                };
+                                if let Some(value) = value {
+                                    pending_box.events.push(crate::game::map::Event::SetFlag {
+                                        name: parts[1].to_string(),
+                                        value,
+                                    });
+                                    game_state.message = format!(
+                                        "Set flag event added. Current: {}",
+                                        pending_box.events.len()
+                                    );
+                                } else {
+                                    game_state.message =
+                                        "Invalid flag value. Expected: true, false or a number"
+                                            .to_string();
+                                }
+                            } else if (parts.len() == 2 || parts.len() == 3)
+                                && parts[0] == "incflag"
+                            {
+                                // incflag <name> [by]
+                                if let Ok(by) = parts.get(2).map_or(Ok(1), |by| by.parse()) {
+                                    pending_box.events.push(
+                                        crate::game::map::Event::IncrementFlag {
+                                            name: parts[1].to_string(),
+                                            by,
+                                        },
+                                    );
+                                    game_state.message = format!(
+                                        "Increment flag event added. Current: {}",
+                                        pending_box.events.len()
+                                    );
+                                } else {
+                                    game_state.message = "Invalid increment amount.".to_string();
+                                }
+                            } else if parts.len() == 2 && parts[0] == "talk" {
+                                // talk <conversation id>
+                                let ids =
+                                    crate::game::dialogue::ConversationRegistry::available_ids();
+                                if ids.iter().any(|id| id == parts[1]) {
+                                    pending_box.conversation = Some(parts[1].to_string());
+                                    game_state.message =
+                                        format!("Conversation set to {}.", parts[1]);
+                                } else {
+                                    game_state.message =
+                                        format!("No conversation named {}.", parts[1]);
+                                }
+                            } else if parts == ["save"] {
+                                pending_box.events.push(crate::game::map::Event::SavePoint);
+                                game_state.message = format!(
+                                    "Save point event added. Current: {}",
+                                    pending_box.events.len()
+                                );
+                            } else if let Ok(event) =
+                                serde_json::from_str::<crate::game::map::Event>(input)
+                            {
+                                // anything else can be typed as JSON, e.g. {"Wait":{"ms":500}}
+                                pending_box.events.push(event);
+                                game_state.message =
+                                    format!("Event added. Current: {}", pending_box.events.len());
                             } else {
                                 game_state.message = "Unknown event format.".to_string();
                             }
                             game_state.text_input_buffer.clear();
                             game_state.show_message = true;
-                            game_state.message_animation_start_time = Instant::now();
-                            game_state.animated_message_content.clear();
+                            game_state.message_typewriter.clear();
                         }
                     }
                     KeyCode::Esc => {
@@ -360,8 +626,7 @@ pub fn process_event(
                                         "SelectObjectBox created and saved.".to_string();
                                 }
                                 game_state.show_message = true;
-                                game_state.message_animation_start_time = Instant::now();
-                                game_state.animated_message_content.clear();
+                                game_state.message_typewriter.clear();
                             }
                         }
                         game_state.is_event_input_active = false;
@@ -374,14 +639,22 @@ pub fn process_event(
                 return Ok(false);
             }
 
+            let Some(action) = action else {
+                return Ok(false);
+            };
             match key.kind {
                 event::KeyEventKind::Press | event::KeyEventKind::Repeat => {
-                    key_states.insert(map_key(key.code), true);
-                    if map_key(key.code) == KeyCode::Esc {
+                    key_states.insert(action, true);
+                    if action == Action::Quit {
                         if game_state.esc_press_start_time.is_none() {
-                            game_state.esc_dot_timer = Instant::now();
+                            game_state.esc_dot_timer = game_state.clock.now();
                         }
-                        game_state.esc_press_start_time = Some(Instant::now());
+                        game_state.esc_press_start_time = Some(game_state.clock.now());
+                    }
+                    if !game_state.is_map_kind_selection_active
+                        && crate::debug::input::handle_debug_input(action, game_state)
+                    {
+                        return Ok(false);
                     }
                     if game_state.is_map_kind_selection_active {
                         let current_map_key =
@@ -389,10 +662,12 @@ pub fn process_event(
                         if let Some(map_to_modify) =
                             game_state.loaded_maps.get_mut(&current_map_key)
                         {
-                            match map_key(key.code) {
-                                KeyCode::Up => map_to_modify.kind = map_to_modify.kind.previous(),
-                                KeyCode::Down => map_to_modify.kind = map_to_modify.kind.next(),
-                                KeyCode::Enter => {
+                            match action {
+                                Action::MoveUp => {
+                                    map_to_modify.kind = map_to_modify.kind.previous()
+                                }
+                                Action::MoveDown => map_to_modify.kind = map_to_modify.kind.next(),
+                                Action::Confirm => {
                                     if let Err(e) = map_to_modify.save_data() {
                                         game_state.message = format!("Failed to save map: {}", e);
                                     } else {
@@ -400,44 +675,49 @@ pub fn process_event(
                                             format!("Map kind set to {:?}", map_to_modify.kind);
                                     }
                                     game_state.show_message = true;
-                                    game_state.message_animation_start_time = Instant::now();
-                                    game_state.animated_message_content.clear();
+                                    game_state.message_typewriter.clear();
                                     game_state.is_map_kind_selection_active = false;
                                 }
-                                KeyCode::Esc => game_state.is_map_kind_selection_active = false,
+                                Action::Cancel | Action::Quit => {
+                                    game_state.is_map_kind_selection_active = false
+                                }
                                 _ => {}
                             }
                         }
-                    } else if map_key(key.code) == KeyCode::Enter {
-                        if !game_state.show_message {
-                            if let Some(box_id) = game_state.current_interaction_box_id {
-                                let current_map_key =
-                                    (game_state.current_map_row, game_state.current_map_col);
-                                if let Some(current_map) =
-                                    game_state.loaded_maps.get(&current_map_key)
-                                {
-                                    if let Some(interacting_box) = current_map
-                                        .select_object_boxes
-                                        .iter()
-                                        .find(|b| b.id == box_id)
-                                    {
-                                        if !interacting_box.messages.is_empty() {
-                                            game_state.message =
-                                                interacting_box.messages[0].clone();
-                                            game_state.show_message = true;
-                                            game_state.message_animation_start_time =
-                                                Instant::now();
-                                            game_state.animated_message_content.clear();
-                                            game_state.message_animation_finished = false;
-                                            game_state.current_message_index = 1;
-                                            game_state.block_player_movement_on_message = true;
-                                            return Ok(false);
-                                        }
-                                    }
+                    } else if action == Action::Confirm {
+                        if !game_state.show_message
+                            && let Some(box_id) = game_state.current_interaction_box_id
+                        {
+                            let current_map_key =
+                                (game_state.current_map_row, game_state.current_map_col);
+                            if let Some(current_map) = game_state.loaded_maps.get(&current_map_key)
+                                && let Some(interacting_box) = current_map
+                                    .select_object_boxes
+                                    .iter()
+                                    .find(|b| b.id == box_id)
+                            {
+                                if interacting_box.messages.is_empty() {
+                                    let events = interacting_box.script_events();
+                                    game_state.run_box_events(&events);
+                                    return Ok(false);
                                 }
+                                game_state.message = interacting_box.messages[0].clone();
+                                game_state.show_message = true;
+                                game_state.message_typewriter.clear();
+                                game_state.message_animation_finished = false;
+                                game_state.current_message_index = 1;
+                                game_state.block_player_movement_on_message = true;
+                                return Ok(false);
                             }
                         }
 
+                        if !game_state.show_message
+                            && game_state.current_interaction_box_id.is_none()
+                            && game_state.talk_to_npc()
+                        {
+                            return Ok(false);
+                        }
+
                         if game_state.show_message {
                             if game_state.message_animation_finished {
                                 if let Some(box_id) = game_state.current_interaction_box_id {
@@ -445,82 +725,93 @@ pub fn process_event(
                                         (game_state.current_map_row, game_state.current_map_col);
                                     if let Some(current_map) =
                                         game_state.loaded_maps.get(&current_map_key)
-                                    {
-                                        if let Some(interacting_box) = current_map
+                                        && let Some(interacting_box) = current_map
                                             .select_object_boxes
                                             .iter()
                                             .find(|b| b.id == box_id)
+                                    {
+                                        if game_state.current_message_index
+                                            < interacting_box.messages.len()
                                         {
-                                            if game_state.current_message_index
-                                                < interacting_box.messages.len()
-                                            {
-                                                game_state.message = interacting_box.messages
-                                                    [game_state.current_message_index]
-                                                    .clone();
-                                                game_state.show_message = true;
-                                                game_state.message_animation_start_time =
-                                                    Instant::now();
-                                                game_state.animated_message_content.clear();
-                                                game_state.message_animation_finished = false;
-                                                game_state.current_message_index += 1;
-                                            } else {
-                                                let events = interacting_box.events.clone();
-                                                let box_id = interacting_box.id;
-                                                game_state.dismiss_message();
-                                                game_state.current_interaction_box_id = None;
-                                                game_state.current_message_index = 0;
-                                                let _events_to_process: Vec<
-                                                    crate::game::map::Event,
-                                                > = events;
-                                                game_state.recently_teleported_from_box_id =
-                                                    Some(box_id);
-                                            }
+                                            game_state.message = interacting_box.messages
+                                                [game_state.current_message_index]
+                                                .clone();
+                                            game_state.show_message = true;
+                                            game_state.message_typewriter.clear();
+                                            game_state.message_animation_finished = false;
+                                            game_state.current_message_index += 1;
+                                        } else {
+                                            let events = interacting_box.script_events();
+                                            let box_id = interacting_box.id;
+                                            game_state.dismiss_message();
+                                            game_state.current_interaction_box_id = None;
+                                            game_state.current_message_index = 0;
+                                            game_state.recently_teleported_from_box_id =
+                                                Some(box_id);
+                                            game_state.run_box_events(&events);
                                         }
                                     }
                                 }
+                            } else if game_state.message_typewriter.is_waiting_for_input() {
+                                game_state.message_typewriter.skip();
                             } else {
                                 game_state.dismiss_message();
                             }
                         }
-                    } else if map_key(key.code) == KeyCode::Char('+') {
+                    } else if action == Action::Menu && !game_state.show_message {
+                        key_states.clear();
+                        game_state.open_menu();
+                    } else if action == Action::DarkenScreen {
                         game_state.deltarune.increase();
-                    } else if map_key(key.code) == KeyCode::Char('-') {
+                    } else if action == Action::BrightenScreen {
                         game_state.deltarune.decrease();
-                    } else if map_key(key.code) == KeyCode::Char('q') {
+                    } else if action == Action::SaveAndQuit {
+                        if game_state.current_save_slot.is_none() {
+                            // no file picked yet, ask which one instead of guessing
+                            key_states.clear();
+                            game_state.open_slot_picker(crate::game::save::SlotPickerMode::Save);
+                            if let Some(picker) = &mut game_state.slot_picker {
+                                picker.quit_after_save = true;
+                            }
+                            return Ok(false);
+                        }
                         game_state.save_game_state()?;
                         return Ok(true);
-                    } else if map_key(key.code) == KeyCode::Char('p') {
+                    } else if action == Action::QuickSave {
+                        if game_state.current_save_slot.is_none() {
+                            key_states.clear();
+                            game_state.open_slot_picker(crate::game::save::SlotPickerMode::Save);
+                            return Ok(false);
+                        }
                         game_state.save_game_state()?;
                         game_state.message = "Game saved!".to_string();
                         game_state.show_message = true;
-                        game_state.message_animation_start_time = Instant::now();
-                        game_state.animated_message_content.clear();
-                    } else if map_key(key.code) == KeyCode::F(2) {
+                        game_state.message_typewriter.clear();
+                    } else if action == Action::DebugToggle {
                         game_state.debug_mode = !game_state.debug_mode;
-                        if game_state.debug_mode {
-                            audio.play_open_settings_sound();
+                        if game_state.debug_mode
+                            && let Err(e) = audio.play_sound("open_settings")
+                        {
+                            game_state.sound_error =
+                                Some(format!("Failed to play sound open_settings: {}", e));
                         }
-                    } else if map_key(key.code) == KeyCode::Char('m') {
+                    } else if action == Action::DumpState {
                         let state_json = serde_json::to_string_pretty(game_state).unwrap();
                         std::fs::write("debug.txt", state_json).unwrap();
                         game_state.message = "Game state saved to debug.txt".to_string();
                         game_state.show_message = true;
-                        game_state.message_animation_start_time = Instant::now();
-                        game_state.animated_message_content.clear();
+                        game_state.message_typewriter.clear();
                     }
                 }
                 event::KeyEventKind::Release => {
-                    key_states.insert(map_key(key.code), false);
-                    if map_key(key.code) == KeyCode::Esc {
+                    key_states.insert(action, false);
+                    if action == Action::Quit {
                         if game_state.esc_press_start_time.is_some() {
-                            game_state.esc_dot_timer = Instant::now();
+                            game_state.esc_dot_timer = game_state.clock.now();
                         }
                         game_state.esc_press_start_time = None;
                     }
-                    if map_key(key.code) == KeyCode::Char('o') && game_state.debug_mode {
-                        game_state.show_collision_box = false;
-                    }
-                    if map_key(key.code) == KeyCode::Char('b') && game_state.debug_mode {
+                    if action == Action::DebugShowCollision {
                         game_state.show_collision_box = false;
                     }
                 }
@@ -2,7 +2,13 @@ pub mod audio;
 pub mod debug;
 pub mod game;
 pub mod game_loop;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod harness;
 pub mod input;
+pub mod replay;
+#[cfg(test)]
+mod snapshot;
 pub mod ui;
 pub mod assets;
 pub mod crash_handler;
\ No newline at end of file
@@ -1,15 +1,60 @@
-use std::io::{self, stdout, IsTerminal};
-use under_term::{game, game_loop, crash_handler};
+use std::io::{self, IsTerminal, stdout};
+use std::path::PathBuf;
+use under_term::replay::{Recording, Replay, Session};
+use under_term::{assets, audio::Audio, crash_handler, game, game_loop};
 
 use crossterm::{
-    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
+    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use ratatui::{backend::CrosstermBackend, Terminal};
+use ratatui::{Terminal, backend::CrosstermBackend, layout::Rect};
+
+// `--name value` or `--name=value`
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+        if let Some(value) = arg
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+// --assets <dir> loads assets from disk before falling back to the embedded ones
+fn parse_asset_dir() -> Option<PathBuf> {
+    arg_value("--assets").map(PathBuf::from)
+}
+
+// --mute skips the output device entirely
+fn is_muted() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--mute")
+}
 
 fn run_app() -> io::Result<()> {
     crash_handler::set_panic_hook();
+    assets::init(parse_asset_dir());
+    game::save::import_legacy_save().map_err(|e| {
+        io::Error::other(format!(
+            "could not import {}: {}",
+            game::save::legacy_save_path().display(),
+            e
+        ))
+    })?;
+    // a broken keymap.json or settings.json is reported before the terminal is taken over
+    let keymap = game::keymap::Keymap::load().map_err(io::Error::other)?;
+    let settings = game::settings::Settings::load()?;
+    // --replay <file> plays a --record <file> session back, see `under_term::replay`
+    let replay = arg_value("--replay")
+        .map(|path| Recording::load(path.as_ref()))
+        .transpose()?;
+    let record_path = arg_value("--record").map(PathBuf::from);
 
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
     enable_raw_mode()?;
@@ -22,12 +67,33 @@ fn run_app() -> io::Result<()> {
     }
 
     let mut game_state = game::state::GameState::load_game_state()?;
+    game_state.keymap = keymap;
+    game_state.settings = settings;
+    let mut session = match (replay, &record_path) {
+        // saves made during the replay go to a scratch copy of the recorded slots
+        (Some(recording), _) => Session::Replay(Replay::start(recording, &mut game_state)?),
+        (None, Some(_)) => {
+            let size = terminal.size()?;
+            Session::Record(Recording::start(
+                rand::random(),
+                &game_state.settings,
+                &game_state.keymap,
+                Rect::new(0, 0, size.width, size.height),
+            )?)
+        }
+        (None, None) => Session::Live,
+    };
     game_state.player.is_walking = false;
     game_state.player.animation_frame = 0;
 
-    
-
-    let result = game_loop::run(&mut terminal, &mut game_state);
+    // no output device is fine, the game just runs silent
+    let mut audio = if is_muted() {
+        Audio::null()
+    } else {
+        Audio::open()
+    };
+    game_state.settings.apply_volume(&mut audio);
+    let result = game_loop::run(&mut terminal, &mut game_state, audio, &mut session);
 
     if let Err(e) = stdout().execute(PopKeyboardEnhancementFlags) {
         eprintln!("Could not disable keyboard enhancement flags: {:?}", e);
@@ -35,6 +101,10 @@ fn run_app() -> io::Result<()> {
     disable_raw_mode()?;
     stdout().execute(LeaveAlternateScreen)?;
 
+    // written even when the game errored out, that's the session worth sending in
+    if let (Session::Record(recording), Some(path)) = (&session, &record_path) {
+        recording.save(path)?;
+    }
     result
 }
 
@@ -0,0 +1,411 @@
+//! `--record <file>` writes down every input event with the update step it came in before,
+//! along with the RNG seed, settings and keys the session ran with. `--replay <file>` starts
+//! from the same seed, settings and keys and feeds the events back into
+//! `input::process_event` at the same steps instead of reading the keyboard. The game steps
+//! at a fixed rate on a stepped clock, so that's enough for the session to play out the
+//! same way again.
+//!
+//! The recording also keeps the terminal's size and every save slot's file from when the
+//! session started. A replay steps the game at the recorded size (and the recorded resizes)
+//! whatever the terminal it's shown in, and plays on a scratch copy of the recorded slots,
+//! so the saves it makes never touch the real ones.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::Audio;
+use crate::game::clock::Clock;
+use crate::game::keymap::{Action, Keymap, key_name, parse_key};
+use crate::game::save::{self, SAVE_SLOT_COUNT};
+use crate::game::settings::Settings;
+use crate::game::state::GameState;
+use crate::{game_loop, input};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyKind {
+    Press,
+    Repeat,
+    Release,
+}
+
+/// The events the game reacts to. Modifiers aren't kept, nothing looks at them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordedInput {
+    // the key as written in keymap.json, except letters keep their case
+    Key { key: String, kind: KeyKind },
+    Resize { width: u16, height: u16 },
+}
+
+impl RecordedInput {
+    /// `None` for events that aren't worth keeping, like mouse movement.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::Key(key) => {
+                let name = key_name(key.code);
+                // keys keymap.json has no name for can't be read back
+                parse_key_label(&name)?;
+                let kind = match key.kind {
+                    KeyEventKind::Press => KeyKind::Press,
+                    KeyEventKind::Repeat => KeyKind::Repeat,
+                    KeyEventKind::Release => KeyKind::Release,
+                };
+                Some(RecordedInput::Key { key: name, kind })
+            }
+            Event::Resize(width, height) => Some(RecordedInput::Resize {
+                width: *width,
+                height: *height,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn to_event(&self) -> Option<Event> {
+        match self {
+            RecordedInput::Key { key, kind } => {
+                let kind = match kind {
+                    KeyKind::Press => KeyEventKind::Press,
+                    KeyKind::Repeat => KeyEventKind::Repeat,
+                    KeyKind::Release => KeyEventKind::Release,
+                };
+                Some(Event::Key(KeyEvent::new_with_kind(
+                    parse_key_label(key)?,
+                    KeyModifiers::NONE,
+                    kind,
+                )))
+            }
+            RecordedInput::Resize { width, height } => Some(Event::Resize(*width, *height)),
+        }
+    }
+}
+
+// text inputs care about case, key bindings don't
+fn parse_key_label(label: &str) -> Option<KeyCode> {
+    let mut chars = label.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(KeyCode::Char(c)),
+        _ => parse_key(label),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    // how many updates ran before it
+    pub step: u64,
+    pub input: RecordedInput,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u64,
+    // how many updates the session ran for
+    pub steps: u64,
+    pub settings: Settings,
+    pub keymap: BTreeMap<Action, Vec<String>>,
+    // the terminal's size when the session started, resizes after that are events
+    pub frame_size: (u16, u16),
+    // every save slot's file when the session started, `None` for an empty slot
+    pub slots: Vec<Option<String>>,
+    pub events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    /// An empty recording of a session about to start in a `frame_size` terminal, with the
+    /// save slots as they are now. Seeds the game's RNG with `seed`.
+    pub fn start(
+        seed: u64,
+        settings: &Settings,
+        keymap: &Keymap,
+        frame_size: Rect,
+    ) -> io::Result<Self> {
+        crate::game::rng::seed(seed);
+        Ok(Recording {
+            seed,
+            steps: 0,
+            settings: settings.clone(),
+            keymap: keymap.to_names(),
+            frame_size: (frame_size.width, frame_size.height),
+            slots: (0..SAVE_SLOT_COUNT)
+                .map(save::read_slot_file)
+                .collect::<io::Result<_>>()?,
+            events: Vec::new(),
+        })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| {
+            io::Error::other(format!(
+                "Failed to read recording {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn push(&mut self, step: u64, event: &Event) {
+        if let Some(input) = RecordedInput::from_event(event) {
+            self.events.push(RecordedEvent { step, input });
+        }
+    }
+
+    /// Puts `game_state`, the RNG and the save slots back where the recorded session
+    /// started from. The slots stay swapped for the scratch copy until the returned
+    /// `ReplaySaves` is dropped.
+    pub fn prepare(&self, game_state: &mut GameState) -> io::Result<ReplaySaves> {
+        crate::game::rng::seed(self.seed);
+        game_state.settings = self.settings.clone();
+        game_state.keymap = Keymap::from_names(self.keymap.clone()).map_err(io::Error::other)?;
+        game_state.set_clock(Clock::stepped());
+        ReplaySaves::new(&self.slots)
+    }
+}
+
+/// A scratch data directory holding the recorded save slots, which `save::data_dir` points
+/// at for as long as this is around. Dropping it deletes the copy and points it back.
+#[derive(Debug)]
+pub struct ReplaySaves {
+    dir: PathBuf,
+}
+
+impl ReplaySaves {
+    fn new(slots: &[Option<String>]) -> io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "under_term_replay_{}_{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        save::redirect_data_dir(Some(dir.clone()));
+        let saves = ReplaySaves { dir };
+        for (slot, contents) in slots.iter().enumerate() {
+            save::restore_slot_file(slot, contents.as_deref())?;
+        }
+        Ok(saves)
+    }
+}
+
+impl Drop for ReplaySaves {
+    fn drop(&mut self) {
+        save::redirect_data_dir(None);
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A recording being played back.
+#[derive(Debug)]
+pub struct Replay {
+    recording: Recording,
+    next_event: usize,
+    // the size the game steps at, the recorded one until a recorded resize
+    frame_size: Rect,
+    // only held on to, dropping it brings the real save slots back
+    _saves: Option<ReplaySaves>,
+}
+
+impl Replay {
+    /// Only reads the events out, see `start` for actually playing them.
+    pub fn new(recording: Recording) -> Self {
+        let (width, height) = recording.frame_size;
+        Replay {
+            recording,
+            next_event: 0,
+            frame_size: Rect::new(0, 0, width, height),
+            _saves: None,
+        }
+    }
+
+    /// Prepares `game_state` for the recording, see `Recording::prepare`. The real save
+    /// slots are back once the replay is dropped.
+    pub fn start(recording: Recording, game_state: &mut GameState) -> io::Result<Self> {
+        let saves = recording.prepare(game_state)?;
+        Ok(Replay {
+            _saves: Some(saves),
+            ..Self::new(recording)
+        })
+    }
+
+    /// The events to feed in before update number `step`.
+    pub fn events_before(&mut self, step: u64) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some(recorded) = self.recording.events.get(self.next_event)
+            && recorded.step <= step
+        {
+            if let RecordedInput::Resize { width, height } = recorded.input {
+                self.frame_size = Rect::new(0, 0, width, height);
+            }
+            events.extend(recorded.input.to_event());
+            self.next_event += 1;
+        }
+        events
+    }
+
+    pub fn frame_size(&self) -> Rect {
+        self.frame_size
+    }
+
+    pub fn is_finished(&self, step: u64) -> bool {
+        step >= self.recording.steps
+    }
+}
+
+/// Where the game loop's input comes from.
+#[derive(Debug)]
+pub enum Session {
+    Live,
+    // the keyboard, written down as it goes
+    Record(Recording),
+    // the recording, the keyboard can only stop it
+    Replay(Replay),
+}
+
+/// Plays a whole recording out as fast as it goes, without a terminal.
+pub fn replay_headless(
+    recording: &Recording,
+    game_state: &mut GameState,
+    audio: &mut Audio,
+) -> io::Result<()> {
+    let mut replay = Replay::start(recording.clone(), game_state)?;
+    let mut key_states = HashMap::new();
+    for step in 0..recording.steps {
+        for event in replay.events_before(step) {
+            if input::process_event(event, game_state, &mut key_states, audio)? {
+                return Ok(());
+            }
+        }
+        game_loop::step(game_state, &key_states, replay.frame_size(), audio);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::Map;
+
+    fn key(code: KeyCode, kind: KeyEventKind) -> Event {
+        Event::Key(KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind))
+    }
+
+    // an 80x24 session that started with every slot empty
+    fn recording(seed: u64) -> Recording {
+        let mut recording = Recording::start(
+            seed,
+            &Settings::default(),
+            &Keymap::default(),
+            Rect::new(0, 0, 80, 24),
+        )
+        .unwrap();
+        recording.slots = vec![None; SAVE_SLOT_COUNT];
+        recording
+    }
+
+    fn field() -> Map {
+        Map {
+            name: "map_0_0".to_string(),
+            player_spawn: (10, 10),
+            width: 120,
+            height: 40,
+            ..Map::default()
+        }
+    }
+
+    #[test]
+    fn events_survive_the_file() {
+        let events = [
+            key(KeyCode::Char('Z'), KeyEventKind::Press),
+            key(KeyCode::Char(' '), KeyEventKind::Repeat),
+            key(KeyCode::Esc, KeyEventKind::Release),
+            key(KeyCode::F(3), KeyEventKind::Press),
+            Event::Resize(100, 30),
+        ];
+        let mut recording = recording(1);
+        for (step, event) in events.iter().enumerate() {
+            recording.push(step as u64, event);
+        }
+        recording.push(9, &Event::FocusLost);
+
+        let json = serde_json::to_string(&recording).unwrap();
+        let mut replay = Replay::new(serde_json::from_str(&json).unwrap());
+        assert_eq!(replay.events_before(1), events[..2]);
+        assert_eq!(replay.frame_size(), Rect::new(0, 0, 80, 24));
+        assert_eq!(replay.events_before(10), events[2..]);
+        assert_eq!(replay.frame_size(), Rect::new(0, 0, 100, 30));
+        assert!(replay.events_before(10).is_empty());
+    }
+
+    #[test]
+    fn a_replay_saves_into_a_copy_of_the_recorded_slots() {
+        let real_data_dir = save::data_dir();
+        let mut recording = recording(3);
+        recording.slots[0] =
+            Some(r#"{ "current_map_name": "map_1_2", "player_position": [5, 6] }"#.to_string());
+        // QuickSave, into the slot the run was started from
+        recording.push(0, &key(KeyCode::Char('p'), KeyEventKind::Press));
+        recording.steps = 1;
+
+        let mut game_state = GameState::from_map(field());
+        game_state.intro_active = false;
+        game_state.current_save_slot = Some(1);
+        let replay = Replay::start(recording.clone(), &mut game_state).unwrap();
+        let scratch_dir = save::data_dir();
+        assert_ne!(scratch_dir, real_data_dir);
+        assert_eq!(save::read_slot(0).unwrap().unwrap().player_x, 5.0);
+        assert!(save::read_slot(1).unwrap().is_none());
+        drop(replay);
+        assert_eq!(save::data_dir(), real_data_dir);
+        assert!(!scratch_dir.exists());
+
+        // played out for real, the save lands in the scratch copy and goes with it
+        let mut audio = Audio::null();
+        let mut replay = Replay::start(recording, &mut game_state).unwrap();
+        for event in replay.events_before(0) {
+            input::process_event(event, &mut game_state, &mut HashMap::new(), &mut audio).unwrap();
+        }
+        let saved = save::read_slot(1).unwrap().unwrap();
+        assert_eq!(saved.map_name, "map_0_0");
+        drop(replay);
+        assert_eq!(save::data_dir(), real_data_dir);
+    }
+
+    #[test]
+    fn same_recording_plays_out_the_same() {
+        let map = field();
+        let mut recording = recording(42);
+        recording.push(0, &key(KeyCode::Right, KeyEventKind::Press));
+        recording.push(25, &key(KeyCode::Right, KeyEventKind::Release));
+        recording.push(30, &key(KeyCode::Down, KeyEventKind::Press));
+        recording.steps = 45;
+
+        let play = || {
+            let mut game_state = GameState::from_map(map.clone());
+            game_state.intro_active = false;
+            // typed out at random intervals
+            game_state.message = "* The recording plays back the same every time.".to_string();
+            game_state.show_message = true;
+            game_state.block_player_movement_on_message = false;
+            let mut audio = Audio::null();
+            replay_headless(&recording, &mut game_state, &mut audio).unwrap();
+            (
+                game_state.player.x,
+                game_state.player.y,
+                game_state.message_typewriter.visible_text(),
+            )
+        };
+        let first = play();
+        assert!(first.0 > 10.0 && first.1 > 10.0);
+        assert!(!first.2.is_empty());
+        assert_eq!(first, play());
+    }
+}
@@ -0,0 +1,116 @@
+//! Golden snapshots of rendered frames, checked into `src/snapshots`. A snapshot is the
+//! frame's text, then every distinct cell style, then a grid saying which style each cell
+//! has. Run the tests with `UNDER_TERM_BLESS=1` to write new ones after a change to how
+//! things look.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+
+pub const BLESS_ENV_VAR: &str = "UNDER_TERM_BLESS";
+
+const STYLE_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/snapshots")
+        .join(format!("{}.snap", name))
+}
+
+/// The buffer written out as text.
+pub fn render(buffer: &Buffer) -> String {
+    let area = buffer.area;
+    let mut styles: Vec<(Color, Color, Modifier)> = Vec::new();
+    let mut style_index: HashMap<(Color, Color, Modifier), usize> = HashMap::new();
+    let mut cell_styles = Vec::with_capacity(buffer.content.len());
+    let mut text = String::new();
+
+    for y in 0..area.height {
+        for x in 0..area.width {
+            let cell = &buffer[(area.x + x, area.y + y)];
+            text.push_str(cell.symbol());
+            let style = (cell.fg, cell.bg, cell.modifier);
+            let index = *style_index.entry(style).or_insert_with(|| {
+                styles.push(style);
+                styles.len() - 1
+            });
+            cell_styles.push(index);
+        }
+        text.push('\n');
+    }
+
+    // every cell's style takes the same number of digits, so the grid lines up with the text
+    let base = STYLE_DIGITS.len();
+    let mut digits = 1;
+    while base.pow(digits) < styles.len() {
+        digits += 1;
+    }
+    let style_id = |mut index: usize| {
+        let mut id = vec![b'0'; digits as usize];
+        for digit in id.iter_mut().rev() {
+            *digit = STYLE_DIGITS[index % base];
+            index /= base;
+        }
+        String::from_utf8(id).unwrap()
+    };
+
+    let mut snapshot = format!("{}x{}\n{}", area.width, area.height, text);
+    snapshot.push_str("--- styles ---\n");
+    for (index, (fg, bg, modifier)) in styles.iter().enumerate() {
+        snapshot.push_str(&format!(
+            "{} fg={:?} bg={:?} mod={:?}\n",
+            style_id(index),
+            fg,
+            bg,
+            modifier
+        ));
+    }
+    snapshot.push_str("--- cells ---\n");
+    for row in cell_styles.chunks(area.width.max(1) as usize) {
+        for &index in row {
+            snapshot.push_str(&style_id(index));
+        }
+        snapshot.push('\n');
+    }
+    snapshot
+}
+
+/// Compares the buffer against `src/snapshots/<name>.snap`, or overwrites the file when
+/// blessing.
+pub fn assert_snapshot(name: &str, buffer: &Buffer) {
+    let actual = render(buffer);
+    let path = path(name);
+    if std::env::var_os(BLESS_ENV_VAR).is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let Ok(expected) = std::fs::read_to_string(&path) else {
+        panic!(
+            "no snapshot at {}, run with {}=1 to write it",
+            path.display(),
+            BLESS_ENV_VAR
+        );
+    };
+    if expected == actual {
+        return;
+    }
+    let (line, (expected_line, actual_line)) = expected
+        .lines()
+        .chain(std::iter::repeat(""))
+        .zip(actual.lines().chain(std::iter::repeat("")))
+        .enumerate()
+        .find(|(_, (expected, actual))| expected != actual)
+        .unwrap();
+    panic!(
+        "snapshot {} differs from line {} on\nexpected: {}\n  actual: {}\nrun with {}=1 if the new look is right",
+        name,
+        line + 1,
+        expected_line,
+        actual_line,
+        BLESS_ENV_VAR
+    );
+}
@@ -1,17 +1,25 @@
+use crate::assets::{self, Sprite};
 use crate::debug;
+use crate::game::attack::AttackPhase;
+use crate::game::battle::{BattleMode, BattleState, MenuButton};
+use crate::game::item::Item;
+use crate::game::menu::{ITEM_ACTIONS, MENU_OPTIONS, MenuPage, OverworldMenu};
+use crate::game::save::{SlotPicker, SlotPickerMode, SlotSummary, format_play_time};
+use crate::game::settings::{ColorMode, SETTINGS_ROWS, SettingsMenu};
 use crate::game::state::{GameState, TeleportCreationState};
+use crate::game::typewriter::{self, Glyph};
 use crate::game::utils::wrap_text_to_width;
-use crate::load_sprite_asset_str;
 use ansi_to_tui::IntoText;
 use figlet_rs::FIGfont;
 use ratatui::prelude::Alignment;
 use ratatui::prelude::Text;
+use std::sync::Arc;
 
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     widgets::{Block, Borders, Clear, Paragraph, Widget},
-    Frame,
 };
 
 fn convert_and_fix_t(font: &FIGfont, text: &str) -> String {
@@ -25,6 +33,48 @@ fn convert_and_fix_t(font: &FIGfont, text: &str) -> String {
     }
 }
 
+// figlet letters just sit side by side, so every run can be converted with its own look
+fn convert_glyphs(font: &FIGfont, glyphs: &[Glyph]) -> Text<'static> {
+    let mut rows: Vec<Vec<ratatui::text::Span<'static>>> = Vec::new();
+    for (text, look) in typewriter::runs(glyphs) {
+        let Some(figure) = font.convert(&text) else {
+            continue;
+        };
+        let mut run_rows: Vec<String> = figure.to_string().lines().map(str::to_string).collect();
+        if look.shake {
+            typewriter::jitter(&mut run_rows);
+        }
+        if rows.len() < run_rows.len() {
+            rows.resize_with(run_rows.len(), Vec::new);
+        }
+        for (row, run_row) in rows.iter_mut().zip(run_rows) {
+            row.push(ratatui::text::Span::styled(run_row, look.style()));
+        }
+    }
+    Text::from(
+        rows.into_iter()
+            .map(ratatui::text::Line::from)
+            .collect::<Vec<_>>(),
+    )
+}
+
+// a missing sprite shows up as its error instead of taking the game down
+fn load_ansi_text(path: &str) -> Text<'static> {
+    assets::store()
+        .load_text(path)
+        .unwrap_or_else(|e| Text::raw(e.to_string()))
+}
+
+fn sprite_text(sprite: Result<&Arc<Sprite>, assets::AssetError>) -> Text<'static> {
+    sprite.map_or_else(|e| Text::raw(e.to_string()), |sprite| sprite.text.clone())
+}
+
+fn load_ansi_sprite(path: &str) -> Arc<Sprite> {
+    assets::store()
+        .load_sprite(path)
+        .unwrap_or_else(|e| Arc::new(Sprite::error(&e)))
+}
+
 fn draw_intro(frame: &mut Frame, game_state: &mut GameState) {
     let size = frame.area();
     frame.render_widget(Block::default().bg(Color::Rgb(0, 0, 0)), size);
@@ -37,13 +87,7 @@ fn draw_intro(frame: &mut Frame, game_state: &mut GameState) {
     let current_frame = &intro.frames[intro.current_frame_index];
 
     // draw ANSI art
-    let ansi_content = load_sprite_asset_str!(current_frame.ansi_path.as_str());
-    let fixed_content = if cfg!(windows) {
-        ansi_content.replace("\r\n", "\n")
-    } else {
-        ansi_content.to_string()
-    };
-    let ansi_text = fixed_content.as_bytes().into_text().unwrap();
+    let ansi_text = load_ansi_text(&current_frame.ansi_path);
 
     let ansi_height = ansi_text.lines.len() as u16;
     let mut ansi_width = 0;
@@ -90,10 +134,8 @@ fn draw_intro(frame: &mut Frame, game_state: &mut GameState) {
 
     let font_content = include_str!("../assets/fonts/Calvin S.flf");
     let font = FIGfont::from_content(font_content).unwrap();
-    let wrapped_text =
-        wrap_text_to_width(&intro.animated_text, message_area.width.saturating_sub(2));
-    let fig_text_str = convert_and_fix_t(&font, &wrapped_text);
-    let fig_text_height = fig_text_str.lines().count() as u16;
+    let fig_text_str = convert_glyphs(&font, intro.typewriter.glyphs());
+    let fig_text_height = fig_text_str.lines.len() as u16;
 
     let intro_text_block = Block::default().style(Style::default().bg(Color::Rgb(0, 0, 0)));
 
@@ -129,46 +171,373 @@ fn draw_enemy_ansi(frame: &mut Frame) {
     let background = Block::default().bg(Color::Rgb(0, 0, 0));
     frame.render_widget(background, size);
 
-    let ansi_content = include_str!("../assets/sprites/enemy/not_a_placeholder/battle_3.ans");
-    let fixed_content = if cfg!(windows) {
-        ansi_content.replace("\r\n", "\n")
-    } else {
-        ansi_content.to_string()
-    };
-    let enemy_text = fixed_content.as_bytes().into_text().unwrap();
-    let enemy_height = enemy_text.lines.len() as u16;
-    let mut enemy_width = 0;
-    for line in enemy_text.lines.iter() {
-        let line_width = line.width() as u16;
-        if line_width > enemy_width {
-            enemy_width = line_width;
-        }
-    }
-
-    let enemy_draw_width = enemy_width.min(size.width);
-    let enemy_draw_height = enemy_height.min(size.height);
+    let enemy = load_ansi_sprite("sprites/enemy/not_a_placeholder/battle_3.ans");
+    let enemy_draw_width = enemy.width.min(size.width);
+    let enemy_draw_height = enemy.height.min(size.height);
 
     let enemy_x = (size.x + (size.width.saturating_sub(enemy_draw_width)) / 2) as i32;
     let enemy_y = size.y + (size.height.saturating_sub(enemy_draw_height)) / 2;
 
     let enemy_area =
         ratatui::layout::Rect::new(enemy_x as u16, enemy_y, enemy_draw_width, enemy_draw_height);
-    let enemy_paragraph = Paragraph::new(enemy_text);
+    let enemy_paragraph = Paragraph::new(enemy.text.clone());
     frame.render_widget(enemy_paragraph, enemy_area);
 }
 
+fn draw_bullet_box(frame: &mut Frame, attack_phase: &AttackPhase, box_area: Rect) {
+    let bullet_box = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Thick)
+        .border_style(Style::default().fg(Color::Rgb(255, 255, 255)))
+        .style(Style::default().bg(Color::Rgb(0, 0, 0)));
+    frame.render_widget(bullet_box, box_area);
+
+    let inner = box_area.inner(ratatui::layout::Margin {
+        vertical: 1,
+        horizontal: 1,
+    });
+
+    for bullet in &attack_phase.bullets {
+        if bullet.x < 0.0 || bullet.y < 0.0 {
+            continue;
+        }
+        let bullet_rect = Rect::new(
+            inner.x + bullet.x as u16,
+            inner.y + bullet.y as u16,
+            bullet.width,
+            bullet.height,
+        )
+        .intersection(inner);
+        if !bullet_rect.is_empty() {
+            frame.render_widget(
+                Paragraph::new(bullet.symbol.as_str())
+                    .style(Style::default().fg(Color::Rgb(255, 255, 255))),
+                bullet_rect,
+            );
+        }
+    }
+
+    // blink while invincible, like the real thing
+    let soul = &attack_phase.soul;
+    let soul_visible =
+        !soul.is_invincible() || (soul.invincibility_remaining.as_millis() / 100).is_multiple_of(2);
+    if soul_visible {
+        let soul_rect =
+            Rect::new(inner.x + soul.x as u16, inner.y + soul.y as u16, 1, 1).intersection(inner);
+        frame.render_widget(
+            Paragraph::new("♥").style(Style::default().fg(Color::Rgb(255, 0, 0))),
+            soul_rect,
+        );
+    }
+}
+
+fn draw_battle_text_box(frame: &mut Frame, battle: &BattleState, area: Rect) {
+    let text_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Thick)
+        .border_style(Style::default().fg(Color::Rgb(255, 255, 255)))
+        .style(Style::default().bg(Color::Rgb(0, 0, 0)))
+        .padding(ratatui::widgets::Padding::new(4, 4, 1, 1));
+    let inner = text_block.inner(area);
+    frame.render_widget(text_block, area);
+
+    let text_style = Style::default()
+        .fg(Color::Rgb(255, 255, 255))
+        .add_modifier(Modifier::BOLD);
+    let selected_style = Style::default()
+        .fg(Color::Rgb(255, 255, 0))
+        .add_modifier(Modifier::BOLD);
+
+    let options: Vec<(String, bool)> = match battle.mode {
+        BattleMode::ActMenu => battle
+            .act_options
+            .iter()
+            .map(|option| (option.name.clone(), false))
+            .collect(),
+        BattleMode::ItemMenu if battle.items.is_empty() => {
+            vec![("(no items)".to_string(), false)]
+        }
+        BattleMode::ItemMenu => battle
+            .items
+            .iter()
+            .map(|item| (item.clone(), false))
+            .collect(),
+        BattleMode::MercyMenu => battle
+            .mercy_options()
+            .iter()
+            .enumerate()
+            .map(|(i, option)| (option.to_string(), i == 0 && battle.is_spareable()))
+            .collect(),
+        BattleMode::PlayerAttack => {
+            let bar_width = inner.width.saturating_sub(2) as usize;
+            let cursor = ((battle.attack_bar_position.clamp(0.0, 1.0)) * bar_width as f32) as usize;
+            let track: String = (0..bar_width)
+                .map(|i| {
+                    if i == cursor {
+                        '█'
+                    } else if i == bar_width / 2 {
+                        '┃'
+                    } else {
+                        '░'
+                    }
+                })
+                .collect();
+            let bar_area = Rect::new(inner.x, inner.y + inner.height / 2, inner.width, 1);
+            frame.render_widget(Paragraph::new(track).style(text_style), bar_area);
+            return;
+        }
+        _ => {
+            let lines = typewriter::to_lines(battle.narrative.glyphs(), inner.width);
+            frame.render_widget(Paragraph::new(lines).style(text_style), inner);
+            return;
+        }
+    };
+
+    let lines: Vec<ratatui::text::Line> = options
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, highlighted))| {
+            let cursor = if i == battle.submenu_index {
+                "♥ "
+            } else {
+                "  "
+            };
+            let style = if highlighted {
+                selected_style
+            } else {
+                text_style
+            };
+            ratatui::text::Line::from(vec![
+                ratatui::text::Span::styled(cursor, Style::default().fg(Color::Rgb(255, 0, 0))),
+                ratatui::text::Span::styled(format!("* {}", name), style),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn draw_battle(frame: &mut Frame, game_state: &GameState, battle: &BattleState) {
+    let size = frame.area();
+    frame.render_widget(Block::default().bg(Color::Rgb(0, 0, 0)), size);
+
+    // enemy sprite up top
+    let enemy_ansi = battle.current_sprite();
+    let enemy_text = game_state.darken_text(
+        enemy_ansi.as_bytes().into_text().unwrap_or_default(),
+        battle.deltarune.level,
+    );
+    let enemy_height = (enemy_text.lines.len() as u16).min(size.height / 2);
+    let enemy_width = enemy_text
+        .lines
+        .iter()
+        .map(|line| line.width() as u16)
+        .max()
+        .unwrap_or(0)
+        .min(size.width);
+    let enemy_area = Rect::new(
+        size.width.saturating_sub(enemy_width) / 2,
+        1,
+        enemy_width,
+        enemy_height,
+    );
+    frame.render_widget(Paragraph::new(enemy_text), enemy_area);
+
+    if let Some(damage) = battle.last_damage_dealt {
+        let damage_text = if damage == 0 {
+            "MISS".to_string()
+        } else {
+            format!("-{}", damage)
+        };
+        let hp_bar_width = 30;
+        let filled =
+            (battle.enemy_hp.max(0) as usize * hp_bar_width) / battle.enemy_max_hp.max(1) as usize;
+        let hp_bar = format!(
+            "{}  {}{}",
+            damage_text,
+            "█".repeat(filled),
+            "░".repeat(hp_bar_width - filled)
+        );
+        let hp_area = Rect::new(
+            enemy_area.x,
+            enemy_area.y + enemy_area.height,
+            enemy_area.width.max(hp_bar_width as u16 + 8),
+            1,
+        )
+        .intersection(size);
+        frame.render_widget(
+            Paragraph::new(hp_bar).style(Style::default().fg(Color::Rgb(255, 0, 0))),
+            hp_area,
+        );
+    }
+
+    // enemy speech bubble, face on the left like the overworld dialogue box
+    if battle.mode == BattleMode::EnemyDialogue {
+        let bubble_width = 60.min(size.width);
+        let bubble_area = Rect::new(
+            (enemy_area.x + enemy_area.width + 2).min(size.width.saturating_sub(bubble_width)),
+            enemy_area.y + 2,
+            bubble_width,
+            12,
+        );
+        let bubble = Block::default()
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .style(
+                Style::default()
+                    .fg(Color::Rgb(0, 0, 0))
+                    .bg(Color::Rgb(255, 255, 255)),
+            );
+        let bubble_inner = bubble.inner(bubble_area);
+        frame.render_widget(Clear, bubble_area);
+        frame.render_widget(bubble, bubble_area);
+
+        let mut text_area = bubble_inner;
+        if let Some(face) = &battle.narrative_face {
+            let face_text = face.as_bytes().into_text().unwrap_or_default();
+            let face_width = face_text
+                .lines
+                .iter()
+                .map(|line| line.width() as u16)
+                .max()
+                .unwrap_or(0)
+                .min(bubble_inner.width / 2);
+            frame.render_widget(
+                Paragraph::new(face_text),
+                Rect::new(
+                    bubble_inner.x,
+                    bubble_inner.y,
+                    face_width,
+                    bubble_inner.height,
+                ),
+            );
+            text_area = Rect::new(
+                bubble_inner.x + face_width + 1,
+                bubble_inner.y,
+                bubble_inner.width.saturating_sub(face_width + 1),
+                bubble_inner.height,
+            );
+        }
+        let lines = typewriter::to_lines(battle.narrative.glyphs(), text_area.width);
+        frame.render_widget(
+            Paragraph::new(lines).style(
+                Style::default()
+                    .fg(Color::Rgb(0, 0, 0))
+                    .add_modifier(Modifier::BOLD),
+            ),
+            text_area,
+        );
+    }
+
+    let buttons_height = 3;
+    let status_height = 1;
+    let box_height = if battle.mode == BattleMode::EnemyAttack {
+        battle
+            .attack_phase
+            .as_ref()
+            .map(|phase| phase.box_height)
+            .unwrap_or(12)
+    } else {
+        12
+    };
+    let box_width = if battle.mode == BattleMode::EnemyAttack {
+        battle
+            .attack_phase
+            .as_ref()
+            .map(|phase| phase.box_width)
+            .unwrap_or(120)
+    } else {
+        120
+    }
+    .min(size.width);
+    let box_area = Rect::new(
+        size.width.saturating_sub(box_width) / 2,
+        size.height
+            .saturating_sub(buttons_height + status_height + box_height + 2),
+        box_width,
+        box_height,
+    );
+
+    match (&battle.mode, &battle.attack_phase) {
+        (BattleMode::EnemyAttack, Some(attack_phase)) => {
+            draw_bullet_box(frame, attack_phase, box_area)
+        }
+        _ => draw_battle_text_box(frame, battle, box_area),
+    }
+
+    let stats = &game_state.player.stats;
+    let status = format!(
+        "LV {}   HP {:>2} / {:<2} {}{}",
+        stats.lv,
+        stats.hp,
+        stats.max_hp,
+        "█".repeat(stats.hp.max(0) as usize),
+        "░".repeat((stats.max_hp - stats.hp).max(0) as usize),
+    );
+    let status_area = Rect::new(
+        box_area.x,
+        box_area.y + box_area.height + 1,
+        box_area.width,
+        1,
+    )
+    .intersection(size);
+    frame.render_widget(
+        Paragraph::new(status).style(
+            Style::default()
+                .fg(Color::Rgb(255, 255, 0))
+                .add_modifier(Modifier::BOLD),
+        ),
+        status_area,
+    );
+
+    let buttons_area = Rect::new(
+        box_area.x,
+        status_area.y + status_height + 1,
+        box_area.width,
+        buttons_height,
+    )
+    .intersection(size);
+    let button_areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 4); 4])
+        .spacing(2)
+        .split(buttons_area);
+    let in_menu = battle.mode == BattleMode::Menu;
+    for (button, area) in MenuButton::ALL.iter().zip(button_areas.iter()) {
+        let selected = in_menu && *button == battle.selected_button;
+        let color = if selected {
+            Color::Rgb(255, 255, 0)
+        } else {
+            Color::Rgb(255, 128, 0)
+        };
+        let label = if selected {
+            format!("♥ {}", button.label())
+        } else {
+            button.label().to_string()
+        };
+        frame.render_widget(
+            Paragraph::new(label)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(color).add_modifier(Modifier::BOLD))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(color)),
+                ),
+            *area,
+        );
+    }
+}
+
 fn draw_dialogue(frame: &mut Frame, game_state: &mut GameState) {
     if let Some(dialogue) = game_state.dialogue_manager.current_dialogue() {
         let size = frame.area();
         frame.render_widget(Block::default().bg(Color::Rgb(0, 0, 0)), size);
 
-        let enemy_ansi = load_sprite_asset_str!(dialogue.enemy_ansi_path.as_str());
-        let fixed_enemy_ansi = if cfg!(windows) {
-            enemy_ansi.replace("\r\n", "\n")
+        let enemy_text = if dialogue.enemy_ansi_path.is_empty() {
+            Text::default()
         } else {
-            enemy_ansi.to_string()
+            load_ansi_text(&dialogue.enemy_ansi_path)
         };
-        let enemy_text = fixed_enemy_ansi.as_bytes().into_text().unwrap();
         let enemy_height = enemy_text.lines.len() as u16;
         let mut enemy_width = 0;
         for line in enemy_text.lines.iter() {
@@ -190,7 +559,7 @@ fn draw_dialogue(frame: &mut Frame, game_state: &mut GameState) {
 
         let enemy_area = ratatui::layout::Rect::new(
             enemy_x.max(0) as u16,
-            enemy_y.max(0),
+            enemy_y,
             enemy_draw_width,
             enemy_draw_height,
         );
@@ -210,7 +579,12 @@ fn draw_dialogue(frame: &mut Frame, game_state: &mut GameState) {
                     .fg(Color::Rgb(255, 255, 255))
                     .bg(Color::Rgb(255, 255, 255)),
             )
-            .title("Dialogue");
+            .title(
+                dialogue
+                    .speaker
+                    .as_deref()
+                    .map_or("Dialogue".to_string(), |speaker| format!(" {} ", speaker)),
+            );
         frame.render_widget(dialogue_block.clone(), dialogue_box_area);
 
         let face_width = (dialogue_box_area.width as f32 * 0.20) as u16;
@@ -231,52 +605,59 @@ fn draw_dialogue(frame: &mut Frame, game_state: &mut GameState) {
         let text_height = dialogue_box_area.height.saturating_sub(4);
         let text_area = ratatui::layout::Rect::new(text_x, text_y, text_width, text_height);
 
-        let face_ansi = load_sprite_asset_str!(dialogue.face_ansi_path.as_str());
-        let fixed_face_ansi = if cfg!(windows) {
-            face_ansi.replace("\r\n", "\n")
+        if !dialogue.face_ansi_path.is_empty() {
+            frame.render_widget(
+                Paragraph::new(load_ansi_text(&dialogue.face_ansi_path))
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+                face_area,
+            );
+        }
+
+        // choices sit on the box's last row, the text gets the rest
+        let choices = game_state.dialogue_manager.visible_choices();
+        let text_area = if choices.is_empty() {
+            text_area
         } else {
-            face_ansi.to_string()
+            let mut spans = Vec::new();
+            for (i, choice) in choices.iter().enumerate() {
+                let selected = i == game_state.dialogue_manager.selected_choice;
+                spans.push(ratatui::text::Span::styled(
+                    if selected { "♥ " } else { "  " },
+                    Style::default().fg(Color::Rgb(255, 0, 0)),
+                ));
+                spans.push(ratatui::text::Span::styled(
+                    format!("{}      ", choice.text),
+                    Style::default()
+                        .fg(if selected {
+                            Color::Rgb(255, 255, 0)
+                        } else {
+                            Color::Rgb(255, 255, 255)
+                        })
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            let choices_area = Rect::new(
+                text_area.x,
+                dialogue_box_area.y + dialogue_box_area.height.saturating_sub(2),
+                text_area.width,
+                1,
+            );
+            frame.render_widget(
+                Paragraph::new(ratatui::text::Line::from(spans)),
+                choices_area,
+            );
+            Rect {
+                height: text_area.height.saturating_sub(2),
+                ..text_area
+            }
         };
-        frame.render_widget(
-            Paragraph::new(fixed_face_ansi.as_bytes().into_text().unwrap())
-                .style(Style::default().add_modifier(Modifier::BOLD)),
-            face_area,
-        );
 
         let font_content = include_str!("../assets/fonts/Calvin S.flf");
         let font = FIGfont::from_content(font_content).unwrap();
 
-        let mut chunks = Vec::new();
-        let visible_text = dialogue
-            .text
-            .chars()
-            .take(game_state.dialogue_manager.visible_text_len)
-            .collect::<String>();
-        let mut remaining_text = visible_text.as_str();
-
-        while !remaining_text.is_empty() {
-            let mut best_split = remaining_text.len();
-            let mut current_width = 0;
-            let mut last_space = 0;
-
-            for (i, c) in remaining_text.char_indices() {
-                if c.is_whitespace() {
-                    last_space = i;
-                }
-                current_width += 3; // estimated width
-                if current_width > 130 {
-                    if last_space > 0 {
-                        best_split = last_space;
-                    } else {
-                        best_split = i;
-                    }
-                    break;
-                }
-            }
-            let (chunk, rest) = remaining_text.split_at(best_split);
-            chunks.push(chunk.to_string());
-            remaining_text = rest;
-        }
+        // figlet letters are about 3 columns wide, so a row holds around 43 of them
+        let chunks = typewriter::wrap_glyphs(game_state.dialogue_manager.typewriter.glyphs(), 43);
+        let chunks: Vec<&Vec<Glyph>> = chunks.iter().filter(|chunk| !chunk.is_empty()).collect();
 
         let num_chunks = chunks.len();
         let constraints: Vec<Constraint> = (0..num_chunks).map(|_| Constraint::Length(3)).collect();
@@ -288,8 +669,7 @@ fn draw_dialogue(frame: &mut Frame, game_state: &mut GameState) {
 
         for (i, chunk) in chunks.iter().enumerate() {
             if i < text_chunks.len() {
-                let wrapped_text = wrap_text_to_width(chunk, text_chunks[i].width);
-                let fig_text_str = convert_and_fix_t(&font, &wrapped_text);
+                let fig_text_str = convert_glyphs(&font, chunk);
                 let text_paragraph = Paragraph::new(fig_text_str)
                     .wrap(ratatui::widgets::Wrap { trim: false })
                     .style(Style::default().add_modifier(Modifier::BOLD));
@@ -303,14 +683,8 @@ fn draw_thank_you_screen(frame: &mut Frame, game_state: &mut GameState) {
     let size = frame.area();
     frame.render_widget(Block::default().bg(Color::Rgb(0, 0, 0)), size);
 
-    let ansi_content = load_sprite_asset_str!("assets/sprites/ME/idle/insanly_dead.ans");
-    let fixed_ansi_content = if cfg!(windows) {
-        ansi_content.replace("\r\n", "\n")
-    } else {
-        ansi_content.to_string()
-    };
     let ansi_text = game_state.darken_text(
-        fixed_ansi_content.as_bytes().into_text().unwrap(),
+        load_ansi_text("sprites/ME/idle/insanly_dead.ans"),
         game_state.deltarune.level,
     );
     let ansi_height = ansi_text.lines.len() as u16;
@@ -356,15 +730,384 @@ fn draw_thank_you_screen(frame: &mut Frame, game_state: &mut GameState) {
     frame.render_widget(Paragraph::new(fig_text), text_area);
 }
 
+fn draw_game_over_screen(frame: &mut Frame, game_state: &mut GameState) {
+    let size = frame.area();
+    frame.render_widget(Block::default().bg(Color::Rgb(0, 0, 0)), size);
+
+    let font_content = include_str!("../assets/fonts/3d.flf");
+    let font = FIGfont::from_content(font_content).unwrap();
+    let fig_text = game_state.darken_text(
+        Text::raw(convert_and_fix_t(&font, "GAME OVER")),
+        game_state.deltarune.level,
+    );
+    let fig_text_height = fig_text.lines.len() as u16;
+    let fig_text_width = fig_text
+        .lines
+        .iter()
+        .map(|line| line.width() as u16)
+        .max()
+        .unwrap_or(0)
+        .min(size.width);
+
+    let text_x = (size.width.saturating_sub(fig_text_width)) / 2;
+    let text_y = size.height / 4;
+    let text_area = Rect::new(text_x, text_y, fig_text_width, fig_text_height).intersection(size);
+    frame.render_widget(Paragraph::new(fig_text), text_area);
+
+    let mut current_y = text_y + fig_text_height + 4;
+    let determination = game_state.darken_text(
+        Text::styled(
+            "* Stay determined...",
+            Style::default()
+                .fg(Color::Rgb(255, 255, 255))
+                .add_modifier(Modifier::BOLD),
+        ),
+        game_state.deltarune.level,
+    );
+    frame.render_widget(
+        Paragraph::new(determination).alignment(Alignment::Center),
+        Rect::new(0, current_y, size.width, 1).intersection(size),
+    );
+    current_y += 4;
+
+    for (i, option) in ["Continue from last save", "Quit"].iter().enumerate() {
+        let selected = i == game_state.game_over_selection;
+        let (cursor, color) = if selected {
+            ("♥ ", Color::Rgb(255, 255, 0))
+        } else {
+            ("  ", Color::Rgb(255, 255, 255))
+        };
+        let line = game_state.darken_text(
+            Text::from(ratatui::text::Line::from(vec![
+                ratatui::text::Span::styled(cursor, Style::default().fg(Color::Rgb(255, 0, 0))),
+                ratatui::text::Span::styled(
+                    option.to_string(),
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ),
+            ])),
+            game_state.deltarune.level,
+        );
+        frame.render_widget(
+            Paragraph::new(line).alignment(Alignment::Center),
+            Rect::new(0, current_y, size.width, 1).intersection(size),
+        );
+        current_y += 2;
+    }
+
+    if game_state.show_message {
+        frame.render_widget(
+            Paragraph::new(game_state.message.as_str())
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(Color::Red)),
+            Rect::new(0, current_y + 2, size.width, 1).intersection(size),
+        );
+    }
+}
+
+fn draw_slot_picker(frame: &mut Frame, picker: &SlotPicker) {
+    let size = frame.area();
+    let width = 70.min(size.width);
+    let height = (picker.slots.len() as u16 * 2 + 6).min(size.height);
+    let area = Rect::new(
+        size.width.saturating_sub(width) / 2,
+        size.height.saturating_sub(height) / 2,
+        width,
+        height,
+    );
+
+    let title = match picker.mode {
+        SlotPickerMode::Title => " Choose a file ",
+        SlotPickerMode::Save => " Save ",
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Thick)
+        .border_style(Style::default().fg(Color::Rgb(255, 255, 255)))
+        .style(Style::default().bg(Color::Rgb(0, 0, 0)))
+        .padding(ratatui::widgets::Padding::new(2, 2, 1, 1))
+        .title(title);
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let text_style = Style::default()
+        .fg(Color::Rgb(255, 255, 255))
+        .add_modifier(Modifier::BOLD);
+    let mut lines = Vec::new();
+    for (i, slot) in picker.slots.iter().enumerate() {
+        let selected = i == picker.selected;
+        let summary = match slot {
+            SlotSummary::Empty => "- empty -".to_string(),
+            SlotSummary::Saved(save_data) => format!(
+                "LV {:<3} {:<12} {}",
+                save_data.stats.lv,
+                save_data.map_name,
+                format_play_time(std::time::Duration::from_secs(save_data.play_time_secs))
+            ),
+            SlotSummary::Unreadable(e) => format!("unreadable ({})", e),
+        };
+        let style = if selected && picker.saved {
+            Style::default()
+                .fg(Color::Rgb(255, 255, 0))
+                .add_modifier(Modifier::BOLD)
+        } else {
+            text_style
+        };
+        lines.push(ratatui::text::Line::from(vec![
+            ratatui::text::Span::styled(
+                if selected { "♥ " } else { "  " },
+                Style::default().fg(Color::Rgb(255, 0, 0)),
+            ),
+            ratatui::text::Span::styled(format!("File {}   {}", i + 1, summary), style),
+        ]));
+        lines.push(ratatui::text::Line::default());
+    }
+
+    let hint = if picker.saved {
+        "* File saved."
+    } else if picker.mode == SlotPickerMode::Save {
+        "[Z] Save   [X] Return"
+    } else {
+        "[Z] Play"
+    };
+    lines.push(ratatui::text::Line::styled(hint, text_style));
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn menu_block() -> Block<'static> {
+    Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Thick)
+        .border_style(Style::default().fg(Color::Rgb(255, 255, 255)))
+        .style(Style::default().bg(Color::Rgb(0, 0, 0)))
+        .padding(ratatui::widgets::Padding::new(2, 2, 1, 1))
+}
+
+fn menu_line(text: String, selected: bool) -> ratatui::text::Line<'static> {
+    ratatui::text::Line::from(vec![
+        ratatui::text::Span::styled(
+            if selected { "♥ " } else { "  " },
+            Style::default().fg(Color::Rgb(255, 0, 0)),
+        ),
+        ratatui::text::Span::styled(
+            text,
+            Style::default()
+                .fg(Color::Rgb(255, 255, 255))
+                .add_modifier(Modifier::BOLD),
+        ),
+    ])
+}
+
+// ITEM / STAT / CELL, laid out like Undertale's: stats and options on the left,
+// the open page on the right and whatever got said in a box at the bottom
+fn draw_overworld_menu(frame: &mut Frame, game_state: &GameState, menu: &OverworldMenu) {
+    let size = frame.area();
+    let stats = &game_state.player.stats;
+    let text_style = Style::default()
+        .fg(Color::Rgb(255, 255, 255))
+        .add_modifier(Modifier::BOLD);
+
+    let summary_area = Rect::new(4, 2, 24, 7).intersection(size);
+    let summary = vec![
+        ratatui::text::Line::styled(format!("LV {}", stats.lv), text_style),
+        ratatui::text::Line::styled(format!("HP {}/{}", stats.hp, stats.max_hp), text_style),
+        ratatui::text::Line::styled(format!("G  {}", stats.gold), text_style),
+    ];
+    let block = menu_block();
+    let inner = block.inner(summary_area);
+    frame.render_widget(Clear, summary_area);
+    frame.render_widget(block, summary_area);
+    frame.render_widget(Paragraph::new(summary), inner);
+
+    let options_area = Rect::new(4, 10, 24, 11).intersection(size);
+    let options: Vec<_> = MENU_OPTIONS
+        .iter()
+        .enumerate()
+        .map(|(i, option)| {
+            let line = menu_line(
+                option.to_string(),
+                i == menu.selected && menu.page == MenuPage::Main,
+            );
+            // nothing to open with an empty inventory
+            if i == 0 && menu.item_names.is_empty() {
+                line.style(Style::default().fg(Color::Rgb(128, 128, 128)))
+            } else {
+                line
+            }
+        })
+        .flat_map(|line| [line, ratatui::text::Line::default()])
+        .collect();
+    let block = menu_block();
+    let inner = block.inner(options_area);
+    frame.render_widget(Clear, options_area);
+    frame.render_widget(block, options_area);
+    frame.render_widget(Paragraph::new(options), inner);
+
+    let page_lines: Vec<ratatui::text::Line> = match menu.page {
+        MenuPage::Main => Vec::new(),
+        MenuPage::Items | MenuPage::ItemActions => {
+            let mut lines: Vec<_> = menu
+                .item_names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    menu_line(
+                        name.clone(),
+                        i == menu.item_index && menu.page == MenuPage::Items,
+                    )
+                })
+                .collect();
+            lines.push(ratatui::text::Line::default());
+            let mut actions = Vec::new();
+            for (i, action) in ITEM_ACTIONS.iter().enumerate() {
+                actions.extend(
+                    menu_line(
+                        format!("{:<8}", action),
+                        i == menu.action_index && menu.page == MenuPage::ItemActions,
+                    )
+                    .spans,
+                );
+            }
+            lines.push(ratatui::text::Line::from(actions));
+            lines
+        }
+        MenuPage::Stat => {
+            let equipment_name = |equipment: &Option<crate::game::player::Equipment>| {
+                equipment
+                    .as_ref()
+                    .map(|equipment| {
+                        Item::load(&equipment.item)
+                            .map(|item| item.name)
+                            .unwrap_or_else(|_| equipment.item.clone())
+                    })
+                    .unwrap_or_else(|| "-".to_string())
+            };
+            let bonus = |equipment: &Option<crate::game::player::Equipment>| {
+                equipment.as_ref().map_or(0, |equipment| equipment.bonus)
+            };
+            let next = stats
+                .exp_to_next_level()
+                .map_or("-".to_string(), |next| next.to_string());
+            [
+                format!("LV {}", stats.lv),
+                format!("HP {} / {}", stats.hp, stats.max_hp),
+                String::new(),
+                format!(
+                    "AT {} ({})      EXP: {}",
+                    stats.atk,
+                    bonus(&stats.weapon),
+                    stats.exp
+                ),
+                format!(
+                    "DF {} ({})      NEXT: {}",
+                    stats.def,
+                    bonus(&stats.armor),
+                    next
+                ),
+                String::new(),
+                format!("WEAPON: {}", equipment_name(&stats.weapon)),
+                format!("ARMOR: {}", equipment_name(&stats.armor)),
+                String::new(),
+                format!("GOLD: {}", stats.gold),
+            ]
+            .into_iter()
+            .map(|line| ratatui::text::Line::styled(line, text_style))
+            .collect()
+        }
+    };
+    if !page_lines.is_empty() {
+        let page_area = Rect::new(30, 2, 50, page_lines.len() as u16 + 4).intersection(size);
+        let block = menu_block();
+        let inner = block.inner(page_area);
+        frame.render_widget(Clear, page_area);
+        frame.render_widget(block, page_area);
+        frame.render_widget(Paragraph::new(page_lines), inner);
+    }
+
+    if let Some(text) = &menu.text {
+        let width = 70.min(size.width);
+        let text_area = Rect::new(
+            size.width.saturating_sub(width) / 2,
+            size.height.saturating_sub(8),
+            width,
+            7,
+        )
+        .intersection(size);
+        let block = menu_block();
+        let inner = block.inner(text_area);
+        frame.render_widget(Clear, text_area);
+        frame.render_widget(block, text_area);
+        frame.render_widget(
+            Paragraph::new(wrap_text_to_width(text, inner.width)).style(text_style),
+            inner,
+        );
+    }
+}
+
+fn draw_settings_menu(frame: &mut Frame, game_state: &GameState, menu: &SettingsMenu) {
+    let size = frame.area();
+    let value_style = Style::default().fg(Color::Rgb(255, 255, 0));
+    let mut lines: Vec<_> = SETTINGS_ROWS
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let value = if i == menu.selected && menu.rebinding {
+                "PRESS A KEY".to_string()
+            } else {
+                game_state.settings.value(*row, &game_state.keymap)
+            };
+            let mut line = menu_line(format!("{:<16}", row.label()), i == menu.selected);
+            line.spans
+                .push(ratatui::text::Span::styled(value, value_style));
+            line
+        })
+        .collect();
+    lines.push(ratatui::text::Line::default());
+    lines.push(ratatui::text::Line::styled(
+        "LEFT/RIGHT: CHANGE   Z: REBIND   X: BACK",
+        Style::default().fg(Color::Rgb(128, 128, 128)),
+    ));
+
+    let width = 50.min(size.width);
+    let height = (lines.len() as u16 + 4).min(size.height);
+    let area = Rect::new(
+        size.width.saturating_sub(width) / 2,
+        size.height.saturating_sub(height) / 2,
+        width,
+        height,
+    );
+    let block = menu_block().title(" SETTINGS ");
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+// terminals without truecolor get every rgb cell squashed down to what they can show
+fn apply_color_mode(frame: &mut Frame, color_mode: ColorMode) {
+    if color_mode == ColorMode::TrueColor {
+        return;
+    }
+    for cell in &mut frame.buffer_mut().content {
+        cell.fg = color_mode.convert(cell.fg);
+        cell.bg = color_mode.convert(cell.bg);
+    }
+}
+
 const MIN_TERMINAL_WIDTH: u16 = 225;
 const MIN_TERMINAL_HEIGHT: u16 = 56;
 
 pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
+    draw_screen(frame, game_state);
+    apply_color_mode(frame, game_state.settings.color_mode);
+}
+
+fn draw_screen(frame: &mut Frame, game_state: &mut GameState) {
     let size = frame.area();
 
     if size.width < MIN_TERMINAL_WIDTH || size.height < MIN_TERMINAL_HEIGHT {
         game_state.show_message = false;
-        game_state.animated_message_content.clear();
+        game_state.message_typewriter.clear();
         game_state.message.clear();
 
         frame.render_widget(Block::default().bg(Color::Reset), size);
@@ -378,25 +1121,9 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
         let debug_area = ratatui::layout::Rect::new(0, 0, size.width, 1);
         frame.render_widget(debug_paragraph, debug_area);
 
-        let peefest_ansi_content = include_str!("../assets/sprites/peefest.ans");
-        let fixed_peefest_content = if cfg!(windows) {
-            peefest_ansi_content.replace("\r\n", "\n")
-        } else {
-            peefest_ansi_content.to_string()
-        };
-        let peefest_text = fixed_peefest_content.as_bytes().into_text().unwrap();
-
-        let peefest_height = peefest_text.lines.len() as u16;
-        let mut peefest_width = 0;
-        for line in peefest_text.lines.iter() {
-            let line_width = line.width() as u16;
-            if line_width > peefest_width {
-                peefest_width = line_width;
-            }
-        }
-
-        let peefest_draw_width = peefest_width.min(size.width);
-        let peefest_draw_height = peefest_height.min(size.height);
+        let peefest = load_ansi_sprite("sprites/peefest.ans");
+        let peefest_draw_width = peefest.width.min(size.width);
+        let peefest_draw_height = peefest.height.min(size.height);
 
         let peefest_x = (size.width.saturating_sub(peefest_draw_width)) / 2;
         let peefest_y = (size.height.saturating_sub(peefest_draw_height)) / 2;
@@ -407,7 +1134,7 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
             peefest_draw_width,
             peefest_draw_height,
         );
-        frame.render_widget(Paragraph::new(peefest_text), peefest_area);
+        frame.render_widget(Paragraph::new(peefest.text.clone()), peefest_area);
 
         let messages = vec![
             "please make ur terminal window bigger",
@@ -458,6 +1185,26 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
         return;
     }
 
+    if let Some(picker) = &game_state.slot_picker
+        && picker.mode == SlotPickerMode::Title
+    {
+        frame.render_widget(Block::default().bg(Color::Rgb(0, 0, 0)), size);
+        draw_slot_picker(frame, picker);
+        return;
+    }
+
+    if game_state.game_over_active {
+        draw_game_over_screen(frame, game_state);
+        return;
+    }
+
+    if let Some(battle) = &game_state.battle
+        && battle.mode != BattleMode::Encounter
+    {
+        draw_battle(frame, game_state, battle);
+        return;
+    }
+
     if game_state.dialogue_active {
         draw_dialogue(frame, game_state);
         return;
@@ -468,7 +1215,11 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
         return;
     }
 
-    if !game_state.dialogue_active && game_state.deltarune.level >= 99 {
+    // a script fading to black just wants a black screen
+    if !game_state.dialogue_active
+        && !game_state.is_script_running()
+        && game_state.deltarune.level >= 99
+    {
         draw_thank_you_screen(frame, game_state);
         return;
     }
@@ -480,7 +1231,12 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
 
     frame.render_widget(Block::default().bg(Color::Rgb(0, 0, 0)), size);
 
-    if game_state.is_flickering && game_state.show_flicker_black_screen {
+    if game_state
+        .battle
+        .as_ref()
+        .is_some_and(|battle| battle.show_flicker_black_screen)
+        && game_state.settings.screen_flicker
+    {
         frame.render_widget(Block::default().bg(Color::Rgb(0, 0, 0)), size);
 
         let ascii_art = "  ▄ ▄  \n■██▄██■\n ▀███▀ \n   ▀   ";
@@ -489,7 +1245,7 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
         let ascii_width = 7;
         let ascii_height = 4;
 
-        let (_, player_sprite_width, player_sprite_height) = game_state.player.get_sprite_content();
+        let (player_sprite_width, player_sprite_height) = game_state.player.sprite_size();
 
         let player_x_on_screen = (game_state.player.x as u16).saturating_sub(game_state.camera_x);
         let player_y_on_screen = (game_state.player.y as u16).saturating_sub(game_state.camera_y);
@@ -507,7 +1263,7 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
     let combined_map_text = game_state.get_combined_map_text(size, game_state.deltarune.level);
 
     let map_paragraph = Paragraph::new(combined_map_text)
-        .scroll((game_state.camera_y, game_state.camera_x))
+        .scroll((0, game_state.camera_x))
         .style(Style::default().bg(Color::Rgb(0, 0, 0)));
 
     let map_block = Block::default().style(Style::default().bg(Color::Rgb(0, 0, 0)));
@@ -515,36 +1271,36 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
     frame.render_widget(map_paragraph, size);
 
     let current_map_key = (game_state.current_map_row, game_state.current_map_col);
-    if let Some(current_map) = game_state.loaded_maps.get(&current_map_key) {
-        if let crate::game::map::MapKind::Objects = current_map.kind {
-            let interaction_rect = game_state.player.get_interaction_rect();
-
-            let select_box_x_on_screen = interaction_rect.x.saturating_sub(game_state.camera_x);
-            let select_box_y_on_screen = interaction_rect.y.saturating_sub(game_state.camera_y);
+    if let Some(current_map) = game_state.loaded_maps.get(&current_map_key)
+        && let crate::game::map::MapKind::Objects = current_map.kind
+    {
+        let interaction_rect = game_state.player.get_interaction_rect();
+
+        let select_box_x_on_screen = interaction_rect.x.saturating_sub(game_state.camera_x);
+        let select_box_y_on_screen = interaction_rect.y.saturating_sub(game_state.camera_y);
+
+        let draw_rect = ratatui::layout::Rect::new(
+            select_box_x_on_screen,
+            select_box_y_on_screen,
+            interaction_rect.width,
+            interaction_rect.height,
+        );
 
-            let draw_rect = ratatui::layout::Rect::new(
-                select_box_x_on_screen,
-                select_box_y_on_screen,
-                interaction_rect.width,
-                interaction_rect.height,
+        let clamped_rect = draw_rect.intersection(size);
+        if !clamped_rect.is_empty() {
+            let select_box_paragraph = Paragraph::new("").block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Rgb(0, 255, 0))),
             );
-
-            let clamped_rect = draw_rect.intersection(size);
-            if !clamped_rect.is_empty() {
-                let select_box_paragraph = Paragraph::new("").block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .border_style(Style::default().fg(Color::Rgb(0, 255, 0))),
-                );
-                frame.render_widget(select_box_paragraph, clamped_rect);
-            }
+            frame.render_widget(select_box_paragraph, clamped_rect);
         }
     }
 
     let mut drawable_elements: Vec<(i32, u8, Text<'static>, i32, i32, u16, u16)> = Vec::new(); // (y_sort_key, z_index, ansi_content, x, y, width, height) ദ്ദി/ᐠ｡‸｡ᐟ\
 
-    let (player_sprite_content, player_sprite_width, player_sprite_height) =
-        game_state.player.get_sprite_content();
+    let player_sprite = game_state.player.sprite();
+    let (player_sprite_width, player_sprite_height) = (player_sprite.width, player_sprite.height);
     let player_x_on_screen =
         (game_state.player.x as i32).saturating_sub(game_state.camera_x as i32);
     let player_y_on_screen =
@@ -552,27 +1308,27 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
     drawable_elements.push((
         player_y_on_screen + player_sprite_height as i32,
         1,
-        player_sprite_content,
+        player_sprite.text.clone(),
         player_x_on_screen,
         player_y_on_screen,
         player_sprite_width,
         player_sprite_height,
     ));
 
-    if game_state.is_placing_sprite {
-        if let Some(pending_sprite) = &game_state.pending_placed_sprite {
-            let sprite_x_on_screen = pending_sprite.x as i32 - game_state.camera_x as i32;
-            let sprite_y_on_screen = pending_sprite.y as i32 - game_state.camera_y as i32;
-            drawable_elements.push((
-                sprite_y_on_screen + pending_sprite.height as i32,
-                0,
-                pending_sprite.ansi_content.as_bytes().into_text().unwrap(),
-                sprite_x_on_screen,
-                sprite_y_on_screen,
-                pending_sprite.width as u16,
-                pending_sprite.height as u16,
-            ));
-        }
+    if game_state.is_placing_sprite
+        && let Some(pending_sprite) = &game_state.pending_placed_sprite
+    {
+        let sprite_x_on_screen = pending_sprite.x as i32 - game_state.camera_x as i32;
+        let sprite_y_on_screen = pending_sprite.y as i32 - game_state.camera_y as i32;
+        drawable_elements.push((
+            sprite_y_on_screen + pending_sprite.height as i32,
+            0,
+            sprite_text(pending_sprite.sprite()),
+            sprite_x_on_screen,
+            sprite_y_on_screen,
+            pending_sprite.width as u16,
+            pending_sprite.height as u16,
+        ));
     }
 
     if let Some(current_map) = game_state.loaded_maps.get(&current_map_key) {
@@ -582,7 +1338,7 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
             drawable_elements.push((
                 sprite_y_on_screen + placed_sprite.height as i32,
                 0,
-                placed_sprite.ansi_content.as_bytes().into_text().unwrap(),
+                sprite_text(placed_sprite.sprite()),
                 sprite_x_on_screen,
                 sprite_y_on_screen,
                 placed_sprite.width as u16,
@@ -661,28 +1417,28 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
         debug::draw::draw_debug_info(frame, game_state);
     }
 
-    if game_state.is_drawing_select_box {
-        if let Some((start_x, start_y)) = game_state.select_box_start_coords {
-            let current_x = game_state.player.x as u16;
-            let current_y = game_state.player.y as u16;
+    if game_state.is_drawing_select_box
+        && let Some((start_x, start_y)) = game_state.select_box_start_coords
+    {
+        let current_x = game_state.player.x as u16;
+        let current_y = game_state.player.y as u16;
 
-            let min_x = start_x.min(current_x);
-            let max_x = start_x.max(current_x);
-            let min_y = start_y.min(current_y);
-            let max_y = start_y.max(current_y);
+        let min_x = start_x.min(current_x);
+        let max_x = start_x.max(current_x);
+        let min_y = start_y.min(current_y);
+        let max_y = start_y.max(current_y);
 
-            let width = max_x.saturating_sub(min_x).saturating_add(1);
-            let height = max_y.saturating_sub(min_y).saturating_add(1);
+        let width = max_x.saturating_sub(min_x).saturating_add(1);
+        let height = max_y.saturating_sub(min_y).saturating_add(1);
 
-            let draw_x = min_x.saturating_sub(game_state.camera_x);
-            let draw_y = min_y.saturating_sub(game_state.camera_y);
+        let draw_x = min_x.saturating_sub(game_state.camera_x);
+        let draw_y = min_y.saturating_sub(game_state.camera_y);
 
-            let draw_rect = ratatui::layout::Rect::new(draw_x, draw_y, width, height);
-            let drawing_block = Block::default()
-                .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Rgb(255, 255, 0)));
-            frame.render_widget(drawing_block, draw_rect);
-        }
+        let draw_rect = ratatui::layout::Rect::new(draw_x, draw_y, width, height);
+        let drawing_block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Rgb(255, 255, 0)));
+        frame.render_widget(drawing_block, draw_rect);
     }
 
     if game_state.debug_mode {
@@ -703,7 +1459,7 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
     }
 
     if game_state.show_message {
-        if game_state.animated_message_content.is_empty() {
+        if game_state.message_typewriter.is_empty() {
             return;
         }
         let message_block = Block::default()
@@ -724,7 +1480,7 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
 
         let font_content = include_str!("../assets/fonts/Calvin S.flf");
         let font = FIGfont::from_content(font_content).unwrap();
-        let ascii_art = convert_and_fix_t(&font, &game_state.animated_message_content);
+        let ascii_art = convert_glyphs(&font, game_state.message_typewriter.glyphs());
         let message_paragraph = Paragraph::new(ascii_art)
             .wrap(ratatui::widgets::Wrap { trim: false })
             .style(
@@ -925,8 +1681,20 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
         frame.render_widget(input_paragraph, input_area);
     }
 
+    if let Some(picker) = &game_state.slot_picker {
+        draw_slot_picker(frame, picker);
+    }
+
+    if let Some(menu) = &game_state.menu {
+        draw_overworld_menu(frame, game_state, menu);
+    }
+
+    if let Some(menu) = &game_state.settings_menu {
+        draw_settings_menu(frame, game_state, menu);
+    }
+
     if game_state.esc_press_start_time.is_some() {
-        let exiting_text_lines = vec![
+        let exiting_text_lines = [
             "╔═╗═╗ ╦╦╔╦╗╦╔╗╔╔═╗",
             "║╣ ╔╩╦╝║ ║ ║║║║║ ╦",
             "╚═╝╩ ╚═╩ ╩ ╩╝╚╝╚═╝",
@@ -940,7 +1708,7 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
         let dot_line_1 = "▄██▄ ".repeat(num_dots);
         let dot_line_2 = "▀██▀ ".repeat(num_dots);
 
-        let combined_lines = vec![
+        let combined_lines = [
             format!("{}\n{}", exiting_text_lines[0], dot_line_0),
             format!("{}\n{}", exiting_text_lines[1], dot_line_1),
             format!("{}\n{}", exiting_text_lines[2], dot_line_2),
@@ -965,3 +1733,124 @@ pub fn draw(frame: &mut Frame, game_state: &mut GameState) {
         frame.render_widget(paragraph, area);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::dialogue::{Dialogue, DialogueGraph, DialogueManager};
+    use crate::game::map::Map;
+    use crate::game::state::TeleportState;
+    use crate::harness::TestHarness;
+    use crate::snapshot::assert_snapshot;
+    use ratatui::{Terminal, backend::TestBackend, buffer::Buffer};
+
+    fn render(game_state: &mut GameState, width: u16, height: u16) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| draw(frame, game_state)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn empty_map() -> Map {
+        Map {
+            name: "map_0_0".to_string(),
+            ..Map::default()
+        }
+    }
+
+    // map_1_2 with the player standing next to its placed sprite, the camera caught up
+    fn overworld() -> TestHarness {
+        let mut harness = TestHarness::new(Map::load("map_1_2").unwrap());
+        harness.frame_size = Rect::new(0, 0, MIN_TERMINAL_WIDTH, MIN_TERMINAL_HEIGHT);
+        harness.game_state.player.x = 150.0;
+        harness.game_state.player.y = 10.0;
+        harness.update();
+        harness
+    }
+
+    #[test]
+    fn intro() {
+        let mut game_state = GameState::from_map(empty_map());
+        game_state.intro_state.typewriter.skip();
+        let buffer = render(&mut game_state, MIN_TERMINAL_WIDTH, MIN_TERMINAL_HEIGHT);
+        assert_snapshot("intro", &buffer);
+    }
+
+    #[test]
+    fn overworld_with_placed_sprites() {
+        let mut harness = overworld();
+        assert!(
+            !harness.game_state.loaded_maps[&(1, 2)]
+                .placed_sprites
+                .is_empty()
+        );
+        let buffer = render(
+            &mut harness.game_state,
+            MIN_TERMINAL_WIDTH,
+            MIN_TERMINAL_HEIGHT,
+        );
+        assert_snapshot("overworld", &buffer);
+    }
+
+    #[test]
+    fn overworld_scrolled_down() {
+        let mut harness = overworld();
+        harness.game_state.player.y = 60.0;
+        harness.wait(std::time::Duration::from_secs(2));
+        assert!(harness.game_state.camera_y > 0);
+        let buffer = render(
+            &mut harness.game_state,
+            MIN_TERMINAL_WIDTH,
+            MIN_TERMINAL_HEIGHT,
+        );
+        assert_snapshot("overworld_scrolled", &buffer);
+    }
+
+    #[test]
+    fn debug_overlay() {
+        let mut harness = overworld();
+        harness.game_state.debug_mode = true;
+        let buffer = render(
+            &mut harness.game_state,
+            MIN_TERMINAL_WIDTH,
+            MIN_TERMINAL_HEIGHT,
+        );
+        assert_snapshot("debug_overlay", &buffer);
+    }
+
+    #[test]
+    fn dialogue_box_with_face() {
+        let mut game_state = GameState::from_map(empty_map());
+        game_state.intro_active = false;
+        game_state.dialogue_active = true;
+        game_state.dialogue_manager =
+            DialogueManager::new(DialogueGraph::from_lines(vec![Dialogue {
+                speaker: Some("Youssef".to_string()),
+                enemy_ansi_path: String::new(),
+                face_ansi_path: "sprites/faces/face_smile.ans".to_string(),
+                voice: None,
+                text: "* Hi there.".to_string(),
+                next: None,
+                choices: Vec::new(),
+                events: Vec::new(),
+            }]));
+        game_state.dialogue_manager.skip_animation();
+        let buffer = render(&mut game_state, MIN_TERMINAL_WIDTH, MIN_TERMINAL_HEIGHT);
+        assert_snapshot("dialogue", &buffer);
+    }
+
+    #[test]
+    fn thank_you_screen() {
+        let mut game_state = GameState::from_map(empty_map());
+        game_state.intro_active = false;
+        game_state.teleport_state = TeleportState::ThankYouScreen;
+        let buffer = render(&mut game_state, MIN_TERMINAL_WIDTH, MIN_TERMINAL_HEIGHT);
+        assert_snapshot("thank_you", &buffer);
+    }
+
+    #[test]
+    fn terminal_too_small() {
+        let mut game_state = GameState::from_map(empty_map());
+        let buffer = render(&mut game_state, 120, 40);
+        assert_snapshot("terminal_too_small", &buffer);
+    }
+}
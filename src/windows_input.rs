@@ -114,4 +114,4 @@ fn vk_to_keycode(vk_code: i32) -> Option<KeyCode> {
             None
         }
     }
-}
\ No newline at end of file
+}